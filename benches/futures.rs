@@ -0,0 +1,93 @@
+#![feature(test)]
+#![feature(type_ascription)]
+
+extern crate future;
+extern crate test;
+
+use future::config::{self, AwaitStrategy};
+use std::thread;
+use test::Bencher;
+
+#[bench]
+fn resolved_value(b: &mut Bencher) {
+    b.iter(|| future::await(future::value::<i64, ()>(1)))
+}
+
+#[bench]
+fn set_then_register(b: &mut Bencher) {
+    b.iter(|| {
+        let (f, setter) = future::new::<i64, ()>();
+        setter.set_result(Ok(1): Result<i64, ()>);
+        future::await(f.map(|i| i + 1))
+    })
+}
+
+#[bench]
+fn register_then_set(b: &mut Bencher) {
+    b.iter(|| {
+        let (f, setter) = future::new::<i64, ()>();
+        let f = f.map(|i| i + 1);
+        setter.set_result(Ok(1): Result<i64, ()>);
+        future::await(f)
+    })
+}
+
+#[bench]
+fn long_chain(b: &mut Bencher) {
+    b.iter(|| {
+        let (f, setter) = future::new::<i64, ()>();
+        let f = (0..100).fold(f, |f, _| f.map(|i| i + 1));
+        setter.set_result(Ok(0): Result<i64, ()>);
+        future::await(f)
+    })
+}
+
+/// The baseline for the `recycle` feature: compare `cargo bench many_short_lived` against
+/// `cargo bench --features recycle many_short_lived`.
+#[bench]
+fn many_short_lived(b: &mut Bencher) {
+    b.iter(|| {
+        (0..1000).map(|i| {
+            let (f, setter) = future::new::<i64, ()>();
+            setter.set_result(Ok(i): Result<i64, ()>);
+            future::await(f).unwrap()
+        }).sum::<i64>()
+    })
+}
+
+#[bench]
+fn large_result(b: &mut Bencher) {
+    b.iter(|| future::await(future::value::<[u64; 32], ()>([0; 32]).map(|a| a[0])))
+}
+
+#[bench]
+fn and_thenf_value(b: &mut Bencher) {
+    b.iter(|| future::await(future::value::<i64, ()>(1).and_thenf(|i| future::value::<i64, ()>(i + 1))))
+}
+
+#[bench]
+fn and_thenf_ready(b: &mut Bencher) {
+    b.iter(|| future::await(future::value::<i64, ()>(1).and_thenf(|i| future::ready_ok::<i64, ()>(i + 1))))
+}
+
+fn await_cross_thread(b: &mut Bencher, strategy: AwaitStrategy) {
+    config::set_await_strategy(strategy);
+    b.iter(|| {
+        let (f, setter) = future::new::<i64, ()>();
+        let producer = thread::spawn(move || setter.set_result(Ok(1): Result<i64, ()>));
+        let result = future::await(f);
+        producer.join().unwrap();
+        result
+    });
+    config::set_await_strategy(AwaitStrategy::Park);
+}
+
+#[bench]
+fn await_cross_thread_park(b: &mut Bencher) {
+    await_cross_thread(b, AwaitStrategy::Park)
+}
+
+#[bench]
+fn await_cross_thread_spin(b: &mut Bencher) {
+    await_cross_thread(b, AwaitStrategy::SpinThenPark { spins: 10000 })
+}
@@ -0,0 +1,212 @@
+use super::{err, Future, Service};
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A `Service` limiting the number of calls in flight to an underlying service, adjusting the
+/// limit to the latency it observes (additive increase, multiplicative decrease). Each call that
+/// succeeds within `target` raises the limit by about one per limit's worth of calls; each call
+/// that fails or takes longer than `target` cuts it by the backoff factor. Calls over the limit
+/// fail immediately with `Overloaded`, shedding load before the underlying service is swamped.
+/// Clones share the same limit.
+/// # Examples
+/// ```
+/// use future;
+/// use future::{AdaptiveLimit, Overloaded, Service};
+/// use future::timer;
+/// use std::time::Duration;
+///
+/// let slow = |req: u64| -> future::Future<u64, Overloaded> {
+///     timer::sleep(Duration::from_millis(20)).transform(move |_| Ok(req))
+/// };
+/// let limited = AdaptiveLimit::new(slow, Duration::from_millis(100)).bounds(1, 1);
+///
+/// let first = limited.call(1);
+/// assert_eq!(Err(Overloaded), future::await(limited.call(2)));
+/// assert_eq!(Ok(1), future::await(first));
+/// ```
+pub struct AdaptiveLimit<Req, Resp: 'static, E: 'static> {
+    service: Arc<Service<Req, Resp, E>>,
+    target: Duration,
+    min: usize,
+    max: usize,
+    backoff: f64,
+    state: Arc<Mutex<LimitState>>
+}
+
+struct LimitState {
+    limit: f64,
+    in_flight: usize
+}
+
+impl<Req: 'static, Resp: 'static, E: 'static> AdaptiveLimit<Req, Resp, E> {
+    /// Limits `service`, starting at 10 calls in flight, between 1 and 1000, backing off by 0.9.
+    pub fn new<S>(service: S, target: Duration) -> AdaptiveLimit<Req, Resp, E>
+        where S: Service<Req, Resp, E> + 'static
+    {
+        AdaptiveLimit {
+            service: Arc::new(service),
+            target: target,
+            min: 1,
+            max: 1000,
+            backoff: 0.9,
+            state: Arc::new(Mutex::new(LimitState { limit: 10.0, in_flight: 0 }))
+        }
+    }
+
+    /// Keeps the limit between `min` and `max`, clamping the current limit into that range.
+    /// # Panics
+    /// This will panic if `min` is 0 or greater than `max`.
+    pub fn bounds(mut self, min: usize, max: usize) -> AdaptiveLimit<Req, Resp, E> {
+        assert!(min > 0 && min <= max, "AdaptiveLimit requires 0 < min <= max");
+        self.min = min;
+        self.max = max;
+        {
+            let mut state = self.state.lock().unwrap();
+            state.limit = state.limit.max(min as f64).min(max as f64);
+        }
+        self
+    }
+
+    /// The factor, between 0 and 1, the limit is multiplied by after a slow or failed call.
+    /// # Panics
+    /// This will panic if `backoff` is not greater than 0 and at most 1.
+    pub fn backoff(mut self, backoff: f64) -> AdaptiveLimit<Req, Resp, E> {
+        assert!(backoff > 0.0 && backoff <= 1.0, "AdaptiveLimit requires 0 < backoff <= 1");
+        self.backoff = backoff;
+        self
+    }
+
+    /// The number of calls currently allowed in flight.
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit as usize
+    }
+
+    /// The number of calls currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight
+    }
+
+    fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.in_flight >= state.limit as usize {
+            return false;
+        }
+        state.in_flight += 1;
+        true
+    }
+
+    fn record(&self, latency: Duration, success: bool) {
+        // A poisoned lock means this is being dropped by an unwinding panic.
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return
+        };
+        state.in_flight -= 1;
+        let limit = if success && latency <= self.target {
+            state.limit + 1.0 / state.limit
+        } else {
+            state.limit * self.backoff
+        };
+        state.limit = limit.max(self.min as f64).min(self.max as f64);
+    }
+}
+
+impl<Req, Resp, E> Service<Req, Resp, E> for AdaptiveLimit<Req, Resp, E>
+    where Req: 'static,
+          Resp: 'static,
+          E: From<Overloaded> + 'static
+{
+    fn call(&self, req: Req) -> Future<Resp, E> {
+        if !self.try_acquire() {
+            return err(E::from(Overloaded));
+        }
+        let mut permit = Permit { limiter: Some(self.clone()), started: Instant::now() };
+        self.service.call(req).on_completion(move |result| permit.record(result.is_ok()))
+    }
+}
+
+/// The slot of a call in flight. Dropped without recording, as it is when the service's
+/// `FutureSetter` is dropped, it frees the slot and counts the call as failed.
+struct Permit<Req: 'static, Resp: 'static, E: 'static> {
+    limiter: Option<AdaptiveLimit<Req, Resp, E>>,
+    started: Instant
+}
+
+impl<Req: 'static, Resp: 'static, E: 'static> Permit<Req, Resp, E> {
+    fn record(&mut self, success: bool) {
+        if let Some(limiter) = self.limiter.take() {
+            limiter.record(self.started.elapsed(), success);
+        }
+    }
+}
+
+impl<Req: 'static, Resp: 'static, E: 'static> Drop for Permit<Req, Resp, E> {
+    fn drop(&mut self) {
+        self.record(false);
+    }
+}
+
+impl<Req, Resp: 'static, E: 'static> Clone for AdaptiveLimit<Req, Resp, E> {
+    fn clone(&self) -> AdaptiveLimit<Req, Resp, E> {
+        AdaptiveLimit {
+            service: self.service.clone(),
+            target: self.target,
+            min: self.min,
+            max: self.max,
+            backoff: self.backoff,
+            state: self.state.clone()
+        }
+    }
+}
+
+/// An Error indicating that a call was shed because too many calls were already in flight.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Overloaded;
+
+impl fmt::Display for Overloaded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Overloaded")
+    }
+}
+
+impl Error for Overloaded {
+    fn description(&self) -> &str {
+        "The call was rejected because too many calls were already in flight"
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, await_safe, new, value};
+
+    #[test]
+    fn limit_grows_with_fast_calls_and_shrinks_with_slow_ones() {
+        let fast = AdaptiveLimit::new(|req: i64| value::<i64, Overloaded>(req), Duration::from_secs(1));
+        for i in 0..50 {
+            assert_eq!(await(fast.call(i)), Ok(i));
+        }
+        assert!(fast.limit() > 10, "limit {}", fast.limit());
+        assert_eq!(fast.in_flight(), 0);
+
+        let failing = AdaptiveLimit::new(|_: i64| err::<i64, Overloaded>(Overloaded), Duration::from_secs(1))
+            .bounds(2, 100);
+        for i in 0..50 {
+            let _ = await(failing.call(i));
+        }
+        assert_eq!(failing.limit(), 2);
+    }
+
+    #[test]
+    fn dropped_setters_free_their_slot_and_back_off() {
+        let limited = AdaptiveLimit::new(|_: i64| new::<i64, Overloaded>().0, Duration::from_secs(1))
+            .bounds(1, 4);
+        let limit = limited.limit();
+        for i in 0..10 {
+            assert!(await_safe(limited.call(i)).is_err());
+        }
+        assert_eq!(limited.in_flight(), 0);
+        assert!(limited.limit() < limit, "limit {}", limited.limit());
+    }
+}
@@ -0,0 +1,253 @@
+use super::{Classification, FailureClassifier, Future, Service};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How a `Balancer` chooses the member to send a call to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Balance {
+    /// Each member in turn
+    RoundRobin,
+    /// The member with the fewest calls outstanding, taking turns between equally loaded members
+    LeastLoaded
+}
+
+/// A `Service` distributing calls across several underlying services. Members can be ejected
+/// for a while after repeated failures, with `eject_on`; if every member is ejected, calls are
+/// distributed across all of them regardless. Clones share the same members and state.
+/// # Examples
+/// ```
+/// use future;
+/// use future::{Balance, Balancer, Service};
+///
+/// let replica = |name: &'static str| move |key: u64| future::value::<String, ()>(format!("{}:{}", name, key));
+/// let balancer = Balancer::new(vec![replica("a"), replica("b")], Balance::RoundRobin);
+///
+/// assert_eq!(Ok(String::from("a:1")), future::await(balancer.call(1)));
+/// assert_eq!(Ok(String::from("b:2")), future::await(balancer.call(2)));
+/// ```
+pub struct Balancer<Req, Resp: 'static, E: 'static> {
+    members: Arc<Vec<Arc<Service<Req, Resp, E>>>>,
+    balance: Balance,
+    ejection: Option<Ejection<E>>,
+    state: Arc<Mutex<BalancerState>>
+}
+
+struct Ejection<E> {
+    classifier: Arc<FailureClassifier<E>>,
+    after_failures: u32,
+    duration: Duration
+}
+
+struct BalancerState {
+    next: usize,
+    members: Vec<MemberState>
+}
+
+#[derive(Clone)]
+struct MemberState {
+    outstanding: usize,
+    failures: u32,
+    ejected_until: Option<Instant>
+}
+
+impl<Req: 'static, Resp: 'static, E: 'static> Balancer<Req, Resp, E> {
+    /// # Panics
+    /// This will panic if `services` is empty.
+    pub fn new<S>(services: Vec<S>, balance: Balance) -> Balancer<Req, Resp, E>
+        where S: Service<Req, Resp, E> + 'static
+    {
+        assert!(!services.is_empty(), "Balancer requires at least one service");
+        let member = MemberState { outstanding: 0, failures: 0, ejected_until: None };
+        Balancer {
+            state: Arc::new(Mutex::new(BalancerState { next: 0, members: vec![member; services.len()] })),
+            members: Arc::new(services.into_iter().map(|s| Arc::new(s) as Arc<Service<Req, Resp, E>>).collect()),
+            balance: balance,
+            ejection: None
+        }
+    }
+
+    /// Ejects a member for `duration` once `after_failures` consecutive calls to it have failed,
+    /// as classified by `classifier`.
+    pub fn eject_on<C>(mut self, classifier: C, after_failures: u32, duration: Duration) -> Balancer<Req, Resp, E>
+        where C: FailureClassifier<E> + 'static
+    {
+        self.ejection = Some(Ejection {
+            classifier: Arc::new(classifier),
+            after_failures: after_failures,
+            duration: duration
+        });
+        self
+    }
+
+    /// The number of calls outstanding on each member, in the order the members were given.
+    pub fn outstanding(&self) -> Vec<usize> {
+        self.state.lock().unwrap().members.iter().map(|member| member.outstanding).collect()
+    }
+
+    /// The number of members not currently ejected.
+    pub fn healthy(&self) -> usize {
+        let now = Instant::now();
+        self.state.lock().unwrap().members.iter().filter(|member| !member.is_ejected(now)).count()
+    }
+
+    fn choose(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let mut candidates = (0..state.members.len())
+            .filter(|&i| !state.members[i].is_ejected(now))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            candidates = (0..state.members.len()).collect();
+        }
+        let offset = state.next % candidates.len();
+        state.next = state.next.wrapping_add(1);
+
+        let chosen = match self.balance {
+            Balance::RoundRobin => candidates[offset],
+            Balance::LeastLoaded => {
+                (0..candidates.len())
+                    .map(|k| candidates[(offset + k) % candidates.len()])
+                    .min_by_key(|&i| state.members[i].outstanding)
+                    .unwrap()
+            }
+        };
+        state.members[chosen].outstanding += 1;
+        chosen
+    }
+
+    fn record(&self, member: usize, classification: Classification) {
+        // A poisoned lock means this is being dropped by an unwinding panic.
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return
+        };
+        let member = &mut state.members[member];
+        member.outstanding -= 1;
+        let ejection = match self.ejection {
+            Some(ref ejection) => ejection,
+            None => return
+        };
+        match classification {
+            Classification::Success => member.failures = 0,
+            Classification::RetryableFailure | Classification::NonRetryableFailure => {
+                member.failures += 1;
+                if member.failures >= ejection.after_failures {
+                    member.failures = 0;
+                    member.ejected_until = Some(Instant::now() + ejection.duration);
+                }
+            },
+            Classification::Ignorable => {}
+        }
+    }
+}
+
+impl MemberState {
+    fn is_ejected(&self, now: Instant) -> bool {
+        self.ejected_until.map_or(false, |until| until > now)
+    }
+}
+
+impl<Req: 'static, Resp: 'static, E: 'static> Service<Req, Resp, E> for Balancer<Req, Resp, E> {
+    fn call(&self, req: Req) -> Future<Resp, E> {
+        let member = self.choose();
+        let mut call = MemberCall { balancer: Some(self.clone()), member: member };
+        self.members[member].call(req).on_completion(move |result| {
+            let classification = match (result, call.ejection()) {
+                (&Err(ref e), Some(ejection)) => ejection.classifier.classify(e),
+                _ => Classification::Success
+            };
+            call.record(classification);
+        })
+    }
+}
+
+/// A call outstanding on a member. Dropped without recording, as it is when the member's
+/// `FutureSetter` is dropped, it ends the call as a failure.
+struct MemberCall<Req: 'static, Resp: 'static, E: 'static> {
+    balancer: Option<Balancer<Req, Resp, E>>,
+    member: usize
+}
+
+impl<Req: 'static, Resp: 'static, E: 'static> MemberCall<Req, Resp, E> {
+    fn ejection(&self) -> Option<&Ejection<E>> {
+        self.balancer.as_ref().and_then(|balancer| balancer.ejection.as_ref())
+    }
+
+    fn record(&mut self, classification: Classification) {
+        if let Some(balancer) = self.balancer.take() {
+            balancer.record(self.member, classification);
+        }
+    }
+}
+
+impl<Req: 'static, Resp: 'static, E: 'static> Drop for MemberCall<Req, Resp, E> {
+    fn drop(&mut self) {
+        self.record(Classification::RetryableFailure);
+    }
+}
+
+impl<Req, Resp: 'static, E: 'static> Clone for Balancer<Req, Resp, E> {
+    fn clone(&self) -> Balancer<Req, Resp, E> {
+        Balancer {
+            members: self.members.clone(),
+            balance: self.balance,
+            ejection: self.ejection.as_ref().map(|ejection| Ejection {
+                classifier: ejection.classifier.clone(),
+                after_failures: ejection.after_failures,
+                duration: ejection.duration
+            }),
+            state: self.state.clone()
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await_safe, err, new, value, FutureSetter};
+
+    #[test]
+    fn least_loaded_avoids_busy_members_and_ejects_failing_ones() {
+        let pending = Arc::new(Mutex::new(vec![]));
+        let member = |kind: &'static str| {
+            let pending = pending.clone();
+            move |req: u32| -> Future<u32, ()> {
+                match kind {
+                    "slow" => {
+                        let (future, setter) = new();
+                        pending.lock().unwrap().push(setter);
+                        future.map(move |()| req)
+                    },
+                    "failing" => err(()),
+                    _ => value(req)
+                }
+            }
+        };
+
+        let balancer = Balancer::new(vec![member("slow"), member("failing"), member("fast")], Balance::LeastLoaded)
+            .eject_on(|_: &()| Classification::RetryableFailure, 1, Duration::from_secs(30));
+        for req in 0..6 {
+            let _ = balancer.call(req);
+        }
+        assert_eq!(balancer.healthy(), 2);
+        assert_eq!(balancer.outstanding(), vec![1, 0, 0]);
+
+        let setter: FutureSetter<(), ()> = pending.lock().unwrap().pop().unwrap();
+        setter.set_result(Ok(()): Result<(), ()>);
+        assert_eq!(balancer.outstanding(), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn dropped_setters_end_calls_as_failures() {
+        let member = |dropping: bool| move |req: u32| -> Future<u32, ()> {
+            if dropping { new().0 } else { value(req) }
+        };
+        let balancer = Balancer::new(vec![member(true), member(false)], Balance::LeastLoaded)
+            .eject_on(|_: &()| Classification::RetryableFailure, 2, Duration::from_secs(30));
+
+        assert!(await_safe(balancer.call(0)).is_err());
+        assert!(await_safe(balancer.call(1)).is_ok());
+        assert!(await_safe(balancer.call(2)).is_err());
+        assert_eq!(balancer.outstanding(), vec![0, 0]);
+        assert_eq!(balancer.healthy(), 1);
+    }
+}
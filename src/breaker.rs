@@ -0,0 +1,336 @@
+use super::{err, Future};
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Stops calls to a failing dependency. After `failure_threshold` consecutive failures the circuit
+/// opens, and calls fail immediately with `CircuitOpen` until `reset_timeout` has passed. A single
+/// trial call is then let through: if it succeeds the circuit closes again, otherwise it re-opens.
+/// Clones of a `CircuitBreaker` share the same circuit.
+///
+/// # Examples
+///
+/// ```
+/// use future;
+/// use future::{CircuitBreaker, CircuitOpen, CircuitState};
+/// use std::time::Duration;
+///
+/// let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+/// let failed = breaker.call(|| future::err::<(), CircuitOpen>(CircuitOpen));
+/// assert!(future::await(failed).is_err());
+/// assert_eq!(CircuitState::Open, breaker.state());
+/// ```
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    state: Arc<Mutex<Circuit>>
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls are allowed through
+    Closed,
+    /// Calls fail immediately
+    Open,
+    /// A single trial call is allowed through to decide whether to close the circuit
+    HalfOpen
+}
+
+struct Circuit {
+    state: BreakerState,
+    /// Bumped whenever the circuit opens or a trial call is let through, so that the outcomes of
+    /// calls let through before then are ignored
+    generation: u64
+}
+
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { trial_in_flight: bool }
+}
+
+/// A call let through by `CircuitBreaker::try_acquire`, to be handed back to `record` with the
+/// outcome of the call. Only the outcome of the trial call counts while the circuit is half-open,
+/// and outcomes of calls let through before the circuit last opened are ignored.
+pub struct BreakerTicket {
+    generation: u64,
+    trial: bool
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, reset_timeout: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            failure_threshold: failure_threshold,
+            reset_timeout: reset_timeout,
+            state: Arc::new(Mutex::new(Circuit { state: BreakerState::Closed { failures: 0 }, generation: 0 }))
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        match self.state.lock().unwrap().state {
+            BreakerState::Closed { .. } => CircuitState::Closed,
+            BreakerState::Open { until } if until > Instant::now() => CircuitState::Open,
+            _ => CircuitState::HalfOpen
+        }
+    }
+
+    /// Calls `f` if the circuit allows it, recording the outcome of the returned `Future`. If `f`
+    /// panics, or the `Future` never resolves because it's dropped or its `FutureSetter` is, the
+    /// call is released without recording an outcome, so a trial call can't hold the circuit
+    /// half-open forever.
+    pub fn call<F, A, E>(&self, f: F) -> Future<A, E>
+        where F: FnOnce() -> Future<A, E>,
+              A: 'static,
+              E: From<CircuitOpen> + 'static
+    {
+        let ticket = match self.try_acquire() {
+            Some(ticket) => ticket,
+            None => return err(E::from(CircuitOpen))
+        };
+        // Released when dropped unused: by a panic in `f`, or with the callbacks holding it.
+        let pending = PendingCall::new(self, ticket);
+        let completed = pending.guard();
+        let future = f().on_completion(move |result| completed.record(result.is_ok()));
+        future.on_abandoned(move || pending.release());
+        future
+    }
+
+    /// Checks whether a call is allowed through, claiming the trial call if the circuit is
+    /// half-open. The returned ticket is handed back to `record` with the outcome of the call.
+    pub fn try_acquire(&self) -> Option<BreakerTicket> {
+        let mut circuit = self.state.lock().unwrap();
+        let trial = match circuit.state {
+            BreakerState::Closed { .. } => false,
+            BreakerState::Open { until } if until > Instant::now() => return None,
+            BreakerState::HalfOpen { trial_in_flight: true } => return None,
+            BreakerState::Open { .. } | BreakerState::HalfOpen { trial_in_flight: false } => true
+        };
+        if trial {
+            circuit.state = BreakerState::HalfOpen { trial_in_flight: true };
+            circuit.generation += 1;
+        }
+        Some(BreakerTicket { generation: circuit.generation, trial: trial })
+    }
+
+    /// Records the outcome of the call `ticket` let through. Outcomes of calls let through before
+    /// the circuit last opened, or of any call but the trial while it's half-open, are ignored.
+    pub fn record(&self, ticket: BreakerTicket, success: bool) {
+        let mut circuit = self.state.lock().unwrap();
+        if ticket.generation != circuit.generation {
+            return;
+        }
+        let next = match (&circuit.state, success) {
+            (&BreakerState::Closed { .. }, true) => BreakerState::Closed { failures: 0 },
+            (&BreakerState::Closed { failures }, false) if failures + 1 < self.failure_threshold => {
+                BreakerState::Closed { failures: failures + 1 }
+            },
+            (&BreakerState::HalfOpen { .. }, true) if ticket.trial => BreakerState::Closed { failures: 0 },
+            (&BreakerState::Closed { .. }, false) => BreakerState::Open { until: Instant::now() + self.reset_timeout },
+            (&BreakerState::HalfOpen { .. }, false) if ticket.trial => {
+                BreakerState::Open { until: Instant::now() + self.reset_timeout }
+            },
+            _ => return
+        };
+        if let BreakerState::Open { .. } = next {
+            circuit.generation += 1;
+        }
+        circuit.state = next;
+    }
+
+    /// Gives up the call `ticket` let through without recording an outcome, letting another trial
+    /// call through if it was the trial.
+    pub(crate) fn release(&self, ticket: BreakerTicket) {
+        let mut circuit = self.state.lock().unwrap();
+        if ticket.trial && ticket.generation == circuit.generation {
+            circuit.state = BreakerState::HalfOpen { trial_in_flight: false };
+        }
+    }
+}
+
+/// A call let through by a `CircuitBreaker` whose outcome hasn't been recorded. The first of
+/// `record` and `release` to be called hands back the ticket; dropping the last clone, or a
+/// `guard`, releases it.
+#[derive(Clone)]
+pub(crate) struct PendingCall {
+    inner: Arc<PendingTicket>
+}
+
+struct PendingTicket {
+    breaker: CircuitBreaker,
+    ticket: Mutex<Option<BreakerTicket>>
+}
+
+impl PendingCall {
+    pub(crate) fn new(breaker: &CircuitBreaker, ticket: BreakerTicket) -> PendingCall {
+        PendingCall { inner: Arc::new(PendingTicket { breaker: breaker.clone(), ticket: Mutex::new(Some(ticket)) }) }
+    }
+
+    pub(crate) fn record(&self, success: bool) {
+        if let Some(ticket) = self.inner.take() {
+            self.inner.breaker.record(ticket, success);
+        }
+    }
+
+    pub(crate) fn release(&self) {
+        if let Some(ticket) = self.inner.take() {
+            self.inner.breaker.release(ticket);
+        }
+    }
+
+    /// A handle for the result callback, which releases the call if the callback is dropped
+    /// without running, as it is when the `FutureSetter` is dropped.
+    pub(crate) fn guard(&self) -> CallGuard {
+        CallGuard(self.clone())
+    }
+}
+
+pub(crate) struct CallGuard(PendingCall);
+
+impl CallGuard {
+    pub(crate) fn record(&self, success: bool) {
+        self.0.record(success)
+    }
+
+    pub(crate) fn release(&self) {
+        self.0.release()
+    }
+}
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+impl PendingTicket {
+    fn take(&self) -> Option<BreakerTicket> {
+        self.ticket.lock().ok().and_then(|mut ticket| ticket.take())
+    }
+}
+
+impl Drop for PendingTicket {
+    fn drop(&mut self) {
+        if let Some(ticket) = self.take() {
+            self.breaker.release(ticket);
+        }
+    }
+}
+
+impl Clone for CircuitBreaker {
+    fn clone(&self) -> CircuitBreaker {
+        CircuitBreaker {
+            failure_threshold: self.failure_threshold,
+            reset_timeout: self.reset_timeout,
+            state: self.state.clone()
+        }
+    }
+}
+
+/// An Error indicating that a call was rejected because its `CircuitBreaker` is open.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CircuitOpen;
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CircuitOpen")
+    }
+}
+
+impl Error for CircuitOpen {
+    fn description(&self) -> &str {
+        "The call was rejected because the circuit breaker is open"
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, new, value};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::thread;
+
+    /// Opens `breaker`, a breaker with a threshold of 1, and waits for it to half-open.
+    fn half_open(breaker: &CircuitBreaker) {
+        let _ = await(breaker.call(|| err::<(), CircuitOpen>(CircuitOpen)));
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    }
+
+    #[test]
+    fn circuit_half_opens_after_reset_timeout() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(20));
+        for _ in 0..2 {
+            let _ = await(breaker.call(|| err::<(), CircuitOpen>(CircuitOpen)));
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(await(breaker.call(|| value::<(), CircuitOpen>(()))), Err(CircuitOpen));
+
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(await(breaker.call(|| value::<(), CircuitOpen>(()))), Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn panicking_trial_calls_are_released() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+        half_open(&breaker);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            breaker.call(|| -> Future<(), CircuitOpen> { panic!("failed to start the call") })
+        }));
+        assert!(result.is_err());
+        assert!(breaker.try_acquire().is_some());
+    }
+
+    #[test]
+    fn abandoned_trial_calls_are_released() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+        half_open(&breaker);
+        let (f, _setter) = new::<(), CircuitOpen>();
+        drop(breaker.call(move || f));
+        assert!(breaker.try_acquire().is_some());
+    }
+
+    #[test]
+    fn trial_calls_with_dropped_setters_are_released() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+        half_open(&breaker);
+        let (f, setter) = new::<(), CircuitOpen>();
+        let _call = breaker.call(move || f);
+        drop(setter);
+        assert!(breaker.try_acquire().is_some());
+    }
+
+    #[test]
+    fn stale_successes_dont_close_an_open_circuit() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        let (slow, setter) = new::<(), CircuitOpen>();
+        let slow = breaker.call(move || slow);
+        let _ = await(breaker.call(|| err::<(), CircuitOpen>(CircuitOpen)));
+        setter.set_result(Ok(()): Result<(), CircuitOpen>);
+        assert_eq!(await(slow), Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[test]
+    fn only_the_trial_call_counts_while_half_open() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+        let (stale, stale_setter) = new::<(), CircuitOpen>();
+        let stale = breaker.call(move || stale);
+        let (dropped, dropped_setter) = new::<(), CircuitOpen>();
+        let _dropped = breaker.call(move || dropped);
+        half_open(&breaker);
+
+        let (trial, trial_setter) = new::<(), CircuitOpen>();
+        let trial = breaker.call(move || trial);
+        drop(dropped_setter);
+        assert!(breaker.try_acquire().is_none());
+        stale_setter.set_result(Ok(()): Result<(), CircuitOpen>);
+        assert_eq!(await(stale), Ok(()));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        trial_setter.set_result(Ok(()): Result<(), CircuitOpen>);
+        assert_eq!(await(trial), Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+}
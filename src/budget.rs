@@ -0,0 +1,139 @@
+use super::{err, timer, Future};
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// A time budget shared across the steps of a multi-step operation, for end-to-end latency
+/// control rather than a separate timeout per step. Each step deducts the time it takes from
+/// what's left for the following steps, since the budget counts down from its creation. Clones
+/// share the same budget.
+///
+/// # Examples
+///
+/// ```
+/// use future;
+/// use future::{BudgetExhausted, TimeBudget};
+/// use std::time::Duration;
+///
+/// let budget = TimeBudget::new(Duration::from_millis(50));
+/// let step_budget = budget.clone();
+/// let (slow, _setter) = future::new::<i64, BudgetExhausted>();
+/// let f = future::value::<i64, BudgetExhausted>(1)
+///     .require_within_budget(&budget)
+///     .and_thenf(move |_| slow.require_within_budget(&step_budget));
+/// assert!(future::await(f).is_err());
+/// assert!(budget.is_exhausted());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TimeBudget {
+    total: Duration,
+    deadline: Instant
+}
+
+impl TimeBudget {
+    /// Starts a budget of `total`.
+    pub fn new(total: Duration) -> TimeBudget {
+        TimeBudget { total: total, deadline: Instant::now() + total }
+    }
+
+    /// The budget this started with.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// The time left, which is zero once the budget is exhausted.
+    pub fn remaining(&self) -> Duration {
+        let now = Instant::now();
+        if now >= self.deadline {
+            Duration::from_secs(0)
+        } else {
+            self.deadline - now
+        }
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+
+    /// Returns the time left, or a `BudgetExhausted` error if there is none, for checking the
+    /// budget before a synchronous step.
+    pub fn check(&self) -> Result<Duration, BudgetExhausted> {
+        if self.is_exhausted() {
+            Err(BudgetExhausted { budget: self.total })
+        } else {
+            Ok(self.remaining())
+        }
+    }
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Fails with `BudgetExhausted` if `budget` runs out before this `Future` resolves, or
+    /// immediately if it already has; otherwise the result is passed through unchanged.
+    pub fn require_within_budget(self, budget: &TimeBudget) -> Future<A, E>
+        where E: From<BudgetExhausted>
+    {
+        if let Err(exhausted) = budget.check() {
+            return err(E::from(exhausted));
+        }
+
+        let (future, setter) = self.derive();
+        let setter = setter.shared();
+
+        // The timer only holds a weak handle, so a call that resolves early isn't kept in memory
+        // until the budget runs out.
+        let exhausted_setter = setter.downgrade();
+        let exhausted = BudgetExhausted { budget: budget.total };
+        timer::at(budget.deadline).register(move |_| {
+            exhausted_setter.set_if_unset(Err(exhausted): Result<A, BudgetExhausted>);
+        });
+
+        self.register(move |result| {
+            setter.set_if_unset(result);
+        });
+        future
+    }
+}
+
+/// An Error indicating that a `TimeBudget` ran out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BudgetExhausted {
+    /// The budget that was exhausted
+    pub budget: Duration
+}
+
+impl fmt::Display for BudgetExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Exhausted the time budget of {:?}", self.budget)
+    }
+}
+
+impl Error for BudgetExhausted {
+    fn description(&self) -> &str {
+        "The time budget ran out"
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, await_safe, new, value};
+
+    #[test]
+    fn exhausted_budgets_fail_fast() {
+        let budget = TimeBudget::new(Duration::from_secs(0));
+        let f = value::<i64, BudgetExhausted>(1).require_within_budget(&budget);
+        assert_eq!(await(f), Err(BudgetExhausted { budget: Duration::from_secs(0) }));
+
+        let budget = TimeBudget::new(Duration::from_secs(10));
+        let f = value::<i64, BudgetExhausted>(1).require_within_budget(&budget);
+        assert_eq!(await(f), Ok(1));
+    }
+
+    #[test]
+    fn the_timer_doesnt_hold_the_setter() {
+        let budget = TimeBudget::new(Duration::from_secs(60));
+        let (f, setter) = new::<i64, BudgetExhausted>();
+        let f = f.require_within_budget(&budget);
+        drop(setter);
+        assert!(await_safe(f).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use super::{config, new, Future, FutureSetter, Priority, TimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Creates (`Future`, `FutureSetter`) pairs with policies set up front, rather than by stacking
+/// combinators on each `Future`.
+/// # Examples
+/// ```
+/// use future;
+/// use future::{Builder, TimeoutError};
+/// use future::config::DropPolicy;
+/// use std::time::Duration;
+///
+/// let (f, _setter) = Builder::new()
+///     .timeout(Duration::from_millis(10))
+///     .on_drop_policy(DropPolicy::Log)
+///     .label("fetch_user")
+///     .build::<String, TimeoutError>();
+///
+/// assert_eq!(Some(String::from("fetch_user")), f.info().label);
+/// assert!(future::await(f).is_err());
+/// ```
+pub struct Builder<T = NoTimeout> {
+    timeout: T,
+    drop_policy: Option<config::DropPolicy>,
+    priority: Option<Priority>,
+    label: Option<Arc<String>>
+}
+
+/// The timeout applied by a `Builder`: either `NoTimeout`, or a `Duration` for a `Builder` whose
+/// `Future`s fail with a `TimeoutError` if they haven't resolved in time.
+pub trait BuildTimeout<E> {
+    fn apply<A: 'static>(&self, future: Future<A, E>) -> Future<A, E>;
+}
+
+/// The timeout of a `Builder` without one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct NoTimeout;
+
+impl<E: 'static> BuildTimeout<E> for NoTimeout {
+    fn apply<A: 'static>(&self, future: Future<A, E>) -> Future<A, E> {
+        future
+    }
+}
+
+impl<E: From<TimeoutError> + 'static> BuildTimeout<E> for Duration {
+    fn apply<A: 'static>(&self, future: Future<A, E>) -> Future<A, E> {
+        future.within(*self)
+    }
+}
+
+impl Builder<NoTimeout> {
+    pub fn new() -> Builder<NoTimeout> {
+        Builder { timeout: NoTimeout, drop_policy: None, priority: None, label: None }
+    }
+}
+
+impl<T> Builder<T> {
+    /// Fails built `Future`s with a `TimeoutError` if they haven't resolved within `timeout`.
+    pub fn timeout(self, timeout: Duration) -> Builder<Duration> {
+        Builder { timeout: timeout, drop_policy: self.drop_policy, priority: self.priority, label: self.label }
+    }
+
+    /// Overrides the crate-level `DropPolicy` for built setters.
+    pub fn on_drop_policy(mut self, policy: config::DropPolicy) -> Builder<T> {
+        self.drop_policy = Some(policy);
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Builder<T> {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Names built `Future`s, and those derived from them, in `Future::info`.
+    pub fn label(mut self, label: &str) -> Builder<T> {
+        self.label = Some(Arc::new(String::from(label)));
+        self
+    }
+
+    /// Creates a new (`Future`, `FutureSetter`) pair with this builder's policies.
+    pub fn build<A, E>(&self) -> (Future<A, E>, FutureSetter<A, E>)
+        where T: BuildTimeout<E>,
+              A: 'static,
+              E: 'static
+    {
+        let (future, setter) = new();
+        {
+            let mut meta = future.lock.lock().unwrap();
+            meta.drop_policy = self.drop_policy.clone();
+            meta.label = self.label.clone();
+        }
+        let future = self.timeout.apply(future);
+        let future = match self.priority {
+            Some(priority) => future.with_priority(priority),
+            None => future
+        };
+        (future, setter)
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::await;
+
+    #[test]
+    fn builders_without_a_timeout_accept_any_error_type() {
+        let builder = Builder::new().priority(Priority::High).label("lookup");
+        let (f, setter) = builder.build::<i64, String>();
+        assert_eq!(f.priority(), Some(Priority::High));
+        let f = f.map(|i| i + 1);
+        assert_eq!(f.info().label, Some(String::from("lookup")));
+
+        setter.set_result(Ok(1): Result<i64, String>);
+        assert_eq!(await(f), Ok(2));
+    }
+}
@@ -0,0 +1,299 @@
+use super::{new, timeout, Future};
+use std::boxed::FnBox;
+use std::error::Error;
+use std::fmt;
+use std::mem;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+
+/// The owning side of a cooperative cancellation signal. Calling `cancel` fires every
+/// `CancelToken` handed out by this source, along with any token derived from them via `or`.
+///
+/// # Examples
+///
+/// ```
+/// use future;
+/// use future::{CancelSource, Cancelled, Future};
+///
+/// let source = CancelSource::new();
+/// let (f, _setter) = future::new::<i64, Cancelled>();
+/// let f = f.bind_cancel(&source.token());
+///
+/// source.cancel();
+/// assert_eq!(Err(Cancelled), future::await(f));
+/// ```
+pub struct CancelSource {
+    token: CancelToken
+}
+
+/// A cloneable handle observing a `CancelSource`. Tokens can be polled with `is_cancelled`,
+/// notified with `on_cancel`, or bound to a `Future` with `Future::bind_cancel`.
+#[derive(Clone)]
+pub struct CancelToken {
+    inner: Arc<Mutex<CancelInner>>
+}
+
+struct CancelInner {
+    cancelled: bool,
+    next_id: u64,
+    callbacks: Vec<(u64, Box<FnBox() -> () + Send>)>,
+    /// For a token derived with `or`, the callbacks through which it observes its parents
+    parents: Vec<CancelRegistration>
+}
+
+/// A callback registered on a token, removed once this is dropped.
+struct CancelRegistration {
+    token: Weak<Mutex<CancelInner>>,
+    id: u64
+}
+
+impl CancelSource {
+    pub fn new() -> CancelSource {
+        CancelSource { token: CancelToken::new() }
+    }
+
+    /// Returns a token that fires when this source is cancelled.
+    pub fn token(&self) -> CancelToken {
+        self.token.clone()
+    }
+
+    /// Fires every token observing this source. Cancelling more than once has no further effect.
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+impl CancelToken {
+    fn new() -> CancelToken {
+        CancelToken {
+            inner: Arc::new(Mutex::new(CancelInner { cancelled: false, next_id: 0, callbacks: vec![], parents: vec![] }))
+        }
+    }
+
+    /// A token that never fires.
+    pub fn never() -> CancelToken {
+        CancelToken::new()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.lock().unwrap().cancelled
+    }
+
+    /// Runs `f` once this token fires. If the token has already fired, `f` runs immediately.
+    pub fn on_cancel<F>(&self, f: F)
+        where F: FnOnce() -> () + Send + 'static
+    {
+        self.register(f);
+    }
+
+    /// Returns a token that fires as soon as either this token or `other` fires. The combined
+    /// token stops observing both once it fires, or every clone of it is dropped.
+    pub fn or(&self, other: &CancelToken) -> CancelToken {
+        let combined = CancelToken::new();
+        let parents = [self, other].iter().filter_map(|parent| {
+            let weak = Arc::downgrade(&combined.inner);
+            parent.register(move || if let Some(inner) = weak.upgrade() {
+                CancelToken { inner: inner }.cancel()
+            })
+        }).collect::<Vec<_>>();
+        let mut inner = combined.inner.lock().unwrap();
+        if !inner.cancelled {
+            inner.parents = parents;
+        }
+        drop(inner);
+        combined
+    }
+
+    /// Runs `f` once this token fires, returning a registration that removes `f` when dropped,
+    /// or runs `f` immediately if the token has already fired.
+    fn register<F>(&self, f: F) -> Option<CancelRegistration>
+        where F: FnOnce() -> () + Send + 'static
+    {
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if !inner.cancelled {
+                let id = inner.next_id;
+                inner.next_id += 1;
+                inner.callbacks.push((id, box f));
+                return Some(CancelRegistration { token: Arc::downgrade(&self.inner), id: id });
+            }
+        }
+        f();
+        None
+    }
+
+    fn cancel(&self) {
+        let (callbacks, parents) = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.cancelled {
+                return;
+            }
+            inner.cancelled = true;
+            (inner.callbacks.drain(..).collect::<Vec<_>>(), mem::replace(&mut inner.parents, vec![]))
+        };
+        drop(parents);
+        for (_, callback) in callbacks {
+            callback();
+        }
+    }
+}
+
+impl Drop for CancelRegistration {
+    fn drop(&mut self) {
+        let token = match self.token.upgrade() {
+            Some(token) => token,
+            None => return
+        };
+        // The callback is dropped outside the lock, as it may hold a setter.
+        let removed = match token.lock() {
+            Ok(mut inner) => inner.callbacks.iter().position(|&(id, _)| id == self.id)
+                .map(|i| inner.callbacks.remove(i)),
+            Err(_) => return
+        };
+        drop(removed);
+    }
+}
+
+/// Like `future::run`, except `f` is passed a `CancelToken` that fires once nothing is waiting on
+/// the result: when the returned `Future`, or every `Future` derived from it, is dropped without
+/// being consumed. `f` can poll the token to stop work early.
+/// # Examples
+/// ```
+/// use future;
+/// use std::sync::mpsc::channel;
+///
+/// let (tx, rx) = channel();
+/// let f = future::run_cancellable(move |token| {
+///     while !token.is_cancelled() {
+///         // some work here
+///     }
+///     tx.send("stopped").unwrap();
+///     Ok(()): Result<(), ()>
+/// });
+///
+/// drop(f);
+/// assert_eq!("stopped", rx.recv().unwrap());
+/// ```
+pub fn run_cancellable<F, A, E>(f: F) -> Future<A, E>
+    where F: FnOnce(CancelToken) -> Result<A, E> + Send + 'static,
+          A: 'static,
+          E: 'static
+{
+    let source = CancelSource::new();
+    let token = source.token();
+    let (future, setter) = new();
+    future.on_abandoned(move || source.cancel());
+    thread::spawn(move || setter.set_result(f(token)));
+    timeout::with_default_timeout(future)
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Races this `Future` against `token`. If the token fires before this `Future` resolves, the
+    /// returned `Future` resolves immediately with a `Cancelled` error; otherwise the result is
+    /// passed through unchanged. The token lets go of this `Future` once it resolves.
+    pub fn bind_cancel(self, token: &CancelToken) -> Future<A, E>
+        where E: From<Cancelled>
+    {
+        let (future, setter) = self.derive();
+        let setter = setter.shared();
+
+        let cancel_setter = setter.clone();
+        // Dropped with the callback below, whether or not it runs.
+        let registration = token.register(move || {
+            cancel_setter.set_if_unset(Err(Cancelled): Result<A, Cancelled>);
+        });
+
+        self.register(move |result| {
+            drop(registration);
+            setter.set_if_unset(result);
+        });
+        future
+    }
+}
+
+/// An Error indicating that the associated `Future` was cancelled via a `CancelToken` before it
+/// resolved.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cancelled")
+    }
+}
+
+impl Error for Cancelled {
+    fn description(&self) -> &str {
+        "The Future was cancelled before it resolved"
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, value};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn bind_cancel_passes_through_results_resolved_first() {
+        let source = CancelSource::new();
+        let f = value::<i64, Cancelled>(1).bind_cancel(&source.token());
+        source.cancel();
+        assert_eq!(await(f), Ok(1));
+    }
+
+    #[test]
+    fn combined_tokens_fire_when_either_parent_fires() {
+        let a = CancelSource::new();
+        let b = CancelSource::new();
+        let combined = a.token().or(&b.token());
+        let (f, _setter) = new::<i64, Cancelled>();
+        let f = f.bind_cancel(&combined);
+
+        assert!(!combined.is_cancelled());
+        b.cancel();
+        assert!(combined.is_cancelled());
+        assert!(!a.is_cancelled());
+        assert_eq!(await(f), Err(Cancelled));
+    }
+
+    #[test]
+    fn long_lived_tokens_let_go_of_resolved_futures() {
+        let source = CancelSource::new();
+        let token = source.token();
+        let callbacks = || token.inner.lock().unwrap().callbacks.len();
+
+        let (f, setter) = new::<i64, Cancelled>();
+        let f = f.bind_cancel(&token);
+        assert_eq!(callbacks(), 1);
+        setter.set_result(Ok(1): Result<i64, Cancelled>);
+        assert_eq!(await(f), Ok(1));
+        assert_eq!(callbacks(), 0);
+
+        let other = CancelSource::new();
+        let combined = token.or(&other.token());
+        assert_eq!(callbacks(), 1);
+        drop(combined);
+        assert_eq!(callbacks(), 0);
+    }
+
+    #[test]
+    fn run_cancellable_fires_once_derived_futures_are_dropped() {
+        let (tx, rx) = channel();
+        let f = run_cancellable(move |token| {
+            let (fired, wait) = channel();
+            token.on_cancel(move || fired.send(()).unwrap());
+            wait.recv().unwrap();
+            tx.send(token.is_cancelled()).unwrap();
+            Ok(()): Result<(), ()>
+        });
+        drop(f.map(|_| ()));
+        assert_eq!(rx.recv(), Ok(true));
+
+        let f = run_cancellable(|token| Ok(token.is_cancelled()): Result<bool, ()>);
+        assert_eq!(await(f), Ok(false));
+    }
+}
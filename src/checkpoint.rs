@@ -0,0 +1,91 @@
+use super::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A named point in a chain of combinators, recorded by `Future::checkpoint` when the result
+/// reaches it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub name: &'static str,
+    /// The time between the chain being built and the result reaching this point
+    pub elapsed: Duration
+}
+
+/// The checkpoints recorded along a chain, shared by every `Future` derived after the first call
+/// to `checkpoint`.
+pub(crate) struct Trail {
+    started: Instant,
+    checkpoints: Vec<Checkpoint>
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Records a `Checkpoint` named `name` when the result reaches this point in the chain, for
+    /// observers added further along with `on_checkpoints`. Times are measured from when the first
+    /// checkpoint of the chain is added, so the differences between consecutive checkpoints give
+    /// the latency of each stage.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let (f, setter) = future::new::<i64, ()>();
+    /// let f = f.checkpoint("fetched")
+    ///     .map(|i| i * 2)
+    ///     .checkpoint("processed")
+    ///     .on_checkpoints(|checkpoints| {
+    ///         for pair in checkpoints.windows(2) {
+    ///             println!("{}: {:?}", pair[1].name, pair[1].elapsed - pair[0].elapsed);
+    ///         }
+    ///     });
+    ///
+    /// setter.set_result(Ok(5): Result<i64, ()>);
+    /// assert_eq!(Ok(10), future::await(f));
+    /// ```
+    pub fn checkpoint(self, name: &'static str) -> Future<A, E> {
+        let trail = {
+            let mut meta = self.lock.lock().unwrap();
+            if meta.trail.is_none() {
+                meta.trail = Some(Arc::new(Mutex::new(Trail { started: Instant::now(), checkpoints: vec![] })));
+            }
+            meta.trail.clone().unwrap()
+        };
+        self.on_completion(move |_| {
+            let mut trail = trail.lock().unwrap();
+            let elapsed = trail.started.elapsed();
+            trail.checkpoints.push(Checkpoint { name: name, elapsed: elapsed });
+        })
+    }
+
+    /// Runs `f` with the checkpoints recorded so far along the chain once the `Future` resolves.
+    pub fn on_checkpoints<F>(self, f: F) -> Future<A, E>
+        where F: FnOnce(&[Checkpoint]) -> (), F: 'static
+    {
+        let trail = self.lock.lock().unwrap().trail.clone();
+        self.on_completion(move |_| match trail {
+            Some(trail) => f(&trail.lock().unwrap().checkpoints),
+            None => f(&[])
+        })
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, new, timer, Never};
+
+    #[test]
+    fn checkpoints_are_recorded_in_chain_order() {
+        let (f, setter) = new::<(), Never>();
+        let names = Arc::new(Mutex::new(vec![]));
+        let names2 = names.clone();
+        let f = f.checkpoint("start")
+            .and_thenf(|()| timer::sleep(Duration::from_millis(10)))
+            .checkpoint("slept")
+            .on_checkpoints(move |checkpoints| {
+                assert!(checkpoints[1].elapsed - checkpoints[0].elapsed >= Duration::from_millis(10));
+                names2.lock().unwrap().extend(checkpoints.iter().map(|c| c.name));
+            });
+
+        setter.set_result(Ok(()): Result<(), Never>);
+        assert_eq!(await(f), Ok(()));
+        assert_eq!(*names.lock().unwrap(), vec!["start", "slept"]);
+    }
+}
@@ -0,0 +1,124 @@
+use super::{err, CircuitBreaker, CircuitOpen, Future, RetryPolicy};
+use super::breaker::PendingCall;
+
+/// How an outcome counts towards retries, circuit breaking and metrics.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Classification {
+    /// A success, or an error that should count as one, such as a "not found"
+    Success,
+    /// A failure that another attempt might not repeat
+    RetryableFailure,
+    /// A failure that another attempt would repeat
+    NonRetryableFailure,
+    /// An outcome that says nothing about the health of the dependency, such as a cancellation
+    Ignorable
+}
+
+/// Categorizes errors, so that `RetryPolicy::classifier`, `CircuitBreaker::call_classified` and
+/// `Future::on_classified` can share one definition of failure. Implemented for closures.
+/// # Examples
+/// ```
+/// use future::{Classification, FailureClassifier};
+///
+/// let by_status = |status: &u16| match *status {
+///     404 => Classification::Success,
+///     500...599 => Classification::RetryableFailure,
+///     _ => Classification::NonRetryableFailure
+/// };
+/// assert_eq!(Classification::RetryableFailure, by_status.classify(&503));
+/// ```
+pub trait FailureClassifier<E>: Send + Sync {
+    fn classify(&self, e: &E) -> Classification;
+}
+
+impl<E, F> FailureClassifier<E> for F
+    where F: Fn(&E) -> Classification + Send + Sync
+{
+    fn classify(&self, e: &E) -> Classification {
+        self(e)
+    }
+}
+
+impl<E: 'static> RetryPolicy<E> {
+    /// Only retry errors `classifier` finds retryable.
+    pub fn classifier<C>(self, classifier: C) -> RetryPolicy<E>
+        where C: FailureClassifier<E> + 'static
+    {
+        self.retry_if(move |e| classifier.classify(e) == Classification::RetryableFailure)
+    }
+}
+
+impl CircuitBreaker {
+    /// Like `call`, except errors count as `classifier` finds them: errors classified as
+    /// successes close the circuit, and ignorable errors aren't recorded.
+    pub fn call_classified<C, F, A, E>(&self, classifier: C, f: F) -> Future<A, E>
+        where C: FailureClassifier<E> + 'static,
+              F: FnOnce() -> Future<A, E>,
+              A: 'static,
+              E: From<CircuitOpen> + 'static
+    {
+        let ticket = match self.try_acquire() {
+            Some(ticket) => ticket,
+            None => return err(E::from(CircuitOpen))
+        };
+        // As for `call`, released if the call panics, is abandoned or never resolves.
+        let pending = PendingCall::new(self, ticket);
+        let classified = pending.guard();
+        let future = f().on_classified(classifier, move |classification| match classification {
+            Classification::Success => classified.record(true),
+            Classification::RetryableFailure | Classification::NonRetryableFailure => classified.record(false),
+            Classification::Ignorable => classified.release()
+        });
+        future.on_abandoned(move || pending.release());
+        future
+    }
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Runs `f` with the classification of the result, `Classification::Success` for any success,
+    /// for example to record metrics.
+    pub fn on_classified<C, F>(self, classifier: C, f: F) -> Future<A, E>
+        where C: FailureClassifier<E> + 'static,
+              F: FnOnce(Classification) -> (), F: 'static
+    {
+        self.on_completion(move |result| f(match *result {
+            Ok(_) => Classification::Success,
+            Err(ref e) => classifier.classify(e)
+        }))
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, CircuitState};
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    enum CallError {
+        Open,
+        Failed(&'static str)
+    }
+
+    impl From<CircuitOpen> for CallError {
+        fn from(_: CircuitOpen) -> CallError { CallError::Open }
+    }
+
+    fn classify(e: &CallError) -> Classification {
+        match *e {
+            CallError::Failed("not found") => Classification::Success,
+            CallError::Failed("cancelled") => Classification::Ignorable,
+            _ => Classification::RetryableFailure
+        }
+    }
+
+    #[test]
+    fn only_classified_failures_open_the_circuit() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        for &message in ["not found", "cancelled"].iter() {
+            let _ = await(breaker.call_classified(classify, || err::<(), _>(CallError::Failed(message))));
+            assert_eq!(breaker.state(), CircuitState::Closed);
+        }
+        let _ = await(breaker.call_classified(classify, || err::<(), _>(CallError::Failed("refused"))));
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+}
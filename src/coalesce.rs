@@ -0,0 +1,220 @@
+use super::{new, Future, FutureSetter};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Creates a `Coalescer` calling `f` for requests, where requests with the same `key` made within
+/// `window` of the first call share its `Future`.
+/// # Examples
+/// ```
+/// use future;
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+/// use std::time::Duration;
+///
+/// let lookups = Rc::new(RefCell::new(vec![]));
+/// let lookups2 = lookups.clone();
+/// let users = future::coalesce(Duration::from_secs(1), |id: &u64| *id, move |id| {
+///     let (f, setter) = future::new::<String, ()>();
+///     lookups2.borrow_mut().push((id, setter));
+///     f
+/// });
+///
+/// let first = users.call(7);
+/// let second = users.call(7);
+/// assert_eq!(1, lookups.borrow().len());
+///
+/// let (id, setter) = lookups.borrow_mut().pop().unwrap();
+/// setter.set_result(Ok(format!("user {}", id)): Result<String, ()>);
+/// assert_eq!(Ok(String::from("user 7")), future::await(first));
+/// assert_eq!(Ok(String::from("user 7")), future::await(second));
+/// ```
+pub fn coalesce<R, K, A, E, KF, F>(window: Duration, key: KF, f: F) -> Coalescer<R, K, A, E>
+    where KF: Fn(&R) -> K, KF: 'static,
+          F: Fn(R) -> Future<A, E>, F: 'static,
+          K: Hash + Eq + Clone + 'static,
+          A: Clone + 'static,
+          E: Clone + 'static
+{
+    Coalescer {
+        window: window,
+        key: Arc::new(key),
+        f: Arc::new(f),
+        state: Arc::new(Mutex::new(CoalesceState { groups: HashMap::new(), next_id: 0 }))
+    }
+}
+
+/// Shares a single underlying `Future` between identical requests, to prevent a dogpile of
+/// identical calls (e.g. on a cache miss). Created with `future::coalesce`; clones share the same
+/// in-flight requests.
+///
+/// A request joins the in-flight call for its key if that call started less than the window ago,
+/// and otherwise starts a new call. Every request sharing a call receives a clone of its result.
+pub struct Coalescer<R, K, A, E>
+    where R: 'static, K: 'static, A: 'static, E: 'static
+{
+    window: Duration,
+    key: Arc<Fn(&R) -> K>,
+    f: Arc<Fn(R) -> Future<A, E>>,
+    state: Arc<Mutex<CoalesceState<K, A, E>>>
+}
+
+struct CoalesceState<K, A, E>
+    where K: 'static, A: 'static, E: 'static
+{
+    groups: HashMap<K, Group<A, E>>,
+    next_id: u64
+}
+
+struct Group<A, E>
+    where A: 'static, E: 'static
+{
+    id: u64,
+    started: Instant,
+    waiters: Arc<Mutex<Vec<FutureSetter<A, E>>>>
+}
+
+impl<R, K, A, E> Coalescer<R, K, A, E>
+    where K: Hash + Eq + Clone + 'static,
+          A: Clone + 'static,
+          E: Clone + 'static
+{
+    /// Requests `req`, joining an in-flight call for the same key if it started within the window.
+    pub fn call(&self, req: R) -> Future<A, E> {
+        let key = (self.key)(&req);
+        let (future, setter) = new();
+        let (id, waiters) = {
+            let mut state = self.state.lock().unwrap();
+            if let Some(group) = state.groups.get(&key) {
+                if group.started.elapsed() < self.window {
+                    group.waiters.lock().unwrap().push(setter);
+                    return future;
+                }
+            }
+            let id = state.next_id;
+            state.next_id += 1;
+            let waiters = Arc::new(Mutex::new(vec![setter]));
+            state.groups.insert(key.clone(), Group { id: id, started: Instant::now(), waiters: waiters.clone() });
+            (id, waiters)
+        };
+
+        // Dropped with the callback, which also ends the call if the callback never runs.
+        let call = PendingGroup { state: self.state.clone(), key: key, id: id, waiters: waiters };
+        (self.f)(req).register(move |result| {
+            for waiter in call.finish() {
+                waiter.set_result(result.clone());
+            }
+        });
+        future
+    }
+
+    /// The number of keys with a call in flight.
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().groups.len()
+    }
+}
+
+/// The group of an underlying call, ended once the call resolves, or its `FutureSetter` is dropped
+/// without setting a result, in which case the waiters' setters are dropped in turn.
+struct PendingGroup<K, A, E>
+    where K: Hash + Eq + 'static, A: 'static, E: 'static
+{
+    state: Arc<Mutex<CoalesceState<K, A, E>>>,
+    key: K,
+    id: u64,
+    waiters: Arc<Mutex<Vec<FutureSetter<A, E>>>>
+}
+
+impl<K: Hash + Eq, A, E> PendingGroup<K, A, E> {
+    /// Removes the group, if it's still in flight, and takes its waiters.
+    fn finish(&self) -> Vec<FutureSetter<A, E>> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return vec![]
+        };
+        // Once the window passes, a newer call for this key may have replaced this one.
+        if state.groups.get(&self.key).map(|group| group.id) == Some(self.id) {
+            state.groups.remove(&self.key);
+        }
+        let mut waiters = match self.waiters.lock() {
+            Ok(waiters) => waiters,
+            Err(_) => return vec![]
+        };
+        waiters.drain(..).collect::<Vec<_>>()
+    }
+}
+
+impl<K: Hash + Eq, A, E> Drop for PendingGroup<K, A, E> {
+    fn drop(&mut self) {
+        drop(self.finish());
+    }
+}
+
+impl<R, K, A, E> Clone for Coalescer<R, K, A, E> {
+    fn clone(&self) -> Coalescer<R, K, A, E> {
+        Coalescer {
+            window: self.window,
+            key: self.key.clone(),
+            f: self.f.clone(),
+            state: self.state.clone()
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, await_safe};
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::thread;
+
+    #[test]
+    fn requests_after_the_window_start_a_new_call() {
+        let calls = Rc::new(Cell::new(0));
+        let calls2 = calls.clone();
+        let setters = Rc::new(RefCell::new(vec![]));
+        let setters2 = setters.clone();
+        let coalescer = coalesce(Duration::from_millis(20), |k: &&str| *k, move |k: &str| {
+            calls2.set(calls2.get() + 1);
+            let (future, setter) = new::<String, ()>();
+            setters2.borrow_mut().push(setter);
+            future.map(move |s| format!("{}{}", k, s))
+        });
+
+        let first = coalescer.call("a");
+        let joined = coalescer.call("a");
+        let other = coalescer.call("b");
+        thread::sleep(Duration::from_millis(30));
+        let late = coalescer.call("a");
+        assert_eq!(calls.get(), 3);
+
+        for (i, setter) in setters.borrow_mut().drain(..).enumerate() {
+            setter.set_result(Ok(i.to_string()): Result<String, ()>);
+        }
+        assert_eq!(await(first), Ok(String::from("a0")));
+        assert_eq!(await(joined), Ok(String::from("a0")));
+        assert_eq!(await(other), Ok(String::from("b1")));
+        assert_eq!(await(late), Ok(String::from("a2")));
+        assert_eq!(coalescer.in_flight(), 0);
+    }
+
+    #[test]
+    fn dropped_setters_fail_the_group() {
+        let setters = Rc::new(RefCell::new(vec![]));
+        let setters2 = setters.clone();
+        let coalescer = coalesce(Duration::from_secs(60), |k: &&str| *k, move |_: &str| {
+            let (future, setter) = new::<String, ()>();
+            setters2.borrow_mut().push(setter);
+            future
+        });
+
+        let first = coalescer.call("a");
+        let joined = coalescer.call("a");
+        assert_eq!(coalescer.in_flight(), 1);
+        setters.borrow_mut().clear();
+        assert_eq!(coalescer.in_flight(), 0);
+        assert!(await_safe(first).is_err());
+        assert!(await_safe(joined).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use super::{new, Future};
+use std::boxed::FnBox;
+
+/// The callback handed to the function given to `futurize`, which sets the `Future`'s result.
+pub type ResultCallback<A, E> = Box<FnBox(Result<A, E>) -> () + Send>;
+
+/// The callback handed to the function given to `futurize_node`, taking an error, if there was
+/// one, and otherwise a value.
+pub type NodeCallback<A, E> = Box<FnBox(Option<E>, Option<A>) -> () + Send>;
+
+/// Consumes `future`, calling `on_ok` with its value or `on_err` with its error, for handing the
+/// result to an API built on separate success and failure callbacks.
+/// # Examples
+/// ```
+/// use future;
+///
+/// future::callbackify(
+///     future::value::<String, String>(String::from("pong")),
+///     |reply| println!("got {}", reply),
+///     |e| println!("failed: {}", e));
+/// ```
+pub fn callbackify<A, E, F, G>(future: Future<A, E>, on_ok: F, on_err: G)
+    where F: FnOnce(A) -> () + 'static,
+          G: FnOnce(E) -> () + 'static,
+          A: 'static,
+          E: 'static
+{
+    future.resolve(|result| match result {
+        Ok(a) => on_ok(a),
+        Err(e) => on_err(e)
+    })
+}
+
+/// Adapts an API that reports its result to a callback into one returning a `Future`. `f` starts
+/// the operation, passing on the callback it's given. If the callback is dropped without being
+/// called, the `Future` is left unresolved, as if its `FutureSetter` had been dropped.
+/// # Examples
+/// ```
+/// use future;
+/// use std::thread;
+///
+/// fn legacy_lookup<F: FnOnce(Result<u64, String>) + Send + 'static>(id: u64, callback: F) {
+///     thread::spawn(move || callback(Ok(id * 2)));
+/// }
+///
+/// let f = future::futurize(|callback| legacy_lookup(21, move |result| callback(result)));
+/// assert_eq!(Ok(42), future::await(f));
+/// ```
+pub fn futurize<F, A, E>(f: F) -> Future<A, E>
+    where F: FnOnce(ResultCallback<A, E>) -> (),
+          A: 'static,
+          E: 'static
+{
+    let (future, setter) = new();
+    f(Box::new(move |result| setter.set_result(result)));
+    future
+}
+
+/// Like `futurize`, for APIs whose callbacks take an optional error and an optional value. The
+/// `Future` fails if there's an error, and otherwise succeeds with the value, if any.
+/// # Examples
+/// ```
+/// use future;
+///
+/// fn legacy_read<F: FnOnce(Option<String>, Option<u64>)>(callback: F) {
+///     callback(Some(String::from("EACCES")), None)
+/// }
+///
+/// let f = future::futurize_node(|callback| legacy_read(move |err, len| callback(err, len)));
+/// assert_eq!(Err(String::from("EACCES")), future::await(f));
+/// ```
+pub fn futurize_node<F, A, E>(f: F) -> Future<Option<A>, E>
+    where F: FnOnce(NodeCallback<A, E>) -> (),
+          A: 'static,
+          E: 'static
+{
+    futurize(|callback| f(Box::new(move |err, a| callback(match err {
+        Some(e) => Err(e),
+        None => Ok(a)
+    }))))
+}
+
+mod test {
+    use super::*;
+    use super::super::{await_safe, value};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn futurized_callbacks_round_trip() {
+        let seen = Arc::new(Mutex::new(None));
+        let seen2 = seen.clone();
+        let f = futurize(|callback: ResultCallback<i64, String>| {
+            callbackify(value::<i64, String>(1), move |i| callback(Ok(i + 1)), |e| panic!("{}", e))
+        });
+        callbackify(f, move |i| *seen2.lock().unwrap() = Some(i), |e| panic!("{}", e));
+        assert_eq!(*seen.lock().unwrap(), Some(2));
+
+        let dropped = futurize_node(|callback: NodeCallback<i64, ()>| drop(callback));
+        assert!(await_safe(dropped).is_err());
+    }
+}
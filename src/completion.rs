@@ -0,0 +1,194 @@
+use super::Future;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Consumes this `Future`, delivering its result to `sink` once it resolves. Returns the id
+    /// the result will be delivered with, assigned in the order `Future`s are forwarded.
+    pub fn forward_to(self, sink: &CompletionSink<A, E>) -> usize {
+        let id = {
+            let mut state = sink.shared.state.lock().unwrap();
+            state.next_id += 1;
+            state.pending += 1;
+            state.next_id - 1
+        };
+        let shared = sink.shared.clone();
+        self.register(move |result| shared.complete(id, result));
+        id
+    }
+}
+
+/// Collects the results of many `Future`s, in the order they resolve, for fanning in work without
+/// a channel per `Future`. Results are taken with `recv`, or handed to a consumer given to
+/// `with_consumer`. Clones of a `CompletionSink` share the same results.
+/// # Examples
+/// ```
+/// use future;
+/// use future::CompletionSink;
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let sink = CompletionSink::new();
+/// let slow = future::run(|| -> Result<&str, ()> {
+///     thread::sleep(Duration::from_millis(20));
+///     Ok("slow")
+/// }).forward_to(&sink);
+/// let fast = future::value("fast").forward_to(&sink);
+///
+/// assert_eq!(Some((fast, Ok("fast"))), sink.recv());
+/// assert_eq!(Some((slow, Ok("slow"))), sink.recv());
+/// assert_eq!(None, sink.recv());
+/// ```
+pub struct CompletionSink<A, E> {
+    shared: Arc<SinkShared<A, E>>
+}
+
+struct SinkShared<A, E> {
+    state: Mutex<SinkState<A, E>>,
+    condvar: Condvar
+}
+
+struct SinkState<A, E> {
+    next_id: usize,
+    /// The number of forwarded `Future`s that haven't resolved yet
+    pending: usize,
+    completed: VecDeque<(usize, Result<A, E>)>,
+    consumer: Option<Box<FnMut(usize, Result<A, E>) -> () + Send>>,
+    /// Whether a thread is handing completions to the consumer
+    draining: bool
+}
+
+impl<A, E> CompletionSink<A, E> {
+    /// Creates a `CompletionSink` whose results are taken with `recv`.
+    pub fn new() -> CompletionSink<A, E> {
+        CompletionSink {
+            shared: Arc::new(SinkShared {
+                state: Mutex::new(SinkState {
+                    next_id: 0,
+                    pending: 0,
+                    completed: VecDeque::new(),
+                    consumer: None,
+                    draining: false
+                }),
+                condvar: Condvar::new()
+            })
+        }
+    }
+
+    /// Creates a `CompletionSink` that hands each result, with its id, to `consumer` on the thread
+    /// that resolved it. Calls to `consumer` never overlap, and follow the order of resolution.
+    pub fn with_consumer<F>(consumer: F) -> CompletionSink<A, E>
+        where F: FnMut(usize, Result<A, E>) -> () + Send + 'static
+    {
+        let sink = CompletionSink::new();
+        sink.shared.state.lock().unwrap().consumer = Some(box consumer);
+        sink
+    }
+
+    /// Blocks until a forwarded `Future` resolves, returning its id and result, or returns `None`
+    /// at once if every forwarded `Future` has already been received.
+    pub fn recv(&self) -> Option<(usize, Result<A, E>)> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(completion) = state.completed.pop_front() {
+                return Some(completion);
+            }
+            if state.pending == 0 || state.consumer.is_some() || state.draining {
+                return None;
+            }
+            state = self.shared.condvar.wait(state).unwrap();
+        }
+    }
+
+    /// Like `recv`, except it gives up and returns `None` after `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<(usize, Result<A, E>)> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(completion) = state.completed.pop_front() {
+                return Some(completion);
+            }
+            let now = Instant::now();
+            if state.pending == 0 || state.consumer.is_some() || state.draining || now >= deadline {
+                return None;
+            }
+            state = self.shared.condvar.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// The number of forwarded `Future`s that haven't resolved yet.
+    pub fn pending(&self) -> usize {
+        self.shared.state.lock().unwrap().pending
+    }
+
+    /// Delivers `result` with the given `id`, as if from a forwarded `Future`.
+    pub(crate) fn deliver(&self, id: usize, result: Result<A, E>) {
+        self.shared.state.lock().unwrap().pending += 1;
+        self.shared.complete(id, result);
+    }
+}
+
+impl<A, E> SinkShared<A, E> {
+    fn complete(&self, id: usize, result: Result<A, E>) {
+        let mut consumer = {
+            let mut state = self.state.lock().unwrap();
+            state.pending -= 1;
+            state.completed.push_back((id, result));
+            if state.draining || state.consumer.is_none() {
+                self.condvar.notify_all();
+                return;
+            }
+            state.draining = true;
+            state.consumer.take().unwrap()
+        };
+
+        // Completions arriving while the consumer runs are queued, and handed over by this loop,
+        // so that a consumer forwarding an already resolved `Future` doesn't deadlock.
+        loop {
+            let completion = {
+                let mut state = self.state.lock().unwrap();
+                match state.completed.pop_front() {
+                    Some(completion) => completion,
+                    None => {
+                        state.consumer = Some(consumer);
+                        state.draining = false;
+                        return;
+                    }
+                }
+            };
+            consumer(completion.0, completion.1);
+        }
+    }
+}
+
+impl<A, E> Clone for CompletionSink<A, E> {
+    fn clone(&self) -> CompletionSink<A, E> {
+        CompletionSink { shared: self.shared.clone() }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{err, new, value};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn consumers_receive_completions_in_arrival_order() {
+        let received = Arc::new(Mutex::new(vec![]));
+        let received2 = received.clone();
+        let sink = CompletionSink::with_consumer(move |id, result: Result<i64, String>| {
+            received2.lock().unwrap().push((id, result))
+        });
+
+        let (f, setter) = new::<i64, String>();
+        assert_eq!(f.forward_to(&sink), 0);
+        value::<i64, String>(1).forward_to(&sink);
+        err::<i64, String>(String::from("failed")).forward_to(&sink);
+        assert_eq!(sink.pending(), 1);
+
+        setter.set_result(Ok(2): Result<i64, String>);
+        assert_eq!(*received.lock().unwrap(), vec![(1, Ok(1)), (2, Err(String::from("failed"))), (0, Ok(2))]);
+        assert_eq!(sink.recv(), None);
+    }
+}
@@ -0,0 +1,339 @@
+//! Crate-wide settings affecting every `Future`.
+
+use super::log::{self, Level};
+use super::TimeoutError;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_BOOL_INIT, ATOMIC_USIZE_INIT};
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct SlowCallbackConfig {
+    threshold: Duration,
+    handler: Arc<Fn(Duration) -> () + Send + Sync>
+}
+
+static SLOW_CALLBACK_ENABLED: AtomicBool = ATOMIC_BOOL_INIT;
+static SLOW_CALLBACK_INIT: Once = ONCE_INIT;
+static mut SLOW_CALLBACK: *const Mutex<Option<SlowCallbackConfig>> = 0 as *const _;
+
+/// Report any transformation or side-effect callback that runs for longer than `threshold`.
+/// `handler` is called with the callback's execution time on the thread that ran it, just after
+/// the slow callback returns. This is off by default, and is useful for finding callbacks that
+/// block the thread resolving a `Future`.
+/// # Examples
+/// ```
+/// use future::config;
+/// use std::time::Duration;
+///
+/// config::set_slow_callback_handler(Duration::from_millis(10), |elapsed| {
+///     println!("A callback blocked for {:?}", elapsed);
+/// });
+/// # config::clear_slow_callback_handler();
+/// ```
+pub fn set_slow_callback_handler<F>(threshold: Duration, handler: F)
+    where F: Fn(Duration) -> () + Send + Sync + 'static
+{
+    *slow_callback().lock().unwrap() = Some(SlowCallbackConfig {
+        threshold: threshold,
+        handler: Arc::new(handler)
+    });
+    SLOW_CALLBACK_ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Turn off slow callback reporting.
+pub fn clear_slow_callback_handler() {
+    SLOW_CALLBACK_ENABLED.store(false, Ordering::SeqCst);
+    *slow_callback().lock().unwrap() = None;
+}
+
+/// Runs the user-supplied callback `f`, reporting it if it exceeds the slow callback threshold.
+pub(crate) fn timed<F, B>(f: F) -> B
+    where F: FnOnce() -> B
+{
+    if !SLOW_CALLBACK_ENABLED.load(Ordering::Relaxed) {
+        return f();
+    }
+
+    let start = Instant::now();
+    let b = f();
+    let elapsed = start.elapsed();
+
+    let handler = match *slow_callback().lock().unwrap() {
+        Some(ref config) if elapsed > config.threshold => Some(config.handler.clone()),
+        _ => None
+    };
+    if let Some(handler) = handler {
+        handler(elapsed);
+    }
+    b
+}
+
+fn slow_callback() -> &'static Mutex<Option<SlowCallbackConfig>> {
+    unsafe {
+        SLOW_CALLBACK_INIT.call_once(|| {
+            SLOW_CALLBACK = Box::into_raw(box Mutex::new(None));
+        });
+        &*SLOW_CALLBACK
+    }
+}
+
+/// What to do when a `FutureSetter` is dropped without setting a result, leaving its `Future` (and
+/// any transformations registered on it) unresolved forever.
+#[derive(Clone)]
+pub enum DropPolicy {
+    /// Do nothing. This is the default.
+    Ignore,
+    /// Log a warning through the `log` module.
+    Log,
+    /// Call the handler.
+    Handler(Arc<Fn(&UnresolvedDrop) -> () + Send + Sync>),
+    /// Panic, in builds with debug assertions enabled; otherwise do nothing. Never panics while the
+    /// thread is already panicking.
+    PanicInDebug
+}
+
+impl fmt::Debug for DropPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DropPolicy::Ignore => write!(f, "Ignore"),
+            DropPolicy::Log => write!(f, "Log"),
+            DropPolicy::Handler(_) => write!(f, "Handler(..)"),
+            DropPolicy::PanicInDebug => write!(f, "PanicInDebug")
+        }
+    }
+}
+
+/// Describes a `FutureSetter` dropped without setting a result, for `DropPolicy::Handler`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct UnresolvedDrop {
+    /// Whether a transformation, side-effect or consumer had been registered on the `Future`,
+    /// i.e. whether any work was lost
+    pub callback_registered: bool
+}
+
+impl fmt::Display for UnresolvedDrop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.callback_registered {
+            write!(f, "FutureSetter dropped without setting a result; registered callbacks will never run")
+        } else {
+            write!(f, "FutureSetter dropped without setting a result")
+        }
+    }
+}
+
+static DROP_POLICY_INIT: Once = ONCE_INIT;
+static mut DROP_POLICY: *const Mutex<DropPolicy> = 0 as *const _;
+
+/// Set the crate-level `DropPolicy`, applied to every `FutureSetter` created by `future::new` (or
+/// the functions built on it) that doesn't override it. Setters created internally by combinators
+/// are only reported when given an override, since they are dropped along with the chain feeding
+/// them, whose root setter is reported already.
+/// # Examples
+/// ```
+/// use future::config::{self, DropPolicy};
+///
+/// config::set_drop_policy(DropPolicy::PanicInDebug);
+/// # config::set_drop_policy(DropPolicy::Ignore);
+/// ```
+pub fn set_drop_policy(policy: DropPolicy) {
+    *drop_policy().lock().unwrap() = policy;
+}
+
+pub(crate) fn report_unresolved_drop(policy: Option<&DropPolicy>, derived: bool, callback_registered: bool) {
+    let policy = match policy {
+        Some(policy) => policy.clone(),
+        None if derived => return,
+        None => drop_policy().lock().unwrap().clone()
+    };
+    let event = UnresolvedDrop { callback_registered: callback_registered };
+    match policy {
+        DropPolicy::Ignore => {},
+        DropPolicy::Log => log::log(Level::Warn, "future", &event.to_string()),
+        DropPolicy::Handler(handler) => handler(&event),
+        DropPolicy::PanicInDebug => {
+            if cfg!(debug_assertions) && !thread::panicking() {
+                panic!("{}", event);
+            }
+        }
+    }
+}
+
+fn drop_policy() -> &'static Mutex<DropPolicy> {
+    unsafe {
+        DROP_POLICY_INIT.call_once(|| {
+            DROP_POLICY = Box::into_raw(box Mutex::new(DropPolicy::Ignore));
+        });
+        &*DROP_POLICY
+    }
+}
+
+/// How `future::await` and `future::await_safe` wait for an unresolved `Future`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AwaitStrategy {
+    /// Park the thread until the result is set. This is the default.
+    Park,
+    /// Poll for the result up to `spins` times before parking, trading CPU time for a lower
+    /// wakeup latency when results are set very soon after `await` is called.
+    SpinThenPark { spins: usize }
+}
+
+static AWAIT_SPINS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Set the crate-level `AwaitStrategy`.
+/// # Examples
+/// ```
+/// use future::config::{self, AwaitStrategy};
+///
+/// config::set_await_strategy(AwaitStrategy::SpinThenPark { spins: 1000 });
+/// # config::set_await_strategy(AwaitStrategy::Park);
+/// ```
+pub fn set_await_strategy(strategy: AwaitStrategy) {
+    let spins = match strategy {
+        AwaitStrategy::Park => 0,
+        AwaitStrategy::SpinThenPark { spins } => spins
+    };
+    AWAIT_SPINS.store(spins, Ordering::SeqCst);
+}
+
+/// The number of times to poll for a result before parking.
+pub(crate) fn await_spins() -> usize {
+    AWAIT_SPINS.load(Ordering::Relaxed)
+}
+
+static DEFAULT_TIMEOUT_INIT: Once = ONCE_INIT;
+static mut DEFAULT_TIMEOUT: *const Mutex<Option<Duration>> = 0 as *const _;
+
+/// Set a timeout applied to every `Future` returned by `future::run`, `future::run_cancellable`,
+/// `ThreadPool::run` or a `Service` wrapped in a `Filter`, as a safety net against forgotten
+/// per-call timeouts. `Future`s created with `future::new` are not affected. One that exceeds the
+/// timeout fails with a `TimeoutError`, converted to its error type, and a warning is logged
+/// through the `log` module. The timeout only applies to `Future`s whose error type is
+/// `TimeoutError`, or has been registered with `register_timeout_error`; it can't fail others.
+/// Off (`None`) by default; it only applies to `Future`s created after it is set.
+/// # Examples
+/// ```
+/// use future;
+/// use future::config;
+/// use future::TimeoutError;
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// #[derive(Debug)]
+/// enum FetchError {
+///     TimedOut(TimeoutError)
+/// }
+///
+/// impl From<TimeoutError> for FetchError {
+///     fn from(e: TimeoutError) -> FetchError {
+///         FetchError::TimedOut(e)
+///     }
+/// }
+///
+/// config::register_timeout_error::<FetchError>();
+/// config::set_default_timeout(Some(Duration::from_millis(10)));
+/// let f = future::run(|| -> Result<(), FetchError> { Ok(thread::sleep(Duration::from_millis(100))) });
+/// match future::await(f) {
+///     Err(FetchError::TimedOut(_)) => {},
+///     other => panic!("Expected a timeout, got {:?}", other)
+/// }
+/// # config::set_default_timeout(None);
+/// ```
+pub fn set_default_timeout(timeout: Option<Duration>) {
+    *default_timeout_lock().lock().unwrap() = timeout;
+}
+
+pub(crate) fn default_timeout() -> Option<Duration> {
+    *default_timeout_lock().lock().unwrap()
+}
+
+type TimeoutErrors = HashMap<TypeId, Box<Any + Send>>;
+
+static TIMEOUT_ERRORS_INIT: Once = ONCE_INIT;
+static mut TIMEOUT_ERRORS: *const Mutex<TimeoutErrors> = 0 as *const _;
+
+/// Lets the default timeout fail `Future`s with the error type `E`, converting the `TimeoutError`
+/// with `E::from`. See `set_default_timeout`.
+pub fn register_timeout_error<E: From<TimeoutError> + 'static>() {
+    let convert: Arc<Fn(TimeoutError) -> E + Send + Sync> = Arc::new(E::from);
+    timeout_errors().lock().unwrap().insert(TypeId::of::<E>(), box convert);
+}
+
+/// The conversion registered for the error type `E`, if any.
+pub(crate) fn timeout_error<E: 'static>() -> Option<Arc<Fn(TimeoutError) -> E + Send + Sync>> {
+    timeout_errors().lock().unwrap().get(&TypeId::of::<E>())
+        .and_then(|convert| convert.downcast_ref::<Arc<Fn(TimeoutError) -> E + Send + Sync>>())
+        .cloned()
+}
+
+fn timeout_errors() -> &'static Mutex<TimeoutErrors> {
+    unsafe {
+        TIMEOUT_ERRORS_INIT.call_once(|| {
+            let mut errors: TimeoutErrors = HashMap::new();
+            let convert: Arc<Fn(TimeoutError) -> TimeoutError + Send + Sync> = Arc::new(|e| e);
+            errors.insert(TypeId::of::<TimeoutError>(), box convert);
+            TIMEOUT_ERRORS = Box::into_raw(box Mutex::new(errors));
+        });
+        &*TIMEOUT_ERRORS
+    }
+}
+
+fn default_timeout_lock() -> &'static Mutex<Option<Duration>> {
+    unsafe {
+        DEFAULT_TIMEOUT_INIT.call_once(|| {
+            DEFAULT_TIMEOUT = Box::into_raw(box Mutex::new(None));
+        });
+        &*DEFAULT_TIMEOUT
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, new, value};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn slow_callbacks_are_reported() {
+        // The handler is global, so other tests running alongside this one may report slow
+        // callbacks too; handlers run on the thread that ran the callback, so only this thread's
+        // reports are kept.
+        let reports = Arc::new(Mutex::new(vec![]));
+        let reports2 = reports.clone();
+        let this_thread = thread::current().id();
+        set_slow_callback_handler(Duration::from_millis(20), move |elapsed| {
+            if thread::current().id() == this_thread {
+                reports2.lock().unwrap().push(elapsed)
+            }
+        });
+
+        let f = value::<i64, ()>(1)
+            .map(|i| i + 1)
+            .map(|i| {
+                thread::sleep(Duration::from_millis(40));
+                i + 1
+            });
+        assert_eq!(await(f), Ok(3));
+        clear_slow_callback_handler();
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0] >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn drop_policy_overrides_report_dropped_setters() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let events2 = events.clone();
+        let (f, setter) = new::<i64, ()>();
+        let _f = f.with_drop_policy(DropPolicy::Handler(Arc::new(move |event: &UnresolvedDrop| {
+            events2.lock().unwrap().push(*event)
+        })));
+
+        drop(setter);
+        assert_eq!(*events.lock().unwrap(), vec![UnresolvedDrop { callback_registered: false }]);
+    }
+}
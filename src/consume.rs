@@ -0,0 +1,127 @@
+use super::Future;
+use std::fmt::Debug;
+use std::thread;
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Wraps this `Future` in an `UnconsumedFuture`, whose only exits are the terminal consumers.
+    pub fn unconsumed(self) -> UnconsumedFuture<A, E> {
+        UnconsumedFuture { future: Some(self) }
+    }
+}
+
+/// A `Future` that must be consumed: built on with `chain` and `map`, and left only by `resolve`,
+/// `resolve_success`, `resolve_err`, `await`, `swallow_err_with_log` or `detach`. Created with
+/// `Future::unconsumed`.
+///
+/// Rust can't forbid dropping a value, so this isn't a compile-time guarantee. Discarding the chain
+/// as it is built is an `unused_must_use` warning, which is only a compile error with
+/// `#![deny(unused_must_use)]`. Dropping one that was kept compiles, and panics in debug builds,
+/// unless the thread is already panicking; in release builds it goes unnoticed.
+/// # Examples
+/// ```
+/// #![deny(unused_must_use)]
+/// use future;
+///
+/// let (f, setter) = future::new::<i64, String>();
+/// f.unconsumed()
+///     .map(|i| i + 1)
+///     .chain(|f| f.map_err(|e| e + " while counting"))
+///     .resolve(|result| println!("{:?}", result));
+/// setter.set_result(Ok(1): Result<i64, String>);
+/// ```
+#[must_use = "an UnconsumedFuture must be resolved, awaited or detached"]
+pub struct UnconsumedFuture<A, E>
+    where A: 'static, E: 'static
+{
+    /// Taken by the consumer
+    future: Option<Future<A, E>>
+}
+
+impl<A: 'static, E: 'static> UnconsumedFuture<A, E> {
+    /// Applies combinators to the wrapped `Future`.
+    pub fn chain<F, B, E2>(self, f: F) -> UnconsumedFuture<B, E2>
+        where F: FnOnce(Future<A, E>) -> Future<B, E2>,
+              B: 'static,
+              E2: 'static
+    {
+        f(self.take()).unconsumed()
+    }
+
+    /// Like `Future::map`.
+    pub fn map<F, B>(self, f: F) -> UnconsumedFuture<B, E>
+        where F: FnOnce(A) -> B, F: 'static,
+              B: 'static
+    {
+        self.chain(|future| future.map(f))
+    }
+
+    /// Like `Future::resolve`.
+    pub fn resolve<F>(self, f: F)
+        where F: FnOnce(Result<A, E>) -> (), F: 'static
+    {
+        self.take().resolve(f)
+    }
+
+    /// Like `Future::resolve_success`.
+    pub fn resolve_success<F>(self, f: F)
+        where F: FnOnce(A) -> (), F: 'static
+    {
+        self.take().resolve_success(f)
+    }
+
+    /// Like `Future::resolve_err`.
+    pub fn resolve_err<F>(self, f: F)
+        where F: FnOnce(E) -> (), F: 'static
+    {
+        self.take().resolve_err(f)
+    }
+
+    /// Like `future::await`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn await(self) -> Result<A, E> {
+        super::await(self.take())
+    }
+
+    /// Like `Future::swallow_err_with_log`.
+    pub fn swallow_err_with_log(self)
+        where E: Debug
+    {
+        self.take().swallow_err_with_log()
+    }
+
+    /// Lets the `Future` run to completion, discarding its result, success or failure.
+    pub fn detach(self) {
+        self.take().resolve(|_| {})
+    }
+
+    fn take(mut self) -> Future<A, E> {
+        self.future.take().unwrap()
+    }
+}
+
+impl<A: 'static, E: 'static> Drop for UnconsumedFuture<A, E> {
+    fn drop(&mut self) {
+        if cfg!(debug_assertions) && self.future.is_some() && !thread::panicking() {
+            panic!("An UnconsumedFuture was dropped without being resolved, awaited or detached");
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{new, value};
+    use std::panic;
+
+    #[test]
+    fn unconsumed_futures_must_be_consumed() {
+        assert_eq!(value::<i64, ()>(1).unconsumed().map(|i| i + 1).await(), Ok(2));
+
+        let (f, _setter) = new::<i64, ()>();
+        let f = f.unconsumed();
+        if cfg!(debug_assertions) {
+            assert!(panic::catch_unwind(panic::AssertUnwindSafe(move || drop(f))).is_err());
+        } else {
+            f.detach();
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use super::Future;
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// A typed value attached to a `ContextualError`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContextValue {
+    Str(String),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bool(bool),
+    Duration(Duration)
+}
+
+impl fmt::Display for ContextValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ContextValue::Str(ref s) => write!(f, "{}", s),
+            ContextValue::Int(i) => write!(f, "{}", i),
+            ContextValue::UInt(u) => write!(f, "{}", u),
+            ContextValue::Float(x) => write!(f, "{}", x),
+            ContextValue::Bool(b) => write!(f, "{}", b),
+            ContextValue::Duration(d) => write!(f, "{:?}", d)
+        }
+    }
+}
+
+impl<'a> From<&'a str> for ContextValue {
+    fn from(s: &'a str) -> ContextValue { ContextValue::Str(String::from(s)) }
+}
+
+impl From<String> for ContextValue {
+    fn from(s: String) -> ContextValue { ContextValue::Str(s) }
+}
+
+impl From<i64> for ContextValue {
+    fn from(i: i64) -> ContextValue { ContextValue::Int(i) }
+}
+
+impl From<i32> for ContextValue {
+    fn from(i: i32) -> ContextValue { ContextValue::Int(i as i64) }
+}
+
+impl From<u64> for ContextValue {
+    fn from(u: u64) -> ContextValue { ContextValue::UInt(u) }
+}
+
+impl From<u32> for ContextValue {
+    fn from(u: u32) -> ContextValue { ContextValue::UInt(u as u64) }
+}
+
+impl From<usize> for ContextValue {
+    fn from(u: usize) -> ContextValue { ContextValue::UInt(u as u64) }
+}
+
+impl From<f64> for ContextValue {
+    fn from(x: f64) -> ContextValue { ContextValue::Float(x) }
+}
+
+impl From<bool> for ContextValue {
+    fn from(b: bool) -> ContextValue { ContextValue::Bool(b) }
+}
+
+impl From<Duration> for ContextValue {
+    fn from(d: Duration) -> ContextValue { ContextValue::Duration(d) }
+}
+
+/// An error carrying structured key/value context, attached with `Future::map_err_context` and
+/// `Future::err_context` as the error propagates, so that observability tooling can read fields
+/// rather than parsing messages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextualError<E> {
+    error: E,
+    context: Vec<(&'static str, ContextValue)>
+}
+
+impl<E> ContextualError<E> {
+    pub fn new(error: E) -> ContextualError<E> {
+        ContextualError { error: error, context: vec![] }
+    }
+
+    /// Attaches `value` under `key`, in addition to any value already under `key`.
+    pub fn with<V: Into<ContextValue>>(mut self, key: &'static str, value: V) -> ContextualError<E> {
+        self.context.push((key, value.into()));
+        self
+    }
+
+    /// The underlying error.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+
+    /// Every key/value pair, in the order they were attached.
+    pub fn context(&self) -> &[(&'static str, ContextValue)] {
+        &self.context
+    }
+
+    /// The value most recently attached under `key`.
+    pub fn get(&self, key: &str) -> Option<&ContextValue> {
+        self.context.iter().rev().find(|&&(k, _)| k == key).map(|&(_, ref value)| value)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for ContextualError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        for (i, &(key, ref value)) in self.context.iter().enumerate() {
+            write!(f, "{}{}={}", if i == 0 { " (" } else { ", " }, key, value)?;
+        }
+        if !self.context.is_empty() {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: Error> Error for ContextualError<E> {
+    fn description(&self) -> &str {
+        self.error.description()
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        Some(&self.error)
+    }
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Wraps an error in a `ContextualError`, attaching `value` under `key`. Use `err_context` to
+    /// attach more context further along.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::ContextValue;
+    ///
+    /// let f = future::err::<(), &str>("connection refused")
+    ///     .map_err_context("operation", "fetch_user")
+    ///     .err_context("attempt", 3);
+    ///
+    /// let e = future::await(f).unwrap_err();
+    /// assert_eq!(Some(&ContextValue::Int(3)), e.get("attempt"));
+    /// assert_eq!("connection refused (operation=fetch_user, attempt=3)", e.to_string());
+    /// ```
+    pub fn map_err_context<V>(self, key: &'static str, value: V) -> Future<A, ContextualError<E>>
+        where V: Into<ContextValue> + 'static
+    {
+        self.map_err(move |e| ContextualError::new(e).with(key, value))
+    }
+}
+
+impl<A: 'static, E: 'static> Future<A, ContextualError<E>> {
+    /// Attaches `value` under `key` to an error.
+    pub fn err_context<V>(self, key: &'static str, value: V) -> Future<A, ContextualError<E>>
+        where V: Into<ContextValue> + 'static
+    {
+        self.map_err(move |e| e.with(key, value))
+    }
+}
@@ -0,0 +1,170 @@
+use super::{err, timer, Filter, Future, Service};
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Attaches `deadline` to this `Future`, or keeps its existing deadline if that's earlier.
+    /// Every `Future` derived from this one inherits it, and the callbacks of `transformf`,
+    /// `and_thenf` and `rescuef` run within it, so calls made through a `DeadlineFilter` from
+    /// those callbacks see the caller's deadline. `Future`s created within a deadline, such as by
+    /// `within_deadline`, carry it from the start.
+    pub fn with_deadline(self, deadline: Instant) -> Future<A, E> {
+        {
+            let mut meta = self.lock.lock().unwrap();
+            meta.deadline = earliest(meta.deadline, Some(deadline));
+        }
+        self
+    }
+
+    /// The deadline of this `Future`, if it was given or inherited one.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.lock.lock().unwrap().deadline
+    }
+}
+
+thread_local!(static CURRENT: Cell<Option<Instant>> = Cell::new(None));
+
+/// The deadline of the work running on this thread, if any.
+pub fn current_deadline() -> Option<Instant> {
+    CURRENT.with(|current| current.get())
+}
+
+/// Runs `f` within `deadline`, or within the current deadline if that's earlier, so that calls
+/// made through a `DeadlineFilter` by `f` are held to it.
+pub fn within_deadline<F, B>(deadline: Instant, f: F) -> B
+    where F: FnOnce() -> B
+{
+    run_within(Some(deadline), f)
+}
+
+/// Runs `f` within `deadline`, if there is one.
+pub(crate) fn run_within<F, B>(deadline: Option<Instant>, f: F) -> B
+    where F: FnOnce() -> B
+{
+    if deadline.is_none() {
+        return f();
+    }
+    let previous = CURRENT.with(|current| current.replace(earliest(current.get(), deadline)));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// Restores the previous deadline, even if the function run within a deadline panics.
+struct Restore(Option<Instant>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.set(self.0));
+    }
+}
+
+fn earliest(a: Option<Instant>, b: Option<Instant>) -> Option<Instant> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if a < b { a } else { b }),
+        (a, None) => a,
+        (None, b) => b
+    }
+}
+
+/// A `Filter` holding each call to the deadline of its caller, so that the deadline propagates
+/// through every layer and hop of a service stack with the time already spent deducted. Calls
+/// made with less than `min_remaining` left fail immediately with `DeadlineExceeded`, rather than
+/// starting work that can't finish in time; calls still running at the deadline fail with it too.
+/// Calls made outside any deadline are passed on unchanged.
+/// # Examples
+/// ```
+/// use future;
+/// use future::{DeadlineExceeded, DeadlineFilter, Filter, Service};
+/// use std::sync::Arc;
+/// use std::time::{Duration, Instant};
+///
+/// let backend = |id: u64| future::value::<u64, DeadlineExceeded>(id * 2);
+/// let frontend = DeadlineFilter::new(Duration::from_millis(10)).and_then(backend);
+///
+/// let now = Instant::now();
+/// let doomed = future::within_deadline(now + Duration::from_millis(5), || frontend.call(1));
+/// assert_eq!(Err(DeadlineExceeded), future::await(doomed));
+///
+/// let f = future::within_deadline(now + Duration::from_secs(5), || frontend.call(2));
+/// assert_eq!(Ok(4), future::await(f));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DeadlineFilter {
+    min_remaining: Duration
+}
+
+impl DeadlineFilter {
+    pub fn new(min_remaining: Duration) -> DeadlineFilter {
+        DeadlineFilter { min_remaining: min_remaining }
+    }
+}
+
+impl<Req, Resp, E> Filter<Req, Resp, E> for DeadlineFilter
+    where Resp: 'static,
+          E: From<DeadlineExceeded> + 'static
+{
+    fn apply(&self, req: Req, next: &Arc<Service<Req, Resp, E>>) -> Future<Resp, E> {
+        let deadline = match current_deadline() {
+            Some(deadline) => deadline,
+            None => return next.call(req)
+        };
+        let now = Instant::now();
+        if deadline < now + self.min_remaining {
+            return err(E::from(DeadlineExceeded));
+        }
+
+        let call = run_within(Some(deadline), || next.call(req));
+        let (future, setter) = call.derive();
+        let setter = setter.shared();
+
+        // The timer only holds a weak handle, so a call that resolves early isn't kept in memory
+        // until the deadline.
+        let exceeded_setter = setter.downgrade();
+        timer::at(deadline).register(move |_| {
+            exceeded_setter.set_if_unset(Err(DeadlineExceeded): Result<Resp, DeadlineExceeded>);
+        });
+
+        call.register(move |result| {
+            setter.set_if_unset(result);
+        });
+        future.with_deadline(deadline)
+    }
+}
+
+/// An Error indicating that a call couldn't complete before the deadline of its caller.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DeadlineExceeded;
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "DeadlineExceeded")
+    }
+}
+
+impl Error for DeadlineExceeded {
+    fn description(&self) -> &str {
+        "The call could not complete before the caller's deadline"
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, new, value};
+
+    #[test]
+    fn deadlines_follow_futures_into_their_continuations() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let (f, setter) = new::<(), ()>();
+        let f = f.with_deadline(deadline)
+            .map(|_| 1)
+            .and_thenf(|i| value::<i64, ()>(i).map(|_| current_deadline()));
+        assert_eq!(current_deadline(), None);
+
+        setter.set_result(Ok(()): Result<(), ()>);
+        assert_eq!(await(f), Ok(Some(deadline)));
+        assert_eq!(current_deadline(), None);
+    }
+}
@@ -0,0 +1,95 @@
+//! Debugging facilities for understanding how `Future`s are composed. Enabled with the `graph`
+//! feature, which records every `Future` and which `Future`s feed which as they are created.
+//!
+//! A `Future` stays in the graph until its `FutureSetter` is dropped, so the exported graph shows
+//! only the chains that are still pending.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::sync::{Mutex, Once, ONCE_INIT};
+
+struct Graph {
+    nodes: HashSet<usize>,
+    edges: HashMap<usize, Vec<usize>>
+}
+
+static NEXT_NODE: AtomicUsize = ATOMIC_USIZE_INIT;
+static GRAPH_INIT: Once = ONCE_INIT;
+static mut GRAPH: *const Mutex<Graph> = 0 as *const _;
+
+/// Dumps the pending `Future`s and the edges between them in Graphviz dot format, with an edge
+/// pointing from each `Future` to the `Future` derived from it by a combinator.
+/// # Examples
+/// ```
+/// use future;
+/// use future::debug;
+///
+/// let (f, _setter) = future::new::<i64, ()>();
+/// let _f = f.map(|i| i + 1);
+/// assert!(debug::export_dot().contains(" -> "));
+/// ```
+pub fn export_dot() -> String {
+    let graph = graph().lock().unwrap();
+    let mut nodes = graph.nodes.iter().collect::<Vec<_>>();
+    nodes.sort();
+
+    let mut dot = String::from("digraph futures {\n");
+    for node in nodes {
+        let _ = writeln!(dot, "    f{};", node);
+        if let Some(children) = graph.edges.get(node) {
+            for child in children.iter().filter(|child| graph.nodes.contains(child)) {
+                let _ = writeln!(dot, "    f{} -> f{};", node, child);
+            }
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// The number of `Future`s currently in the graph.
+pub fn pending_count() -> usize {
+    graph().lock().unwrap().nodes.len()
+}
+
+/// Keeps a `Future`'s node in the graph while alive. Owned by the `FutureSetter`.
+pub(crate) struct NodeHandle {
+    id: usize
+}
+
+impl NodeHandle {
+    pub(crate) fn new() -> NodeHandle {
+        let id = NEXT_NODE.fetch_add(1, Ordering::SeqCst);
+        graph().lock().unwrap().nodes.insert(id);
+        NodeHandle { id: id }
+    }
+
+    pub(crate) fn id(&self) -> usize {
+        self.id
+    }
+}
+
+impl Drop for NodeHandle {
+    fn drop(&mut self) {
+        let mut graph = graph().lock().unwrap();
+        graph.nodes.remove(&self.id);
+        graph.edges.remove(&self.id);
+    }
+}
+
+/// Records that the `Future` with id `child` was derived from the one with id `parent`.
+pub(crate) fn edge(parent: usize, child: usize) {
+    let mut graph = graph().lock().unwrap();
+    if graph.nodes.contains(&parent) {
+        graph.edges.entry(parent).or_insert_with(Vec::new).push(child);
+    }
+}
+
+fn graph() -> &'static Mutex<Graph> {
+    unsafe {
+        GRAPH_INIT.call_once(|| {
+            GRAPH = Box::into_raw(box Mutex::new(Graph { nodes: HashSet::new(), edges: HashMap::new() }));
+        });
+        &*GRAPH
+    }
+}
@@ -0,0 +1,157 @@
+//! Quiescence points for services built on this crate, e.g. to swap configuration only once every
+//! request that started under the old one has finished.
+
+use super::{new, value, Future, FutureSetter, Never};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Stamps `Future`s with the current epoch, and reports when every `Future` stamped before an
+/// epoch has settled. Clones share the same epochs.
+/// # Examples
+/// ```
+/// use future;
+/// use future::epoch::EpochGate;
+///
+/// let gate = EpochGate::new();
+/// let (request, setter) = future::new::<i64, ()>();
+/// let request = gate.stamp(request);
+///
+/// // Requests stamped from here on run under the new configuration.
+/// let drained = gate.advance();
+/// assert!(!drained.is_resolved());
+///
+/// setter.set_result(Ok(1): Result<i64, ()>);
+/// assert!(drained.is_resolved());
+/// assert_eq!(Ok(1), future::await(request));
+/// ```
+pub struct EpochGate {
+    state: Arc<Mutex<GateState>>
+}
+
+struct GateState {
+    epoch: u64,
+    /// The number of unsettled `Future`s stamped in each epoch, for epochs with any
+    in_flight: BTreeMap<u64, usize>,
+    /// Each waiter resolves once no `Future` stamped before its epoch is unsettled
+    waiters: Vec<(u64, FutureSetter<(), Never>)>
+}
+
+impl EpochGate {
+    /// Creates a gate at epoch 0.
+    pub fn new() -> EpochGate {
+        EpochGate {
+            state: Arc::new(Mutex::new(GateState { epoch: 0, in_flight: BTreeMap::new(), waiters: vec![] }))
+        }
+    }
+
+    /// The current epoch.
+    pub fn epoch(&self) -> u64 {
+        self.state.lock().unwrap().epoch
+    }
+
+    /// The number of stamped `Future`s that haven't settled, across every epoch.
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight.values().sum()
+    }
+
+    /// Stamps `future` with the current epoch, counting it as in flight until it resolves, or its
+    /// `FutureSetter` is dropped without setting a result. The result is passed through unchanged.
+    pub fn stamp<A: 'static, E: 'static>(&self, future: Future<A, E>) -> Future<A, E> {
+        let epoch = {
+            let mut state = self.state.lock().unwrap();
+            let epoch = state.epoch;
+            *state.in_flight.entry(epoch).or_insert(0) += 1;
+            epoch
+        };
+        // Dropped with the callback, whether or not it runs.
+        let stamp = Stamp { state: self.state.clone(), epoch: epoch };
+        future.on_completion(move |_| drop(stamp))
+    }
+
+    /// Moves on to the next epoch, returning a `Future` that resolves once every `Future` stamped
+    /// in a prior epoch has settled.
+    pub fn advance(&self) -> Future<(), Never> {
+        let mut state = self.state.lock().unwrap();
+        state.epoch += 1;
+        let epoch = state.epoch;
+        if state.drained(epoch) {
+            return value(());
+        }
+        let (drained, setter) = new();
+        state.waiters.push((epoch, setter));
+        drained
+    }
+}
+
+impl GateState {
+    /// Whether every `Future` stamped before `epoch` has settled.
+    fn drained(&self, epoch: u64) -> bool {
+        self.in_flight.keys().next().map_or(true, |&oldest| oldest >= epoch)
+    }
+}
+
+/// Counts a stamped `Future` as in flight until dropped.
+struct Stamp {
+    state: Arc<Mutex<GateState>>,
+    epoch: u64
+}
+
+impl Drop for Stamp {
+    fn drop(&mut self) {
+        let drained = {
+            let mut state = match self.state.lock() {
+                Ok(state) => state,
+                Err(_) => return
+            };
+            let settled = {
+                let count = state.in_flight.get_mut(&self.epoch).unwrap();
+                *count -= 1;
+                *count == 0
+            };
+            if !settled {
+                return;
+            }
+            state.in_flight.remove(&self.epoch);
+            let waiters = state.waiters.drain(..).collect::<Vec<_>>();
+            let (drained, waiting) = waiters.into_iter().partition::<Vec<_>, _>(|&(epoch, _)| state.drained(epoch));
+            state.waiters = waiting;
+            drained
+        };
+        for (_, setter) in drained {
+            setter.set_result::<Never>(Ok(()));
+        }
+    }
+}
+
+impl Clone for EpochGate {
+    fn clone(&self) -> EpochGate {
+        EpochGate { state: self.state.clone() }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::await;
+
+    #[test]
+    fn advance_waits_only_for_prior_epochs() {
+        let gate = EpochGate::new();
+        let (old, old_setter) = new::<i64, ()>();
+        let _old = gate.stamp(old);
+        let first = gate.advance();
+
+        let (current, current_setter) = new::<i64, ()>();
+        let _current = gate.stamp(current);
+        let second = gate.advance();
+        assert_eq!(gate.in_flight(), 2);
+
+        drop(old_setter);
+        assert!(first.is_resolved());
+        assert!(!second.is_resolved());
+
+        current_setter.set_result(Ok(1): Result<i64, ()>);
+        assert_eq!(await(second), Ok(()));
+        assert_eq!(gate.epoch(), 2);
+        assert_eq!(gate.in_flight(), 0);
+    }
+}
@@ -0,0 +1,79 @@
+use super::Future;
+use std::fmt::Debug;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::Duration;
+
+/// How many seconds `expect_ok` and `expect_err` wait before deciding a `Future` is stuck.
+const EXPECT_TIMEOUT_SECS: u64 = 10;
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Blocks until this `Future` resolves, returning its value. Meant for tests.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// assert_eq!(2, future::value::<i64, String>(1).map(|i| i + 1).expect_ok());
+    /// ```
+    /// # Panics
+    /// This will panic, with the `Debug` of the error and the label of the `Future` if it has one,
+    /// if the `Future` fails, if its `FutureSetter` is dropped without setting the result, or if
+    /// it hasn't resolved within 10 seconds.
+    pub fn expect_ok(self) -> A
+        where E: Debug
+    {
+        let name = self.name();
+        match self.await_for_test(&name) {
+            Ok(a) => a,
+            Err(e) => panic!("Expected {} to succeed, but it failed with {:?}", name, e)
+        }
+    }
+
+    /// Blocks until this `Future` resolves, returning its error. Meant for tests.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// assert_eq!("not found", future::err::<i64, &str>("not found").expect_err());
+    /// ```
+    /// # Panics
+    /// Like `expect_ok`, except this will panic with the `Debug` of the value if the `Future`
+    /// succeeds.
+    pub fn expect_err(self) -> E
+        where A: Debug
+    {
+        let name = self.name();
+        match self.await_for_test(&name) {
+            Ok(a) => panic!("Expected {} to fail, but it succeeded with {:?}", name, a),
+            Err(e) => e
+        }
+    }
+
+    fn name(&self) -> String {
+        self.lock.lock().unwrap().name()
+    }
+
+    fn await_for_test(self, name: &str) -> Result<A, E> {
+        let (tx, rx) = channel();
+        self.register(move |result| {
+            let _ = tx.send(result);
+        });
+        match rx.recv_timeout(Duration::from_secs(EXPECT_TIMEOUT_SECS)) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => panic!("Expected {} to resolve within {}s", name, EXPECT_TIMEOUT_SECS),
+            Err(RecvTimeoutError::Disconnected) => panic!("Expected {} to resolve, but its setter was dropped", name)
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::Builder;
+
+    #[test]
+    #[should_panic(expected = "Expected Future 'fetch_user' to succeed, but it failed with \"not found\"")]
+    fn expect_ok_names_the_future_and_its_error() {
+        let (f, setter) = Builder::new().label("fetch_user").build::<i64, &str>();
+        setter.set_result(Err("not found"): Result<i64, &str>);
+        f.expect_ok();
+    }
+}
@@ -0,0 +1,123 @@
+use super::{new, Future, FutureSetter};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Create a `Future` of `len` values, and a `FutureSequenceSetter` by which any number of
+/// producers set them by index, for scatter-gather where the shape of the result is known up
+/// front. The `Future` resolves once every slot is filled, or as soon as any producer fails.
+/// # Examples
+/// ```
+/// use future;
+/// use std::thread;
+///
+/// let (f, setter) = future::new_sequence::<u64, String>(3);
+/// for i in 0..3 {
+///     let setter = setter.clone();
+///     thread::spawn(move || setter.set_slot(i, i as u64 * 10));
+/// }
+/// assert_eq!(Ok(vec![0, 10, 20]), future::await(f));
+/// ```
+pub fn new_sequence<A, E>(len: usize) -> (Future<Vec<A>, E>, FutureSequenceSetter<A, E>)
+    where A: 'static, E: 'static
+{
+    let (future, setter) = new();
+    let state = SequenceState { slots: (0..len).map(|_| None).collect(), remaining: len, setter: Some(setter) };
+    let setter = FutureSequenceSetter { state: Arc::new(Mutex::new(state)) };
+    if len == 0 {
+        setter.finish(setter.state.lock().unwrap());
+    }
+    (future, setter)
+}
+
+/// Sets the slots of a `Future` created with `future::new_sequence`. Clones share the same slots,
+/// so that each producer can hold its own. If every clone is dropped before the `Future` resolves,
+/// it is left unresolved, as if its `FutureSetter` had been dropped.
+pub struct FutureSequenceSetter<A, E>
+    where A: 'static, E: 'static
+{
+    state: Arc<Mutex<SequenceState<A, E>>>
+}
+
+struct SequenceState<A, E>
+    where A: 'static, E: 'static
+{
+    slots: Vec<Option<A>>,
+    remaining: usize,
+    /// Taken once the `Future` resolves
+    setter: Option<FutureSetter<Vec<A>, E>>
+}
+
+impl<A: 'static, E: 'static> FutureSequenceSetter<A, E> {
+    /// Fills slot `i` with `a`, resolving the `Future` if it was the last one. Returns whether `a`
+    /// was used: false if the slot was already filled, or the `Future` already failed.
+    /// # Panics
+    /// This will panic if `i` is out of range.
+    pub fn set_slot(&self, i: usize, a: A) -> bool {
+        let mut state = self.state.lock().unwrap();
+        assert!(i < state.slots.len(), "Slot {} is out of range for a sequence of {}", i, state.slots.len());
+        if state.setter.is_none() || state.slots[i].is_some() {
+            return false;
+        }
+        state.slots[i] = Some(a);
+        state.remaining -= 1;
+        if state.remaining == 0 {
+            self.finish(state);
+        }
+        true
+    }
+
+    /// Fails the `Future` with `e`, unless it has already resolved. Returns whether `e` was used.
+    pub fn fail<E2: Into<E>>(&self, e: E2) -> bool {
+        let setter = self.state.lock().unwrap().setter.take();
+        match setter {
+            Some(setter) => {
+                setter.set_result(Err(e): Result<Vec<A>, E2>);
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Like `set_slot` for a success, and `fail` for an error.
+    pub fn set_slot_result<E2: Into<E>>(&self, i: usize, result: Result<A, E2>) -> bool {
+        match result {
+            Ok(a) => self.set_slot(i, a),
+            Err(e) => self.fail(e)
+        }
+    }
+
+    /// The number of slots not yet filled.
+    pub fn remaining(&self) -> usize {
+        self.state.lock().unwrap().remaining
+    }
+
+    fn finish(&self, mut state: MutexGuard<SequenceState<A, E>>) {
+        let values = state.slots.drain(..).map(Option::unwrap).collect::<Vec<_>>();
+        let setter = state.setter.take().unwrap();
+        drop(state);
+        setter.set_result(Ok(values): Result<Vec<A>, E>);
+    }
+}
+
+impl<A: 'static, E: 'static> Clone for FutureSequenceSetter<A, E> {
+    fn clone(&self) -> FutureSequenceSetter<A, E> {
+        FutureSequenceSetter { state: self.state.clone() }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::await;
+
+    #[test]
+    fn sequences_fail_on_the_first_error() {
+        let (f, setter) = new_sequence::<i64, String>(3);
+        assert!(setter.set_slot(2, 20));
+        assert!(!setter.set_slot(2, 21));
+        assert!(setter.fail(String::from("producer 1 failed")));
+        assert!(!setter.set_slot(0, 0));
+        assert_eq!(await(f), Err(String::from("producer 1 failed")));
+
+        let (f, _setter) = new_sequence::<i64, String>(0);
+        assert_eq!(await(f), Ok(vec![]));
+    }
+}
@@ -0,0 +1,173 @@
+//! A small adapter turning a blocking HTTP client into `Future`s, with optional timeouts, retries
+//! and circuit breaking. Enabled with the `http` feature.
+//!
+//! This crate doesn't depend on any particular HTTP client: requests are made by a user-supplied
+//! closure, which is run on a dedicated `ThreadPool`.
+
+use super::{retry, CircuitBreaker, CircuitOpen, Future, RetryPolicy, ThreadPool, TimeoutError};
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Makes requests with a blocking client closure, returning `Future`s of the responses.
+///
+/// # Examples
+///
+/// ```
+/// use future;
+/// use future::http::HttpClient;
+/// use future::{CircuitBreaker, RetryPolicy};
+/// use std::time::Duration;
+///
+/// // Stands in for a call to a real blocking HTTP client
+/// fn get(url: String) -> Result<String, String> {
+///     Ok(format!("<html>{}</html>", url))
+/// }
+///
+/// let client = HttpClient::new(4, get)
+///     .timeout(Duration::from_secs(5))
+///     .retry(RetryPolicy::new(3))
+///     .circuit_breaker(CircuitBreaker::new(5, Duration::from_secs(30)));
+///
+/// let body = client.call(String::from("http://example.com"));
+/// assert_eq!("<html>http://example.com</html>", future::await(body).unwrap());
+/// ```
+pub struct HttpClient<Req, Resp, E> {
+    pool: ThreadPool,
+    send: Arc<Fn(Req) -> Result<Resp, E> + Send + Sync>,
+    timeout: Option<Duration>,
+    retry: Option<RetryPolicy<HttpError<E>>>,
+    breaker: Option<CircuitBreaker>
+}
+
+impl<Req, Resp, E> HttpClient<Req, Resp, E>
+    where Req: Clone + Send + 'static,
+          Resp: Send + 'static,
+          E: Send + 'static
+{
+    /// Creates a client making requests with `send` on a pool of `threads` threads.
+    pub fn new<F>(threads: usize, send: F) -> HttpClient<Req, Resp, E>
+        where F: Fn(Req) -> Result<Resp, E> + Send + Sync + 'static
+    {
+        HttpClient {
+            pool: ThreadPool::new(threads),
+            send: Arc::new(send),
+            timeout: None,
+            retry: None,
+            breaker: None
+        }
+    }
+
+    /// Fail each attempt that takes longer than `timeout`. The pool thread making a timed out
+    /// request stays busy until the client closure returns.
+    pub fn timeout(mut self, timeout: Duration) -> HttpClient<Req, Resp, E> {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retry failed attempts, including timed out ones, according to `policy`.
+    pub fn retry(mut self, policy: RetryPolicy<HttpError<E>>) -> HttpClient<Req, Resp, E> {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Guard calls with `breaker`. A call counts as a single success or failure, regardless of how
+    /// many attempts were retried.
+    pub fn circuit_breaker(mut self, breaker: CircuitBreaker) -> HttpClient<Req, Resp, E> {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    /// Makes the request `req`, returning a `Future` of the response.
+    pub fn call(&self, req: Req) -> Future<Resp, HttpError<E>> {
+        let pool = self.pool.clone();
+        let send = self.send.clone();
+        let timeout = self.timeout;
+        let attempt = move || {
+            let send = send.clone();
+            let req = req.clone();
+            let response = pool.run(move || send(req).map_err(HttpError::Client));
+            match timeout {
+                Some(timeout) => response.within(timeout),
+                None => response
+            }
+        };
+
+        let policy = self.retry.clone();
+        let call = move || match policy {
+            Some(policy) => retry(policy, attempt),
+            None => attempt()
+        };
+
+        match self.breaker {
+            Some(ref breaker) => breaker.call(call),
+            None => call()
+        }
+    }
+}
+
+/// The ways a request made through an `HttpClient` can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpError<E> {
+    /// The client closure returned an error
+    Client(E),
+    /// The request did not complete within the client's timeout
+    Timeout(TimeoutError),
+    /// The client's circuit breaker is open
+    CircuitOpen(CircuitOpen)
+}
+
+impl<E> From<TimeoutError> for HttpError<E> {
+    fn from(err: TimeoutError) -> Self {
+        HttpError::Timeout(err)
+    }
+}
+
+impl<E> From<CircuitOpen> for HttpError<E> {
+    fn from(err: CircuitOpen) -> Self {
+        HttpError::CircuitOpen(err)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for HttpError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HttpError::Client(ref e) => write!(f, "Client error: {}", e),
+            HttpError::Timeout(ref e) => e.fmt(f),
+            HttpError::CircuitOpen(ref e) => e.fmt(f)
+        }
+    }
+}
+
+impl<E: Error> Error for HttpError<E> {
+    fn description(&self) -> &str {
+        match *self {
+            HttpError::Client(ref e) => e.description(),
+            HttpError::Timeout(ref e) => e.description(),
+            HttpError::CircuitOpen(ref e) => e.description()
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::await;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    #[test]
+    fn timed_out_attempts_are_retried() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls2 = calls.clone();
+        let client = HttpClient::new(2, move |req: i64| {
+            if calls2.fetch_add(1, Ordering::SeqCst) == 0 {
+                thread::sleep(Duration::from_millis(100));
+            }
+            Ok(req): Result<i64, ()>
+        }).timeout(Duration::from_millis(20)).retry(RetryPolicy::new(2));
+
+        assert_eq!(await(client.call(7)), Ok(7));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
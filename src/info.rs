@@ -0,0 +1,73 @@
+use super::{Future, FutureSetter, Meta, Priority};
+use std::time::Duration;
+
+/// A snapshot of the state of a `Future`, for dashboards and debugging of services with many
+/// `Future`s in flight. Returned by `Future::info` and `FutureSetter::info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FutureInfo {
+    /// The number of combinators between this `Future` and the root `Future` it was derived from,
+    /// which is 0 for a `Future` created by `future::new`
+    pub chain_depth: usize,
+    /// Whether a transformation, side-effect or consumer has been registered on the `Future`
+    pub callback_registered: bool,
+    pub resolved: bool,
+    /// The time since the `Future` was created
+    pub age: Duration,
+    pub priority: Option<Priority>,
+    /// The label given to the chain, e.g. by `Builder::label`
+    pub label: Option<String>,
+    /// The label, suffixed with the number of combinators since it was given, as described by
+    /// `Future::with_label`
+    pub stage: Option<String>
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Describes the current state of this `Future`.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let (f, _setter) = future::new::<i64, ()>();
+    /// let f = f.map(|i| i + 1).map(|i| i * 2);
+    /// let info = f.info();
+    /// assert_eq!(2, info.chain_depth);
+    /// assert!(!info.resolved);
+    /// ```
+    pub fn info(&self) -> FutureInfo {
+        let meta = self.lock.lock().unwrap();
+        info(&meta, self.result.borrow().is_some())
+    }
+}
+
+impl<A: 'static, E: 'static> FutureSetter<A, E> {
+    /// Describes the current state of the associated `Future`.
+    pub fn info(&self) -> FutureInfo {
+        info(&self.lock.lock().unwrap(), false)
+    }
+}
+
+fn info(meta: &Meta, resolved: bool) -> FutureInfo {
+    FutureInfo {
+        chain_depth: meta.depth,
+        callback_registered: meta.callback_registered,
+        resolved: resolved,
+        age: meta.created.elapsed(),
+        priority: meta.priority,
+        label: meta.label.as_ref().map(|label| (**label).clone()),
+        stage: meta.stage()
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::new;
+
+    #[test]
+    fn setter_info_reports_registered_callbacks() {
+        let (f, setter) = new::<i64, ()>();
+        assert!(!setter.info().callback_registered);
+        f.resolve(|_| {});
+        assert!(setter.info().callback_registered);
+        assert_eq!(setter.info().chain_depth, 0);
+    }
+}
@@ -0,0 +1,80 @@
+use super::sample;
+use std::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT};
+use std::time::Duration;
+
+/// How to randomize a delay, so that many clients waiting on the same schedule, such as retries
+/// after a shared dependency fails, spread out rather than all acting at once. Used by
+/// `RetryPolicy::jitter` and `interval_jittered`.
+/// # Examples
+/// ```
+/// use future::Jitter;
+/// use std::time::Duration;
+///
+/// let delay = Jitter::Equal.apply(Duration::from_millis(100), None);
+/// assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Jitter {
+    /// Use the delay as is
+    None,
+    /// Wait a random time between zero and the delay
+    Full,
+    /// Wait half the delay, plus a random time up to the other half
+    Equal,
+    /// Wait a random time between `base` and three times the previous wait, capped at the delay
+    Decorrelated { base: Duration }
+}
+
+impl Jitter {
+    /// Randomizes `delay`, given the randomized wait before this one, if any.
+    pub fn apply(&self, delay: Duration, previous: Option<Duration>) -> Duration {
+        match *self {
+            Jitter::None => delay,
+            Jitter::Full => between(Duration::from_millis(0), delay),
+            Jitter::Equal => {
+                let half = delay / 2;
+                half + between(Duration::from_millis(0), delay - half)
+            },
+            Jitter::Decorrelated { base } => {
+                let upper = previous.map_or(delay, |previous| previous * 3);
+                let wait = between(base, if upper > base { upper } else { base });
+                if wait < delay { wait } else { delay }
+            }
+        }
+    }
+}
+
+/// A random duration between `low` and `high`.
+fn between(low: Duration, high: Duration) -> Duration {
+    let range = nanos(high - low) as f64;
+    low + from_nanos((range * sample::random(&STATE)) as u64)
+}
+
+/// The state of the generator shared by every jittered delay.
+static STATE: AtomicUsize = ATOMIC_USIZE_INIT;
+
+fn nanos(d: Duration) -> u64 {
+    d.as_secs() * 1_000_000_000 + d.subsec_nanos() as u64
+}
+
+fn from_nanos(nanos: u64) -> Duration {
+    Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+}
+
+mod test {
+    use super::*;
+
+    #[test]
+    fn jittered_delays_stay_in_range() {
+        let delay = Duration::from_millis(100);
+        for _ in 0..1000 {
+            assert!(Jitter::Full.apply(delay, None) <= delay);
+            assert!(Jitter::Equal.apply(delay, None) >= delay / 2);
+
+            let base = Duration::from_millis(10);
+            let wait = Jitter::Decorrelated { base: base }.apply(delay, Some(Duration::from_millis(20)));
+            assert!(wait >= base && wait <= Duration::from_millis(60), "{:?}", wait);
+        }
+        assert_eq!(Jitter::None.apply(delay, None), delay);
+    }
+}
@@ -1,5 +1,6 @@
-use super::Future;
+use super::{abandon, new, priority, value, Future, FutureSetter, Meta, Never};
 use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
 
 pub fn join2<A, B, ERR>(
     fa: Future<A, ERR>,
@@ -9,9 +10,10 @@ pub fn join2<A, B, ERR>(
           B: Debug + 'static,
           ERR: Debug + 'static
 {
-    fa.and_thenf(|a| {
+    let sources = [fb.lock.clone()];
+    inherit(fa.and_thenf(|a| {
         fb.map(|b| (a, b))
-    })
+    }), &sources)
 }
 
 pub fn join3<A, B, C, ERR>(
@@ -24,11 +26,12 @@ pub fn join3<A, B, C, ERR>(
           C: Debug + 'static,
           ERR: Debug + 'static
 {
-    fa.and_thenf(|a| {
+    let sources = [fb.lock.clone(), fc.lock.clone()];
+    inherit(fa.and_thenf(|a| {
         fb.and_thenf(|b| {
             fc.map(|c| (a,b,c))
         })
-    })
+    }), &sources)
 }
 
 pub fn join4<A, B, C, D, ERR>(
@@ -43,13 +46,14 @@ pub fn join4<A, B, C, D, ERR>(
           D: Debug + 'static,
           ERR: Debug + 'static
 {
-    fa.and_thenf(|a| {
+    let sources = [fb.lock.clone(), fc.lock.clone(), fd.lock.clone()];
+    inherit(fa.and_thenf(|a| {
         fb.and_thenf(|b| {
             fc.and_thenf(|c| {
                 fd.map(|d| (a, b, c, d))
             })
         })
-    })
+    }), &sources)
 }
 
 pub fn join5<A, B, C, D, E, ERR>(
@@ -66,7 +70,8 @@ pub fn join5<A, B, C, D, E, ERR>(
           E: Debug + 'static,
           ERR: Debug + 'static
 {
-    fa.and_thenf(|a| {
+    let sources = [fb.lock.clone(), fc.lock.clone(), fd.lock.clone(), fe.lock.clone()];
+    inherit(fa.and_thenf(|a| {
         fb.and_thenf(|b| {
             fc.and_thenf(|c| {
                 fd.and_thenf(|d| {
@@ -74,7 +79,7 @@ pub fn join5<A, B, C, D, E, ERR>(
                 })
             })
         })
-    })
+    }), &sources)
 }
 
 pub fn join6<A, B, C, D, E, F, ERR>(
@@ -93,7 +98,8 @@ pub fn join6<A, B, C, D, E, F, ERR>(
           F: Debug + 'static,
           ERR: Debug + 'static
 {
-    fa.and_thenf(|a| {
+    let sources = [fb.lock.clone(), fc.lock.clone(), fd.lock.clone(), fe.lock.clone(), ff.lock.clone()];
+    inherit(fa.and_thenf(|a| {
         fb.and_thenf(|b| {
             fc.and_thenf(|c| {
                 fd.and_thenf(|d| {
@@ -103,7 +109,7 @@ pub fn join6<A, B, C, D, E, F, ERR>(
                 })
             })
         })
-    })
+    }), &sources)
 }
 
 pub fn join7<A, B, C, D, E, F, G, ERR>(
@@ -124,7 +130,8 @@ pub fn join7<A, B, C, D, E, F, G, ERR>(
           G: Debug + 'static,
           ERR: Debug + 'static
 {
-    fa.and_thenf(|a| {
+    let sources = [fb.lock.clone(), fc.lock.clone(), fd.lock.clone(), fe.lock.clone(), ff.lock.clone(), fg.lock.clone()];
+    inherit(fa.and_thenf(|a| {
         fb.and_thenf(|b| {
             fc.and_thenf(|c| {
                 fd.and_thenf(|d| {
@@ -136,7 +143,7 @@ pub fn join7<A, B, C, D, E, F, G, ERR>(
                 })
             })
         })
-    })
+    }), &sources)
 }
 
 pub fn join8<A, B, C, D, E, F, G, H, ERR>(
@@ -159,7 +166,8 @@ pub fn join8<A, B, C, D, E, F, G, H, ERR>(
           H: Debug + 'static,
           ERR: Debug + 'static
 {
-    fa.and_thenf(|a| {
+    let sources = [fb.lock.clone(), fc.lock.clone(), fd.lock.clone(), fe.lock.clone(), ff.lock.clone(), fg.lock.clone(), fh.lock.clone()];
+    inherit(fa.and_thenf(|a| {
         fb.and_thenf(|b| {
             fc.and_thenf(|c| {
                 fd.and_thenf(|d| {
@@ -173,7 +181,7 @@ pub fn join8<A, B, C, D, E, F, G, H, ERR>(
                 })
             })
         })
-    })
+    }), &sources)
 }
 
 pub fn join9<A, B, C, D, E, F, G, H, I, ERR>(
@@ -198,7 +206,8 @@ pub fn join9<A, B, C, D, E, F, G, H, I, ERR>(
           I: Debug + 'static,
           ERR: Debug + 'static
 {
-    fa.and_thenf(|a| {
+    let sources = [fb.lock.clone(), fc.lock.clone(), fd.lock.clone(), fe.lock.clone(), ff.lock.clone(), fg.lock.clone(), fh.lock.clone(), fi.lock.clone()];
+    inherit(fa.and_thenf(|a| {
         fb.and_thenf(|b| {
             fc.and_thenf(|c| {
                 fd.and_thenf(|d| {
@@ -214,7 +223,7 @@ pub fn join9<A, B, C, D, E, F, G, H, I, ERR>(
                 })
             })
         })
-    })
+    }), &sources)
 }
 
 pub fn join10<A, B, C, D, E, F, G, H, I, J, ERR>(
@@ -241,7 +250,8 @@ pub fn join10<A, B, C, D, E, F, G, H, I, J, ERR>(
           J: Debug + 'static,
           ERR: Debug + 'static
 {
-    fa.and_thenf(|a| {
+    let sources = [fb.lock.clone(), fc.lock.clone(), fd.lock.clone(), fe.lock.clone(), ff.lock.clone(), fg.lock.clone(), fh.lock.clone(), fi.lock.clone(), fj.lock.clone()];
+    inherit(fa.and_thenf(|a| {
         fb.and_thenf(|b| {
             fc.and_thenf(|c| {
                 fd.and_thenf(|d| {
@@ -259,7 +269,7 @@ pub fn join10<A, B, C, D, E, F, G, H, I, J, ERR>(
                 })
             })
         })
-    })
+    }), &sources)
 }
 
 pub fn join11<A, B, C, D, E, F, G, H, I, J, K, ERR>(
@@ -288,7 +298,8 @@ pub fn join11<A, B, C, D, E, F, G, H, I, J, K, ERR>(
           K: Debug + 'static,
           ERR: Debug + 'static
 {
-    fa.and_thenf(|a| {
+    let sources = [fb.lock.clone(), fc.lock.clone(), fd.lock.clone(), fe.lock.clone(), ff.lock.clone(), fg.lock.clone(), fh.lock.clone(), fi.lock.clone(), fj.lock.clone(), fk.lock.clone()];
+    inherit(fa.and_thenf(|a| {
         fb.and_thenf(|b| {
             fc.and_thenf(|c| {
                 fd.and_thenf(|d| {
@@ -308,7 +319,7 @@ pub fn join11<A, B, C, D, E, F, G, H, I, J, K, ERR>(
                 })
             })
         })
-    })
+    }), &sources)
 }
 
 pub fn join12<A, B, C, D, E, F, G, H, I, J, K, L, ERR>(
@@ -339,7 +350,8 @@ pub fn join12<A, B, C, D, E, F, G, H, I, J, K, L, ERR>(
           L: Debug + 'static,
           ERR: Debug + 'static
 {
-    fa.and_thenf(|a| {
+    let sources = [fb.lock.clone(), fc.lock.clone(), fd.lock.clone(), fe.lock.clone(), ff.lock.clone(), fg.lock.clone(), fh.lock.clone(), fi.lock.clone(), fj.lock.clone(), fk.lock.clone(), fl.lock.clone()];
+    inherit(fa.and_thenf(|a| {
         fb.and_thenf(|b| {
             fc.and_thenf(|c| {
                 fd.and_thenf(|d| {
@@ -361,5 +373,190 @@ pub fn join12<A, B, C, D, E, F, G, H, I, J, K, L, ERR>(
                 })
             })
         })
+    }), &sources)
+}
+
+/// Joins `futures` into a `Future` of all of their values, in order, failing as soon as any of
+/// them fails rather than once the earlier ones resolve. The inputs still in flight are then
+/// abandoned, firing the cancellation of those started with `future::run_cancellable`, so doomed
+/// work can stop early.
+/// # Examples
+/// ```
+/// use future;
+/// use std::sync::mpsc::channel;
+///
+/// let (tx, rx) = channel();
+/// let slow = future::run_cancellable(move |token| {
+///     while !token.is_cancelled() {
+///         // some work here
+///     }
+///     tx.send("stopped").unwrap();
+///     Ok(1): Result<i64, String>
+/// });
+/// let failed = future::err::<i64, String>(String::from("bad input"));
+///
+/// assert_eq!(Err(String::from("bad input")), future::await(future::try_join(vec![slow, failed])));
+/// assert_eq!("stopped", rx.recv().unwrap());
+/// ```
+pub fn try_join<A, E>(futures: Vec<Future<A, E>>) -> Future<Vec<A>, E>
+    where A: 'static, E: 'static
+{
+    if futures.is_empty() {
+        return value(vec![]);
+    }
+    let (joined, setter) = new();
+    let sources = futures.iter().map(|f| f.lock.clone()).collect::<Vec<_>>();
+    let state = Arc::new(Mutex::new(TryJoin {
+        values: futures.iter().map(|_| None).collect(),
+        remaining: futures.len(),
+        setter: Some(setter)
+    }));
+    let joined = inherit(joined, &sources);
+
+    for (i, future) in futures.into_iter().enumerate() {
+        let state = state.clone();
+        let sources = sources.clone();
+        future.register(move |result| {
+            let mut join = state.lock().unwrap();
+            match result {
+                Ok(a) => {
+                    join.values[i] = Some(a);
+                    join.remaining -= 1;
+                    if join.remaining == 0 {
+                        if let Some(setter) = join.setter.take() {
+                            let values = join.values.drain(..).map(Option::unwrap).collect();
+                            drop(join);
+                            setter.set_result(Ok(values): Result<Vec<A>, E>);
+                        }
+                    }
+                },
+                Err(e) => {
+                    let setter = match join.setter.take() {
+                        Some(setter) => setter,
+                        None => return
+                    };
+                    let in_flight = (0..sources.len())
+                        .filter(|&j| j != i && join.values[j].is_none())
+                        .collect::<Vec<_>>();
+                    drop(join);
+                    setter.set_result(Err(e));
+                    for j in in_flight {
+                        abandon(&sources[j]);
+                    }
+                }
+            }
+        });
+    }
+    joined
+}
+
+/// Joins two equal-length collections of `Future`s element-wise, into a `Future` of pairs of their
+/// values, e.g. to recombine the results of two parallel bulk lookups. Like `try_join`, it fails
+/// as soon as any of them fails.
+/// # Panics
+/// This will panic if `left` and `right` differ in length.
+/// # Examples
+/// ```
+/// use future;
+///
+/// let names = vec![future::value("ada"), future::value("grace")];
+/// let ages = vec![future::value(36), future::value(85)];
+/// let joined = future::join_pairs::<_, _, ()>(names, ages);
+/// assert_eq!(Ok(vec![("ada", 36), ("grace", 85)]), future::await(joined));
+/// ```
+pub fn join_pairs<A, B, E>(left: Vec<Future<A, E>>, right: Vec<Future<B, E>>) -> Future<Vec<(A, B)>, E>
+    where A: 'static, B: 'static, E: 'static
+{
+    assert!(left.len() == right.len(), "Can't pair {} Futures with {}", left.len(), right.len());
+    let n = left.len();
+    let sides = left.into_iter().map(|f| f.map(Side::Left))
+        .chain(right.into_iter().map(|f| f.map(Side::Right)))
+        .collect();
+    try_join(sides).map(move |mut sides| {
+        let right = sides.split_off(n);
+        sides.into_iter().zip(right).map(|pair| match pair {
+            (Side::Left(a), Side::Right(b)) => (a, b),
+            _ => unreachable!()
+        }).collect()
     })
 }
+
+enum Side<A, B> {
+    Left(A),
+    Right(B)
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Joins this `Future` with `other`, waiting for both to resolve and keeping both results
+    /// whatever they are, unlike `join2`, which loses the outcome of `other` if this fails. For
+    /// paired operations where both sides must be settled, such as releasing two resources.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let unlock_a = future::err::<(), String>(String::from("lease expired"));
+    /// let unlock_b = future::value::<(), String>(());
+    ///
+    /// let (a, b) = future::await(unlock_a.join_with_errors(unlock_b)).unwrap();
+    /// assert_eq!(Err(String::from("lease expired")), a);
+    /// assert_eq!(Ok(()), b);
+    /// ```
+    pub fn join_with_errors<B, E2>(self, other: Future<B, E2>) -> Future<(Result<A, E>, Result<B, E2>), Never>
+        where B: 'static, E2: 'static
+    {
+        let sources = [other.lock.clone()];
+        inherit(self.transformf(|a| other.transform(|b| Ok((a, b)))), &sources)
+    }
+
+    /// Holds back the result of this `Future` until `other` has resolved too, whatever its
+    /// result, for ordering effects without joining their values.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let (flushed, flush_setter) = future::new::<(), String>();
+    /// let metrics = future::value::<u64, ()>(42).after(flushed);
+    /// assert!(!metrics.is_resolved());
+    ///
+    /// flush_setter.set_result(Err(String::from("disk full")): Result<(), String>);
+    /// assert_eq!(Ok(42), future::await(metrics));
+    /// ```
+    pub fn after<B, E2>(self, other: Future<B, E2>) -> Future<A, E>
+        where B: 'static, E2: 'static
+    {
+        let sources = [other.lock.clone()];
+        inherit(self.transformf(|result| other.transform(|_| result)), &sources)
+    }
+
+    /// Resolves with this `Future`'s value if it succeeds, and otherwise with the result of
+    /// `fallback`. Unlike `rescuef`, the fallback is already under way rather than started after
+    /// this fails, so a failure doesn't add the fallback's latency. If this succeeds, `fallback` is
+    /// abandoned, firing its cancellation if it was started with `future::run_cancellable`.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let primary = future::err::<&str, &str>("primary unavailable");
+    /// let replica = future::value::<&str, &str>("replica");
+    /// assert_eq!(Ok("replica"), future::await(primary.or(replica)));
+    /// ```
+    pub fn or(self, fallback: Future<A, E>) -> Future<A, E> {
+        let sources = [fallback.lock.clone()];
+        inherit(self.rescuef(|_| fallback), &sources)
+    }
+}
+
+struct TryJoin<A: 'static, E: 'static> {
+    values: Vec<Option<A>>,
+    remaining: usize,
+    setter: Option<FutureSetter<Vec<A>, E>>
+}
+
+/// Links a joined `Future` to every joined input up front, rather than as each earlier input
+/// resolves, so that its priority reaches inputs that are still queued.
+fn inherit<A: 'static, E: 'static>(joined: Future<A, E>, sources: &[Arc<Mutex<Meta>>]) -> Future<A, E> {
+    for source in sources {
+        priority::link(&joined.lock, source);
+    }
+    joined
+}
@@ -4,18 +4,131 @@
 #![feature(fnbox)]
 #![feature(type_ascription)]
 
+pub mod config;
+#[cfg(feature = "graph")]
+pub mod debug;
+pub mod epoch;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod log;
+pub mod process;
+pub mod raw;
+#[cfg(feature = "registry")]
+pub mod registry;
+#[cfg(all(unix, feature = "signal"))]
+pub mod signal;
+pub mod sync;
+pub mod time;
+pub mod timer;
+pub mod trace;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+mod adaptive;
+mod balancer;
+mod breaker;
+mod budget;
+mod builder;
+mod cancel;
+mod checkpoint;
+mod classify;
+mod coalesce;
+mod compat;
+mod completion;
+mod consume;
+mod context;
+mod deadline;
+mod expect;
+mod gather;
+mod info;
+mod jitter;
 mod join;
+mod limiter;
+mod memo;
+mod panic;
+mod periodic;
+mod pipeline;
+mod pool;
+mod priority;
+mod progress;
+mod race;
+mod ready;
+mod recipe;
+#[cfg(feature = "recycle")]
+mod recycle;
+mod retry;
+mod saga;
+mod sample;
+mod scope;
+mod sequencer;
+mod service;
+mod settle;
+mod shard;
+mod stream;
+mod taskset;
+mod throttle;
+mod timeout;
+mod traverse;
+mod validate;
+mod wait;
 
+pub use adaptive::*;
+pub use balancer::*;
+pub use breaker::*;
+pub use budget::*;
+pub use builder::*;
+pub use cancel::*;
+pub use checkpoint::*;
+pub use classify::*;
+pub use coalesce::*;
+pub use compat::*;
+pub use completion::*;
+pub use consume::*;
+pub use context::*;
+pub use deadline::*;
+pub use expect::*;
+pub use gather::*;
+pub use info::*;
+pub use jitter::*;
 pub use join::*;
+pub use limiter::*;
+pub use memo::*;
+pub use panic::*;
+pub use periodic::*;
+pub use pipeline::*;
+pub use pool::*;
+pub use priority::*;
+pub use progress::*;
+pub use race::*;
+pub use ready::*;
+pub use recipe::*;
+#[cfg(feature = "recycle")]
+pub use recycle::*;
+pub use retry::*;
+pub use saga::*;
+pub use sample::*;
+pub use scope::*;
+pub use sequencer::*;
+pub use service::*;
+pub use settle::*;
+pub use shard::*;
+pub use stream::*;
+pub use taskset::*;
+pub use throttle::*;
+pub use timeout::*;
+pub use traverse::*;
+pub use validate::*;
+pub use wait::*;
 
 use std::boxed::FnBox;
 use std::cell::RefCell;
 use std::error::Error;
 use std::fmt;
 use std::iter::FromIterator;
-use std::sync::mpsc::channel;
-use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, TryRecvError};
+use std::sync::{Arc, Mutex, Weak};
 use std::thread;
+use std::time::{Duration, Instant};
 
 /// A handle on the result of an asynchronous compution that allows for transformations and
 /// side effects.
@@ -29,6 +142,13 @@ use std::thread;
 /// Additionally, side-effects that don't consume the `Future` can be added via `on_completion`,
 /// `on_success`, and `on_err`.
 ///
+/// Callbacks run on the thread that sets the result, or on the thread adding them if it's already
+/// set, and never while the internal lock is held, so they may use the same chain of `Future`s.
+///
+/// `Future` is `#[must_use]`, so discarding one without consuming it is an `unused_must_use`
+/// warning, in this crate and in every crate using it. Bind a `Future` that is deliberately left
+/// unconsumed with `let _f = ...`.
+///
 /// # Examples
 ///
 /// ```
@@ -48,21 +168,155 @@ use std::thread;
 ///     .on_err(|err| println!("Got an err: {:?}", err))
 ///     .resolve(|answer| println!("Answer: {:?}", answer));
 /// ```
+#[must_use = "a Future does nothing with its result unless resolved, awaited or swallowed"]
 pub struct Future<A, E>
     where 'static, E: 'static
 {
-    lock: Arc<Mutex<()>>,
-    callback: Arc<RefCell<Option<Box<FnBox(Result<A, E>) -> ()>>>>,
-    result: Arc<RefCell<Option<Box<Result<A, E>>>>>
+    lock: Arc<Mutex<Meta>>,
+    callback: Arc<RefCell<Option<Callback<A, E>>>>,
+    result: Arc<RefCell<Option<Result<A, E>>>>,
+    #[cfg(feature = "graph")]
+    node: usize
 }
 
 /// The mechanism by which the result of a `Future` is resolved.
 pub struct FutureSetter<A, E>
     where A: 'static, E: 'static
 {
-    lock: Arc<Mutex<()>>,
-    callback: Arc<RefCell<Option<Box<FnBox(Result<A, E>) -> ()>>>>,
-    result: Arc<RefCell<Option<Box<Result<A, E>>>>>
+    lock: Arc<Mutex<Meta>>,
+    callback: Arc<RefCell<Option<Callback<A, E>>>>,
+    result: Arc<RefCell<Option<Result<A, E>>>>,
+    drop_guard: DropGuard,
+    #[cfg(feature = "graph")]
+    node: debug::NodeHandle,
+    #[cfg(feature = "registry")]
+    entry: registry::Entry
+}
+
+/// What is run with the result of a `Future` once it's set.
+enum Callback<A: 'static, E: 'static> {
+    Fn(Box<FnBox(Result<A, E>) -> ()>),
+    /// Set the result of another `Future`, without an intermediate closure
+    Forward(FutureSetter<A, E>)
+}
+
+impl<A: 'static, E: 'static> Callback<A, E> {
+    fn call(self, result: Result<A, E>) {
+        match self {
+            Callback::Fn(f) => f(result),
+            Callback::Forward(setter) => setter.set_result(result)
+        }
+    }
+}
+
+/// State shared by a `Future` and its `FutureSetter`, guarded by the same lock as the result.
+struct Meta {
+    callback_registered: bool,
+    drop_policy: Option<config::DropPolicy>,
+    priority: Option<Priority>,
+    /// The `Future`s this one was derived from, which inherit its priority and abandonment
+    upstream: Vec<Arc<Mutex<Meta>>>,
+    /// Whether the `Future` was consumed by registering a callback
+    consumed: bool,
+    /// Whether the result has been set, whether or not it has been taken by a callback since
+    resolved: bool,
+    /// Whether the `FutureSetter` was dropped without setting the result
+    setter_dropped: bool,
+    /// Run if the `Future` is dropped without being consumed or resolved
+    abandoned: Vec<Box<FnBox() -> () + Send>>,
+    /// Whether the `Future` has been abandoned, so that later abandonment callbacks run at once
+    consumer_dropped: bool,
+    created: Instant,
+    /// The number of combinators between this `Future` and the root it was derived from
+    depth: usize,
+    /// The checkpoints of the chain, shared with every `Future` derived from this one
+    trail: Option<Arc<Mutex<checkpoint::Trail>>>,
+    /// A name for the chain, inherited by every `Future` derived from this one
+    label: Option<Arc<String>>,
+    /// The `depth` of the `Future` the label was given to
+    label_depth: usize,
+    /// The deadline of the work this `Future` is part of, inherited by every `Future` derived
+    /// from this one
+    deadline: Option<Instant>,
+    /// The `ThreadPool` job producing this `Future`'s result, while it's queued, so that raising
+    /// the priority of this `Future` reschedules it
+    pool_job: Option<pool::QueuedJob>
+}
+
+impl Meta {
+    fn new() -> Meta {
+        Meta {
+            callback_registered: false,
+            drop_policy: None,
+            priority: None,
+            upstream: vec![],
+            consumed: false,
+            resolved: false,
+            setter_dropped: false,
+            abandoned: vec![],
+            consumer_dropped: false,
+            created: Instant::now(),
+            depth: 0,
+            trail: None,
+            label: None,
+            label_depth: 0,
+            deadline: deadline::current_deadline(),
+            pool_job: None
+        }
+    }
+
+    /// The label of the chain, suffixed with the number of combinators since the label was given,
+    /// e.g. "fetch_user+2" for the result of two `map`s on a `Future` labelled "fetch_user".
+    fn stage(&self) -> Option<String> {
+        self.label.as_ref().map(|label| match self.depth.saturating_sub(self.label_depth) {
+            0 => (**label).clone(),
+            stage => format!("{}+{}", label, stage)
+        })
+    }
+
+    /// Describes the `Future` in messages, by its stage if it has a label.
+    fn name(&self) -> String {
+        match self.stage() {
+            Some(stage) => format!("Future '{}'", stage),
+            None => String::from("the Future")
+        }
+    }
+}
+
+/// Reports a `FutureSetter` dropped without setting a result according to the drop policy.
+/// Owned by the `FutureSetter`, and disarmed by `set_result`.
+struct DropGuard {
+    lock: Arc<Mutex<Meta>>,
+    armed: bool,
+    derived: bool
+}
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        if self.armed {
+            // As for `Future`, a poisoned lock means this is being dropped by an unwinding panic.
+            let mut meta = match self.lock.lock() {
+                Ok(meta) => meta,
+                Err(_) => return
+            };
+            meta.setter_dropped = true;
+            config::report_unresolved_drop(meta.drop_policy.as_ref(), self.derived, meta.callback_registered);
+        }
+        #[cfg(feature = "recycle")]
+        recycle::release(&self.lock);
+    }
+}
+
+/// Allocates the `Meta` of a new `Future`, reusing a recycled one when the `recycle` feature is
+/// enabled.
+#[cfg(feature = "recycle")]
+fn meta_cell() -> Arc<Mutex<Meta>> {
+    recycle::meta_cell(Meta::new())
+}
+
+#[cfg(not(feature = "recycle"))]
+fn meta_cell() -> Arc<Mutex<Meta>> {
+    Arc::new(Mutex::new(Meta::new()))
 }
 
 ///
@@ -73,16 +327,25 @@ pub fn new<A, E>() -> (Future<A, E>, FutureSetter<A, E>)
 {
     let callback = Arc::new(RefCell::new(None));
     let result   = Arc::new(RefCell::new(None));
+    #[cfg(feature = "graph")]
+    let node = debug::NodeHandle::new();
 
     let future = Future {
-        lock: Arc::new(Mutex::new(())),
+        lock: meta_cell(),
         callback: callback.clone(),
-        result: result.clone()
+        result: result.clone(),
+        #[cfg(feature = "graph")]
+        node: node.id()
     };
     let setter = FutureSetter {
         lock: future.lock.clone(),
         callback: callback,
-        result: result
+        result: result,
+        drop_guard: DropGuard { lock: future.lock.clone(), armed: true, derived: false },
+        #[cfg(feature = "graph")]
+        node: node,
+        #[cfg(feature = "registry")]
+        entry: registry::Entry::new(&future.lock)
     };
     (future, setter)
 }
@@ -108,24 +371,113 @@ pub fn done<A: 'static, E: 'static>(result: Result<A, E>) -> Future<A, E> {
 /// Blocks until the Future resolves
 /// # Panics
 /// This will panic if the FutureSetter is dropped without setting the result.
+#[cfg(not(target_arch = "wasm32"))]
 pub fn await<A, E>(f: Future<A, E>) -> Result<A, E>
     where A: 'static, E: 'static
 {
-    await_safe(f).unwrap()
+    let name = f.lock.lock().unwrap().name();
+    match await_safe(f) {
+        Ok(result) => result,
+        Err(DroppedSetterError) => panic!("The FutureSetter of {} was dropped without setting a result", name)
+    }
 }
 
 ///
 /// Like `await`, but wraps the `Future`s `Result` in an additional `Result`
 /// # Failures
 /// Returns Err(DroppedSetterError) if the FutureSetter goes out of scope without setting the result.
+#[cfg(not(target_arch = "wasm32"))]
 pub fn await_safe<A, E>(f: Future<A, E>) -> Result<Result<A, E>, DroppedSetterError>
     where A: 'static, E: 'static
 {
+    if let Some(current) = priority::current() {
+        priority::boost(&f.lock, Some(current));
+    }
     let (tx, rx) = channel();
-    f.resolve(move |result| tx.send(result).unwrap());
+    f.register(move |result| tx.send(result).unwrap());
+    for _ in 0..config::await_spins() {
+        match rx.try_recv() {
+            Ok(result) => return Ok(result),
+            Err(TryRecvError::Disconnected) => return Err(DroppedSetterError),
+            Err(TryRecvError::Empty) => {}
+        }
+    }
     rx.recv().map_err(|_| DroppedSetterError)
 }
 
+///
+/// Blocks until the first of `futures` resolves, returning its index and result. The results of
+/// the others are discarded.
+/// # Examples
+/// ```
+/// use future;
+///
+/// let (slow, _setter) = future::new::<i64, ()>();
+/// let fast = future::value::<i64, ()>(2);
+/// assert_eq!((1, Ok(2)), future::await_first(vec![slow, fast]));
+/// ```
+/// # Panics
+/// This will panic if `futures` is empty, or if every FutureSetter is dropped without setting a
+/// result.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn await_first<A, E>(futures: Vec<Future<A, E>>) -> (usize, Result<A, E>)
+    where A: 'static, E: 'static
+{
+    assert!(!futures.is_empty(), "await_first requires at least one Future");
+    let (tx, rx) = channel();
+    for (i, f) in futures.into_iter().enumerate() {
+        let tx = tx.clone();
+        f.register(move |result| {
+            let _ = tx.send((i, result));
+        });
+    }
+    drop(tx);
+    rx.recv().unwrap()
+}
+
+///
+/// Blocks until all of `futures` resolve, returning their results in order. `progress` is called
+/// on this thread with the number completed and the total, once at the start and again as each
+/// finishes, e.g. to draw a progress bar.
+/// # Examples
+/// ```
+/// use future;
+///
+/// let downloads = (0..3).map(|i| future::value::<i64, ()>(i)).collect();
+/// let results = future::await_all_with_progress(downloads, |done, total| {
+///     println!("{}/{} downloaded", done, total);
+/// });
+/// assert_eq!(vec![Ok(0), Ok(1), Ok(2)], results);
+/// ```
+/// # Panics
+/// This will panic if the `FutureSetter` of any of `futures` goes out of scope without setting the
+/// result.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn await_all_with_progress<A, E, F>(futures: Vec<Future<A, E>>, mut progress: F) -> Vec<Result<A, E>>
+    where F: FnMut(usize, usize) -> (),
+          A: 'static,
+          E: 'static
+{
+    let total = futures.len();
+    let (tx, rx) = channel();
+    for (i, f) in futures.into_iter().enumerate() {
+        let tx = tx.clone();
+        f.register(move |result| {
+            let _ = tx.send((i, result));
+        });
+    }
+    drop(tx);
+
+    let mut results = (0..total).map(|_| None).collect::<Vec<_>>();
+    progress(0, total);
+    for completed in 1..total + 1 {
+        let (i, result) = rx.recv().unwrap();
+        results[i] = Some(result);
+        progress(completed, total);
+    }
+    results.into_iter().map(Option::unwrap).collect()
+}
+
 /// Execute function `F` in a new thread, returning a `Future` of the result.
 pub fn run<F, A, E>(f: F) -> Future<A, E>
     where F: FnOnce() -> Result<A, E> + 'static + Send,
@@ -133,11 +485,59 @@ pub fn run<F, A, E>(f: F) -> Future<A, E>
           E: 'static
 {
     let (future, setter) = new();
+    #[cfg(feature = "wasm")]
+    {
+        if let Some(spawner) = wasm::spawner() {
+            let task = move || setter.set_result(f());
+            spawner.spawn(box task);
+            return timeout::with_default_timeout(future);
+        }
+    }
     thread::spawn(move || setter.set_result(f()));
-    future
+    timeout::with_default_timeout(future)
 }
 
 impl<A: 'static, E: 'static> Future<A, E> {
+    /// Overrides the crate-level `DropPolicy` applied if the `FutureSetter` of this `Future` is
+    /// dropped without setting a result. Unlike the crate-level policy, an override also applies to
+    /// a `Future` created by a combinator, whose setter is dropped when the chain feeding it is.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::config::DropPolicy;
+    ///
+    /// let (f, setter) = future::new::<i64, ()>();
+    /// let f = f.map(|i| i + 1).with_drop_policy(DropPolicy::Log);
+    /// drop(setter); // logs that the mapped Future was dropped without a result
+    /// ```
+    pub fn with_drop_policy(self, policy: config::DropPolicy) -> Future<A, E> {
+        self.lock.lock().unwrap().drop_policy = Some(policy);
+        self
+    }
+
+    /// Names this `Future` and those derived from it, which are told apart by a suffix counting
+    /// the combinators since this one, e.g. "fetch_user+1" after a `map`. The name appears in
+    /// `Future::info`, the `Debug` output of each `Future`, panics from `await` and `expect_ok`,
+    /// and `TimeoutError`s. It also names any unlabelled `Future`s this was derived from, so that
+    /// a stuck chain is counted under it in the `registry`.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let (f, _setter) = future::new::<i64, ()>();
+    /// let f = f.with_label("fetch_user").map(|i| i + 1);
+    /// assert_eq!(Some(String::from("fetch_user+1")), f.info().stage);
+    /// ```
+    pub fn with_label(self, label: &'static str) -> Future<A, E> {
+        {
+            let mut meta = self.lock.lock().unwrap();
+            meta.label = Some(Arc::new(String::from(label)));
+            meta.label_depth = meta.depth;
+        }
+        label_upstream(&self.lock, label);
+        self
+    }
+
     /// Checks whether the result on the Future has been set
     /// # Examples
     /// let (future, setter) = future::new::<i64, ()>();
@@ -168,6 +568,26 @@ impl<A: 'static, E: 'static> Future<A, E> {
         })
     }
 
+    /// Derive a value from a borrow of the successful value, keeping the original alongside it.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::Future;
+    ///
+    /// let payload: Future<Vec<u8>, ()> = future::value(vec![0; 1024]);
+    /// let with_len = payload.map_ref(|bytes| bytes.len());
+    /// assert_eq!(1024, future::await(with_len).unwrap().1);
+    /// ```
+    pub fn map_ref<F, B>(self, f: F) -> Future<(A, B), E>
+        where F: FnOnce(&A) -> B, F: 'static,
+              B: 'static
+    {
+        self.map(|a| {
+            let b = f(&a);
+            (a, b)
+        })
+    }
+
     /// Transform an error value into another.
     /// # Examples
     /// ```
@@ -215,6 +635,117 @@ impl<A: 'static, E: 'static> Future<A, E> {
         })
     }
 
+    /// Swap the success and error values, for when a failure is the expected outcome.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::Future;
+    ///
+    /// let future: Future<i64, String> = future::err(String::from("timed out"));
+    /// assert_eq!("timed out", future::await(future.invert()).unwrap());
+    /// ```
+    pub fn invert(self) -> Future<E, A> {
+        self.transform(|result| match result {
+            Ok(a)  => Err(a),
+            Err(e) => Ok(e)
+        })
+    }
+
+    /// Replace a successful value with `b`, for when only completion matters.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::Future;
+    ///
+    /// let written: Future<usize, String> = future::value(512);
+    /// assert_eq!(Ok(()), future::await(written.replace(())));
+    /// ```
+    pub fn replace<B: 'static>(self, b: B) -> Future<B, E> {
+        self.map(move |_| b)
+    }
+
+    /// Replace an error value with `e`.
+    pub fn replace_err<E2: 'static>(self, e: E2) -> Future<A, E2> {
+        self.map_err(move |_| e)
+    }
+
+    /// Resolve with `a` in place of an error, like `Result::unwrap_or`.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::Future;
+    ///
+    /// let port: Future<u16, String> = future::err(String::from("PORT not set"));
+    /// assert_eq!(Ok(8080), future::await(port.unwrap_or(8080)));
+    /// ```
+    pub fn unwrap_or(self, a: A) -> Future<A, Never> {
+        self.unwrap_or_else(move |_| a)
+    }
+
+    /// Resolve with `f` applied to an error, like `Result::unwrap_or_else`. Unlike `handle`, the
+    /// resulting `Future` can't fail.
+    pub fn unwrap_or_else<F>(self, f: F) -> Future<A, Never>
+        where F: FnOnce(E) -> A, F: 'static
+    {
+        self.transform(|result| Ok(result.unwrap_or_else(f)))
+    }
+
+    /// Resolve with the success value, if any, discarding an error, like `Result::ok`.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::Future;
+    ///
+    /// let future: Future<i64, String> = future::err(String::from("not found"));
+    /// assert_eq!(Ok(None), future::await(future.ok()));
+    /// ```
+    pub fn ok(self) -> Future<Option<A>, Never> {
+        self.transform(|result| Ok(result.ok()))
+    }
+
+    /// Resolve with the error, if any, discarding a success value, like `Result::err`.
+    pub fn err(self) -> Future<Option<E>, Never> {
+        self.transform(|result| Ok(result.err()))
+    }
+
+    /// Resolve with whether the `Future` succeeded with a value equal to `b`, like
+    /// `Result::contains`.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::Future;
+    ///
+    /// let status: Future<u16, String> = future::value(200);
+    /// assert_eq!(Ok(true), future::await(status.contains(200)));
+    /// ```
+    pub fn contains<B>(self, b: B) -> Future<bool, Never>
+        where B: PartialEq<A> + 'static
+    {
+        self.transform(move |result| Ok(match result {
+            Ok(ref a) => b == *a,
+            Err(_) => false
+        }))
+    }
+
+    /// Resolve with whether the `Future` succeeded, like `Result::is_ok`. Use `future::await` for
+    /// the blocking form, or `resolve` to be called back with the answer.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::Future;
+    ///
+    /// let future: Future<i64, String> = future::value(5);
+    /// assert_eq!(Ok(true), future::await(future.is_ok()));
+    /// ```
+    pub fn is_ok(self) -> Future<bool, Never> {
+        self.transform(|result| Ok(result.is_ok()))
+    }
+
+    /// Resolve with whether the `Future` failed, like `Result::is_err`.
+    pub fn is_err(self) -> Future<bool, Never> {
+        self.transform(|result| Ok(result.is_err()))
+    }
+
     /// Transform a success value when the transformation might fail. Returns an Err<E> if either
     /// the original computation or the transformation fail. The error type of the transformation
     /// must have an instance of Into<E> so that the final result has the same error type.
@@ -267,57 +798,113 @@ impl<A: 'static, E: 'static> Future<A, E> {
               E2: 'static,
               B: 'static
     {
-        let (future, setter) = new();
-        self.resolve(|result| {
-            setter.set_result(f(result));
+        let (future, setter) = self.derive();
+        self.register(|result| {
+            setter.set_result(config::timed(|| f(result)));
         });
         future
     }
 
-    /// Like `and_then`, except when the transformation returns another `Future` instead of a
-    /// `Result`
-    pub fn and_thenf<F, B, E2>(self, f: F) -> Future<B, E>
-        where F: FnOnce(A) -> Future<B, E2>, F: 'static,
-              E2: Into<E>, E2: 'static,
-              B: 'static
+    /// Like `and_then`, except when the transformation returns another `Future` (or anything
+    /// else that's `IntoFuture`, such as a `Ready`) instead of a `Result`
+    pub fn and_thenf<F, R>(self, f: F) -> Future<R::Item, E>
+        where F: FnOnce(A) -> R, F: 'static,
+              R: IntoFuture, R::Error: Into<E>
     {
-        self.transformf(|result| match result {
-            Ok(a)  => f(a).map_err(E2::into),
-            Err(e) => done(Err(e))
+        self.then_set(|result, setter| match result {
+            Ok(a)  => config::timed(|| f(a)).set_into(setter),
+            Err(e) => setter.set_result::<E>(Err(e))
         })
     }
 
     /// Monadic `bind`; same as `and_thenf`
-    pub fn bind<F, B, E2>(self, f: F) -> Future<B, E>
-        where F: FnOnce(A) -> Future<B, E2>, F: 'static,
-              E2: Into<E>, E2: 'static,
-              B: 'static
+    pub fn bind<F, R>(self, f: F) -> Future<R::Item, E>
+        where F: FnOnce(A) -> R, F: 'static,
+              R: IntoFuture, R::Error: Into<E>
     {
         self.and_thenf(f)
     }
 
-    /// Like `rescue`, except when the transformation returns another `Future` instead of a
-    /// `Result`
-    pub fn rescuef<F, E2>(self, f: F) -> Future<A, E>
-        where F: FnOnce(E) -> Future<A, E2>, F: 'static,
-              E2: Into<E>, E2: 'static
+    /// Like `rescue`, except when the transformation returns another `Future` (or anything else
+    /// that's `IntoFuture`) instead of a `Result`
+    pub fn rescuef<F, R>(self, f: F) -> Future<A, E>
+        where F: FnOnce(E) -> R, F: 'static,
+              R: IntoFuture<Item = A>, R::Error: Into<E>
     {
-        self.transformf(|result| match result {
-            Err(e) => f(e).map_err(E2::into),
-            Ok(a) => done(Ok(a))
+        self.then_set(|result, setter| match result {
+            Err(e) => config::timed(|| f(e)).set_into(setter),
+            Ok(a) => setter.set_result::<E>(Ok(a))
         })
     }
 
-    /// Like `transform`, except when the transformation returns another `Future` instead of a
-    /// `Result`
-    pub fn transformf<F, B, E2>(self, f: F) -> Future<B, E2>
-        where F: FnOnce(Result<A, E>) -> Future<B, E2>, F: 'static,
-              E2: 'static,
-              B: 'static
+    /// Tries each of `strategies` in order on an error, until one accepts it by returning a
+    /// recovery `Future`. If that recovery fails too, its error is offered to the strategies after
+    /// it, so layered fallbacks don't need nested `rescuef`s. Fails with the last error if no
+    /// remaining strategy accepts it.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::Future;
+    ///
+    /// let from_cache: Box<Fn(String) -> Option<Future<i64, String>>> =
+    ///     Box::new(|_| Some(future::err(String::from("cache miss"))));
+    /// let from_replica: Box<Fn(String) -> Option<Future<i64, String>>> =
+    ///     Box::new(|e| if e == "timeout" { Some(future::value(2)) } else { None });
+    /// let default: Box<Fn(String) -> Option<Future<i64, String>>> = Box::new(|_| Some(future::value(0)));
+    ///
+    /// let f = future::err::<i64, String>(String::from("timeout"))
+    ///     .rescue_chain(vec![from_cache, from_replica, default]);
+    /// assert_eq!(Ok(0), future::await(f));
+    /// ```
+    pub fn rescue_chain(self, strategies: Vec<Box<Fn(E) -> Option<Future<A, E>>>>) -> Future<A, E>
+        where E: Clone
+    {
+        rescue_from(self, strategies, 0)
+    }
+
+    /// Like `transform`, except when the transformation returns another `Future` (or anything
+    /// else that's `IntoFuture`) instead of a `Result`
+    pub fn transformf<F, R>(self, f: F) -> Future<R::Item, R::Error>
+        where F: FnOnce(Result<A, E>) -> R, F: 'static,
+              R: IntoFuture
+    {
+        self.then_set(|result_a, setter| config::timed(|| f(result_a)).set_into(setter))
+    }
+
+    /// Runs `f` with the result and the setter of a derived `Future` once this one resolves,
+    /// within the deadline of the derived `Future`.
+    fn then_set<F, B, E2>(self, f: F) -> Future<B, E2>
+        where F: FnOnce(Result<A, E>, FutureSetter<B, E2>) -> (), F: 'static,
+              B: 'static,
+              E2: 'static
+    {
+        let (future, setter) = self.derive();
+        let deadline = future.deadline();
+        self.register(move |result| deadline::run_within(deadline, || f(result, setter)));
+        future
+    }
+
+    /// Like `transform`, except `f` runs on a new thread once the `Future` resolves, rather than
+    /// on the thread resolving it. Useful for heavyweight processing of a result produced on a
+    /// thread that shouldn't be held up, such as the timer thread. See `ThreadPool::then_spawn_on`
+    /// to run `f` on a pool instead.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let f = future::value::<Vec<i64>, ()>(vec![3, 1, 2])
+    ///     .then_spawn(|result| result.map(|mut v| { v.sort(); v }));
+    /// assert_eq!(Ok(vec![1, 2, 3]), future::await(f));
+    /// ```
+    pub fn then_spawn<F, B, E2>(self, f: F) -> Future<B, E2>
+        where F: FnOnce(Result<A, E>) -> Result<B, E2> + Send + 'static,
+              A: Send, E: Send,
+              B: 'static,
+              E2: 'static
     {
-        let (future, setter) = new();
-        self.resolve(|result_a| {
-            f(result_a).resolve(|result_b| setter.set_result(result_b));
+        let (future, setter) = self.derive();
+        self.register(move |result| {
+            thread::spawn(move || setter.set_result(f(result)));
         });
         future
     }
@@ -349,14 +936,73 @@ impl<A: 'static, E: 'static> Future<A, E> {
     pub fn on_completion<F>(self, f: F) -> Future<A, E>
         where F: FnOnce(&Result<A, E>) -> (), F: 'static
     {
-        let (future, setter) = new();
-        self.resolve(|result| {
-            f(&result);
+        let (future, setter) = self.derive();
+        self.register(|result| {
+            config::timed(|| f(&result));
             setter.set_result(result);
         });
         future
     }
 
+    /// Sends a copy of the result to `sink` when the `Future` resolves, passing the result on
+    /// unchanged. Useful for audit logging, or for comparing shadow traffic against the original.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let (audit, sink) = future::new();
+    /// let f = future::value::<i64, String>(5).tee_to(sink);
+    /// assert_eq!(Ok(5), future::await(f));
+    /// assert_eq!(Ok(Ok(5)), future::await(audit));
+    /// ```
+    pub fn tee_to(self, sink: FutureSetter<Result<A, E>, Never>) -> Future<A, E>
+        where A: Clone, E: Clone
+    {
+        self.on_completion(move |result| sink.set_result::<Never>(Ok(result.clone())))
+    }
+
+    /// Adds a side-effect that reports when the `Future` is slow to resolve. If the `Future` is
+    /// still unresolved after `after`, `f` is called with `SlowHint::StillPending`, and then again
+    /// with `SlowHint::Resolved` once it does resolve. `f` is never called for a `Future` that
+    /// resolves within `after`. The hint is fired by the shared `timer` thread, so `f` should
+    /// return quickly. The result is passed through unchanged.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::{Future, SlowHint};
+    /// use std::time::Duration;
+    ///
+    /// let f: Future<i64, ()> = future::value(5);
+    /// let f = f.on_timeout_hint(Duration::from_millis(100), |hint| match hint {
+    ///     SlowHint::StillPending(elapsed) => println!("still waiting after {:?}", elapsed),
+    ///     SlowHint::Resolved(elapsed) => println!("finally resolved after {:?}", elapsed)
+    /// });
+    /// assert_eq!(5, future::await(f).unwrap());
+    /// ```
+    pub fn on_timeout_hint<F>(self, after: Duration, f: F) -> Future<A, E>
+        where F: Fn(SlowHint) -> (), F: Send + 'static
+    {
+        let start = Instant::now();
+        let state = Arc::new(Mutex::new(SlowHintState { resolved: false, hinted: false, f: f }));
+
+        let timer_state = state.clone();
+        timer::sleep(after).register(move |_| {
+            let mut state = timer_state.lock().unwrap();
+            if !state.resolved {
+                state.hinted = true;
+                (state.f)(SlowHint::StillPending(start.elapsed()));
+            }
+        });
+
+        self.on_completion(move |_| {
+            let mut state = state.lock().unwrap();
+            state.resolved = true;
+            if state.hinted {
+                (state.f)(SlowHint::Resolved(start.elapsed()));
+            }
+        })
+    }
+
     /// Stores the side-effecting `f` to be run once the `Future` completes. `f` will only run if
     /// the `Future` resolves successfully; an error result will be dropped. This consumes the
     /// `Future`
@@ -387,22 +1033,201 @@ impl<A: 'static, E: 'static> Future<A, E> {
     pub fn resolve<F>(self, f: F)
         where F: FnOnce(Result<A, E>) -> (), F: 'static
     {
-        let _lock = self.lock.lock().unwrap();
+        self.register(|result| config::timed(|| f(result)))
+    }
+
+    /// The same as `resolve`: `f` runs exactly once, with the result, on the thread that sets it,
+    /// or on this thread if it's already set. Like every callback, `f` runs after the internal
+    /// lock is released, so it may use the `Future`s of the same chain, e.g. raising the
+    /// priority of one derived from this.
+    pub fn then<F>(self, f: F)
+        where F: FnOnce(Result<A, E>) -> (), F: 'static
+    {
+        self.resolve(f)
+    }
+
+    /// Like `resolve`, except it reports what became of `f`: whether it was stored, or already
+    /// ran because the result was set. If the `FutureSetter` was dropped without setting the
+    /// result, so that `f` could never run, this `Future` and `f` are handed back instead.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::Registration;
+    ///
+    /// let (f, setter) = future::new::<i64, String>();
+    /// drop(setter);
+    /// match f.try_resolve(|result| println!("{:?}", result)) {
+    ///     Ok(registration) => panic!("Expected the callback back, got {:?}", registration),
+    ///     Err((_f, callback)) => callback(Err(String::from("no result")))
+    /// }
+    ///
+    /// let f = future::value::<i64, String>(1);
+    /// assert_eq!(Ok(Registration::Ran), f.try_resolve(|_| {}).map_err(|_| ()));
+    /// ```
+    pub fn try_resolve<F>(self, f: F) -> Result<Registration, (Future<A, E>, F)>
+        where F: FnOnce(Result<A, E>) -> (), F: 'static
+    {
+        let result = {
+            let mut meta = self.lock.lock().unwrap();
+            debug_assert!(!meta.consumed, "Registered a second callback on a Future");
+
+            let result = self.result.borrow_mut().take();
+            match result {
+                Some(result) => {
+                    meta.consumed = true;
+                    Some(result)
+                },
+                None if meta.setter_dropped => None,
+                None => {
+                    meta.consumed = true;
+                    meta.callback_registered = true;
+                    let f = |result| config::timed(|| f(result));
+                    *self.callback.borrow_mut() = Some(Callback::Fn(box f));
+                    return Ok(Registration::Registered);
+                }
+            }
+        };
+        match result {
+            Some(result) => {
+                config::timed(|| f(result));
+                Ok(Registration::Ran)
+            },
+            None => Err((self, f))
+        }
+    }
+
+    /// Runs `f` if this `Future`, or a `Future` derived from it, is dropped without being consumed
+    /// or resolved, i.e. once nothing can observe its result.
+    fn on_abandoned<F>(&self, f: F)
+        where F: FnOnce() -> () + Send + 'static
+    {
+        self.lock.lock().unwrap().abandoned.push(box f);
+    }
+
+    /// Creates a new (`Future`, `FutureSetter`) pair for a `Future` derived from this one by a
+    /// combinator, recording the edge between them when the `graph` feature is enabled.
+    fn derive<B: 'static, E2: 'static>(&self) -> (Future<B, E2>, FutureSetter<B, E2>) {
+        let (future, mut setter) = new();
+        setter.drop_guard.derived = true;
+        {
+            let parent = self.lock.lock().unwrap();
+            let mut meta = future.lock.lock().unwrap();
+            meta.depth = parent.depth + 1;
+            meta.trail = parent.trail.clone();
+            meta.label = parent.label.clone();
+            meta.label_depth = parent.label_depth;
+            if parent.deadline.is_some() {
+                meta.deadline = parent.deadline;
+            }
+        }
+        priority::link(&future.lock, &self.lock);
+        #[cfg(feature = "graph")]
+        debug::edge(self.node, future.node);
+        (future, setter)
+    }
+
+    /// Stores `f` to be run once the `Future` completes, like `resolve`, but without the
+    /// instrumentation applied to user callbacks. Used for the internal plumbing of combinators.
+    fn register<F>(self, f: F)
+        where F: FnOnce(Result<A, E>) -> (), F: 'static
+    {
+        let result = {
+            let mut meta = self.lock.lock().unwrap();
+            debug_assert!(!meta.consumed, "Registered a second callback on a Future");
+            meta.consumed = true;
+
+            let result = self.result.borrow_mut().take();
+            match result {
+                Some(result) => result,
+                None => {
+                    debug_assert!(self.callback.borrow().is_none(), "Overwrote the callback of a Future");
+                    meta.callback_registered = true;
+                    *self.callback.borrow_mut() = Some(Callback::Fn(box f));
+                    return;
+                }
+            }
+        };
+        // Callbacks never run under the lock, so they're free to use this `Future`'s chain.
+        f(result)
+    }
+}
+
+impl<A: 'static, E: 'static> fmt::Debug for Future<A, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let meta = self.lock.lock().unwrap();
+        f.debug_struct("Future")
+            .field("label", &meta.stage())
+            .field("depth", &meta.depth)
+            .field("resolved", &meta.resolved)
+            .finish()
+    }
+}
 
-        let result_set = {
-            self.result.borrow().is_some()
+impl<A: 'static, E: 'static> Drop for Future<A, E> {
+    fn drop(&mut self) {
+        // A poisoned lock means a panic while registering on this `Future`, which is being
+        // dropped as that panic unwinds; panicking again would abort.
+        let abandoned = match self.lock.lock() {
+            Ok(meta) => !meta.consumed && self.result.borrow().is_none(),
+            Err(_) => return
         };
+        if abandoned {
+            abandon(&self.lock);
+        }
+        #[cfg(feature = "recycle")]
+        recycle::release(&self.lock);
+    }
+}
+
+/// Runs the abandonment callbacks of a `Future` dropped without being consumed, and of every
+/// unresolved `Future` it was derived from, since nothing is waiting on their results any more.
+fn abandon(meta: &Arc<Mutex<Meta>>) {
+    let (callbacks, upstream) = {
+        let mut meta = meta.lock().unwrap();
+        meta.consumer_dropped = true;
+        (meta.abandoned.drain(..).collect::<Vec<_>>(), meta.upstream.clone())
+    };
+    for callback in callbacks {
+        callback();
+    }
+    for meta in upstream {
+        abandon(&meta);
+    }
+}
 
-        if result_set {
-            let box result = unwrap_unsafe(self.result);
-            f(result);
-        } else {
-            *self.callback.borrow_mut() = Some(box f);
-            Arc::downgrade(&self.callback);
+/// Gives `label` to the unlabelled `Future`s that the `Future` of `meta` was derived from.
+fn label_upstream(meta: &Arc<Mutex<Meta>>, label: &'static str) {
+    let upstream = meta.lock().unwrap().upstream.clone();
+    for meta in upstream {
+        {
+            let mut meta = meta.lock().unwrap();
+            if meta.label.is_some() {
+                continue;
+            }
+            meta.label = Some(Arc::new(String::from(label)));
+            meta.label_depth = meta.depth;
         }
+        label_upstream(&meta, label);
     }
 }
 
+/// Offers the error of `future`, if any, to `strategies` from the `i`th on, for `rescue_chain`.
+fn rescue_from<A, E>(future: Future<A, E>, strategies: Vec<Box<Fn(E) -> Option<Future<A, E>>>>, i: usize) -> Future<A, E>
+    where A: 'static, E: Clone + 'static
+{
+    future.transformf(move |result| match result {
+        Ok(a) => done(Ok(a)),
+        Err(e) => {
+            for j in i..strategies.len() {
+                if let Some(recovery) = strategies[j](e.clone()) {
+                    return rescue_from(recovery, strategies, j + 1);
+                }
+            }
+            done(Err(e))
+        }
+    })
+}
+
 impl<A, E, E2> Future<Future<A, E2>, E>
     where A: 'static, E: 'static,
           E2: Into<E> + 'static
@@ -435,29 +1260,325 @@ impl<A: 'static, E: 'static> FutureSetter<A, E> {
     /// transformations associated with the `Future`.
     pub fn set_result<E2: Into<E>>(self, result: Result<A, E2>) {
         let result = result.map_err(E2::into);
-        let _lock = self.lock.lock().unwrap();
+        let mut drop_guard = self.drop_guard;
+        drop_guard.armed = false;
+        let callback = {
+            let mut meta = self.lock.lock().unwrap();
+            debug_assert!(!meta.resolved, "Set the result of a Future twice");
+            meta.resolved = true;
+            // Nothing upstream of a resolved `Future` can affect it any more, and it can no longer
+            // be abandoned.
+            meta.upstream.clear();
+            meta.abandoned.clear();
 
-        let callback_set = {
-            self.callback.borrow().is_some()
+            let callback = self.callback.borrow_mut().take();
+            match callback {
+                Some(callback) => callback,
+                None => {
+                    *self.result.borrow_mut() = Some(result);
+                    return;
+                }
+            }
         };
-
-        if callback_set {
-            let callback = unwrap_unsafe(self.callback);
-            callback(result);
-        } else {
-            *self.result.borrow_mut() = Some(box result);
-            Arc::downgrade(&self.result);
-        }
+        callback.call(result)
     }
 
     pub fn callback_set(&self) -> bool {
         let _lock = self.lock.lock().unwrap();
         self.callback.borrow().is_some()
     }
+
+    /// Runs `f` if the consumer goes away before the result is set: if the associated `Future`, or
+    /// every `Future` derived from it, is dropped without being consumed. Lets a producer release
+    /// resources held for the result, e.g. return a connection to its pool. `f` runs at once if
+    /// the consumer has already gone, and never runs once the result is set.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let returned = Arc::new(AtomicBool::new(false));
+    /// let returned2 = returned.clone();
+    /// let (f, setter) = future::new::<String, ()>();
+    /// let setter = setter.with_cleanup(move || returned2.store(true, Ordering::SeqCst));
+    ///
+    /// drop(f.map(|body| body.len()));
+    /// assert!(returned.load(Ordering::SeqCst));
+    /// # drop(setter);
+    /// ```
+    pub fn with_cleanup<F>(self, f: F) -> FutureSetter<A, E>
+        where F: FnOnce() -> () + Send + 'static
+    {
+        self.map_consumer_dropped(f);
+        self
+    }
+
+    /// Like `with_cleanup`, but borrows the setter, for a producer that keeps it in place (e.g. in
+    /// a queue of pending requests) and frees what it reserved as soon as the consumer is gone,
+    /// rather than discovering that when it next gets to the request.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let free_slots = Arc::new(AtomicUsize::new(0));
+    /// let (f, setter) = future::new::<String, ()>();
+    /// let queue = vec![setter];
+    ///
+    /// let slots = free_slots.clone();
+    /// queue[0].map_consumer_dropped(move || { slots.fetch_add(1, Ordering::SeqCst); });
+    /// drop(f);
+    /// assert_eq!(1, free_slots.load(Ordering::SeqCst));
+    /// ```
+    pub fn map_consumer_dropped<F>(&self, f: F)
+        where F: FnOnce() -> () + Send + 'static
+    {
+        let run_now = {
+            let mut meta = self.lock.lock().unwrap();
+            if meta.consumer_dropped {
+                Some(f)
+            } else {
+                meta.abandoned.push(box f);
+                None
+            }
+        };
+        if let Some(f) = run_now {
+            f();
+        }
+    }
+
+    /// Overrides the crate-level `DropPolicy` for this setter, applied if it's dropped without
+    /// setting a result.
+    pub fn set_drop_policy(&self, policy: config::DropPolicy) {
+        self.lock.lock().unwrap().drop_policy = Some(policy);
+    }
+
+    /// Adapts this setter to accept a `B`, converted by `f` when the result is set. The dual of
+    /// `Future::map`, for handing a producer a setter of its own output type.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let (f, setter) = future::new::<String, ()>();
+    /// let setter = setter.premap(|i: i64| i.to_string());
+    /// setter.set_result(Ok(5): Result<i64, ()>);
+    /// assert_eq!("5", future::await(f).unwrap());
+    /// ```
+    pub fn premap<F, B>(self, f: F) -> FutureSetter<B, E>
+        where F: FnOnce(B) -> A, F: 'static,
+              B: 'static
+    {
+        self.adapt(|result| result.map(f))
+    }
+
+    /// Adapts this setter to accept an error of type `E2`, converted by `f` when the result is set.
+    pub fn premap_err<F, E2>(self, f: F) -> FutureSetter<A, E2>
+        where F: FnOnce(E2) -> E, F: 'static,
+              E2: 'static
+    {
+        self.adapt(|result| result.map_err(f))
+    }
+
+    fn adapt<F, B, E2>(self, f: F) -> FutureSetter<B, E2>
+        where F: FnOnce(Result<B, E2>) -> Result<A, E>, F: 'static,
+              B: 'static,
+              E2: 'static
+    {
+        let (future, mut setter) = new();
+        // Only this setter is reported if the adapted one is dropped without setting a result.
+        setter.drop_guard.derived = true;
+        future.register(move |result| self.set_result(config::timed(|| f(result))));
+        setter
+    }
+
+    /// Sets the result of the associated `Future` to that of `future` once it resolves. Cheaper
+    /// than `future.resolve(move |r| setter.set_result(r))`, as the setter is stored in place of a
+    /// boxed callback.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let (inner, inner_setter) = future::new::<i64, ()>();
+    /// let (outer, outer_setter) = future::new::<i64, ()>();
+    /// outer_setter.forward_from(inner);
+    ///
+    /// inner_setter.set_result(Ok(5): Result<i64, ()>);
+    /// assert_eq!(Ok(5), future::await(outer));
+    /// ```
+    pub fn forward_from(self, future: Future<A, E>) {
+        priority::link(&self.lock, &future.lock);
+        #[cfg(feature = "graph")]
+        debug::edge(future.node, self.node.id());
+
+        let result = {
+            let mut meta = future.lock.lock().unwrap();
+            debug_assert!(!meta.consumed, "Registered a second callback on a Future");
+            meta.consumed = true;
+
+            let result = future.result.borrow_mut().take();
+            match result {
+                Some(result) => result,
+                None => {
+                    debug_assert!(future.callback.borrow().is_none(), "Overwrote the callback of a Future");
+                    meta.callback_registered = true;
+                    *future.callback.borrow_mut() = Some(Callback::Forward(self));
+                    return;
+                }
+            }
+        };
+        self.set_result(result)
+    }
+
+    /// Like `forward_from`, converting the error of `future`, without the intermediate `Future`
+    /// that `map_err` would derive.
+    pub(crate) fn forward_converted<E2: Into<E> + 'static>(self, future: Future<A, E2>) {
+        priority::link(&self.lock, &future.lock);
+        #[cfg(feature = "graph")]
+        debug::edge(future.node, self.node.id());
+        future.register(move |result| self.set_result(result))
+    }
+
+    /// Converts this setter into a cloneable `SharedSetter`, allowing several completion paths
+    /// (e.g. a success path, an error path and a watchdog) to race to set the result.
+    pub fn shared(self) -> SharedSetter<A, E> {
+        SharedSetter { setter: Arc::new(Mutex::new(Some(self))) }
+    }
 }
 
 unsafe impl<A: 'static, E: 'static> Send for FutureSetter<A, E> {}
 
+/// A cloneable `FutureSetter` for which only the first attempt to set the result succeeds. Created
+/// with `FutureSetter::shared`.
+/// # Examples
+/// ```
+/// use future;
+///
+/// let (future, setter) = future::new::<i64, String>();
+/// let setter = setter.shared();
+/// let watchdog = setter.clone();
+///
+/// assert!(setter.set_if_unset(Ok(1): Result<i64, String>));
+/// assert!(!watchdog.set_if_unset(Err(String::from("timed out"))));
+/// assert_eq!(Ok(1), future::await(future));
+/// ```
+pub struct SharedSetter<A, E>
+    where A: 'static, E: 'static
+{
+    setter: Arc<Mutex<Option<FutureSetter<A, E>>>>
+}
+
+impl<A: 'static, E: 'static> SharedSetter<A, E> {
+    /// Sets the result of the associated `Future` if no result has been set yet, otherwise
+    /// returns the rejected result in an `AlreadySet` error.
+    pub fn try_set_result<E2: Into<E>>(&self, result: Result<A, E2>) -> Result<(), AlreadySet<A, E>> {
+        let result = result.map_err(E2::into);
+        let setter = self.setter.lock().unwrap().take();
+        match setter {
+            Some(setter) => Ok(setter.set_result(result)),
+            None => Err(AlreadySet(result))
+        }
+    }
+
+    /// Like `try_set_result`, except a rejected result is dropped. Returns whether the result was
+    /// set.
+    pub fn set_if_unset<E2: Into<E>>(&self, result: Result<A, E2>) -> bool {
+        self.try_set_result(result).is_ok()
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.setter.lock().unwrap().is_none()
+    }
+
+    /// A handle that can set the result only while some `SharedSetter` for it is still alive, for
+    /// watchdogs that shouldn't keep the setter, and the result cell, alive until they fire.
+    pub(crate) fn downgrade(&self) -> WeakSetter<A, E> {
+        WeakSetter { setter: Arc::downgrade(&self.setter) }
+    }
+}
+
+/// A `SharedSetter` that doesn't keep the setter alive. Created with `SharedSetter::downgrade`.
+pub(crate) struct WeakSetter<A, E>
+    where A: 'static, E: 'static
+{
+    setter: Weak<Mutex<Option<FutureSetter<A, E>>>>
+}
+
+impl<A: 'static, E: 'static> WeakSetter<A, E> {
+    /// Like `SharedSetter::set_if_unset`, except nothing is set once every `SharedSetter` has
+    /// been dropped.
+    pub(crate) fn set_if_unset<E2: Into<E>>(&self, result: Result<A, E2>) -> bool {
+        match self.setter.upgrade() {
+            Some(setter) => SharedSetter { setter: setter }.set_if_unset(result),
+            None => false
+        }
+    }
+}
+
+impl<A: 'static, E: 'static> Clone for SharedSetter<A, E> {
+    fn clone(&self) -> SharedSetter<A, E> {
+        SharedSetter { setter: self.setter.clone() }
+    }
+}
+
+/// An Error returned by `SharedSetter::try_set_result` when the result has already been set,
+/// carrying the rejected result.
+#[derive(Debug)]
+pub struct AlreadySet<A, E>(pub Result<A, E>);
+
+impl<A, E> fmt::Display for AlreadySet<A, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AlreadySet")
+    }
+}
+
+impl<A: fmt::Debug, E: fmt::Debug> Error for AlreadySet<A, E> {
+    fn description(&self) -> &str {
+        "The result of the Future associated with this setter has already been set"
+    }
+}
+
+/// A type with no values, used as the error type of a `Future` that cannot fail.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Never {}
+
+impl fmt::Display for Never {
+    fn fmt(&self, _: &mut fmt::Formatter) -> fmt::Result {
+        match *self {}
+    }
+}
+
+impl Error for Never {
+    fn description(&self) -> &str {
+        match *self {}
+    }
+}
+
+/// Passed to the callback given to `Future::on_timeout_hint`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SlowHint {
+    /// The `Future` has been unresolved for at least the contained `Duration`
+    StillPending(Duration),
+    /// The slow `Future` resolved after the contained `Duration`
+    Resolved(Duration)
+}
+
+struct SlowHintState<F> {
+    resolved: bool,
+    hinted: bool,
+    f: F
+}
+
+/// What became of a callback given to `Future::try_resolve`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Registration {
+    /// The result hadn't been set yet, so the callback was stored, to run once it is
+    Registered,
+    /// The result was already set, so the callback ran before `try_resolve` returned
+    Ran
+}
+
 /// An Error indicating that the `FutureSetter` for the associated `Future` left scope and was
 /// dropped before setting the result of the `Future`.
 #[derive(Debug, Copy, Clone)]
@@ -475,18 +1596,11 @@ impl Error for DroppedSetterError {
     }
 }
 
-/// Unwraps an `Arc<RefCell<Option<A>>>` into an `A`. This assumes the `Arc` has only
-/// one strong reference and that the `Option` is `Some`.
-fn unwrap_unsafe<A>(v: Arc<RefCell<Option<A>>>) -> A {
-    match Arc::try_unwrap(v) {
-        Ok(cell) => cell.into_inner().expect("Value was not set. This is a bug!"),
-        Err(_) => panic!("Multiple strong references held to arc; this is a bug!")
-    }
-}
-
 mod test {
     use std::cell::Cell;
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
     use super::*;
 
     #[test]
@@ -562,6 +1676,248 @@ mod test {
         assert_eq!(await(transformed_future), Ok(9));
     }
 
+    #[test]
+    fn on_timeout_hint_is_silent_for_fast_futures() {
+        let hints = Arc::new(Mutex::new(vec![]));
+        let hints2 = hints.clone();
+        let f = value::<i64, ()>(1)
+            .on_timeout_hint(Duration::from_millis(10), move |hint| hints2.lock().unwrap().push(hint));
+
+        assert_eq!(await(f), Ok(1));
+        thread::sleep(Duration::from_millis(50));
+        assert!(hints.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn on_timeout_hint_reports_pending_then_resolved() {
+        let hints = Arc::new(Mutex::new(vec![]));
+        let hints2 = hints.clone();
+        let (future, setter) = new::<i64, ()>();
+        let f = future
+            .on_timeout_hint(Duration::from_millis(10), move |hint| hints2.lock().unwrap().push(hint));
+
+        thread::sleep(Duration::from_millis(50));
+        setter.set_result(Ok(1): Result<i64, ()>);
+        assert_eq!(await(f), Ok(1));
+
+        let hints = hints.lock().unwrap();
+        assert_eq!(hints.len(), 2);
+        match (hints[0], hints[1]) {
+            (SlowHint::StillPending(pending), SlowHint::Resolved(resolved)) => assert!(pending <= resolved),
+            other => panic!("Unexpected hints: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn shared_setter_rejects_all_but_first_result() {
+        let (future, setter) = new::<i64, String>();
+        let setter = setter.shared();
+        let setter2 = setter.clone();
+        assert_eq!(setter2.is_set(), false);
+
+        assert!(setter.try_set_result(Ok(1): Result<i64, String>).is_ok());
+        match setter2.try_set_result(Ok(2): Result<i64, String>) {
+            Err(AlreadySet(Ok(2))) => {},
+            other => panic!("Expected AlreadySet, got {:?}", other)
+        }
+        assert_eq!(setter2.is_set(), true);
+        assert_eq!(await(future), Ok(1));
+    }
+
+    #[test]
+    fn forward_from_passes_on_resolved_and_pending_results() {
+        let (outer, outer_setter) = new::<i64, ()>();
+        outer_setter.forward_from(value(1));
+        assert_eq!(await(outer), Ok(1));
+
+        let (inner, inner_setter) = new::<i64, ()>();
+        let (outer, outer_setter) = new::<i64, ()>();
+        let outer = outer.map(|i| i + 1);
+        outer_setter.forward_from(inner);
+        assert!(!outer.is_resolved());
+        inner_setter.set_result(Ok(1): Result<i64, ()>);
+        assert_eq!(await(outer), Ok(2));
+    }
+
+    #[test]
+    fn cleanup_runs_only_if_the_consumer_goes_first() {
+        let cleaned = Arc::new(Mutex::new(0));
+        let (f, setter) = new::<i64, ()>();
+        let cleaned2 = cleaned.clone();
+        let setter = setter.with_cleanup(move || *cleaned2.lock().unwrap() += 1);
+        let f = f.map(|i| i + 1);
+        setter.set_result(Ok(1): Result<i64, ()>);
+        drop(f);
+        assert_eq!(*cleaned.lock().unwrap(), 0);
+
+        let (f, setter) = new::<i64, ()>();
+        drop(f);
+        let cleaned2 = cleaned.clone();
+        let _setter = setter.with_cleanup(move || *cleaned2.lock().unwrap() += 1);
+        assert_eq!(*cleaned.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn await_all_with_progress_reports_each_completion() {
+        let (slow, setter) = new::<i64, ()>();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            setter.set_result(Ok(1): Result<i64, ()>);
+        });
+        let mut reported = vec![];
+        let results = await_all_with_progress(vec![slow, value(2)], |done, total| reported.push((done, total)));
+        assert_eq!(results, vec![Ok(1), Ok(2)]);
+        assert_eq!(reported, vec![(0, 2), (1, 2), (2, 2)]);
+    }
+
+    #[test]
+    fn each_transition_delivers_the_result_exactly_once() {
+        // Set, then register
+        let (f, setter) = new::<i64, ()>();
+        setter.set_result(Ok(1): Result<i64, ()>);
+        assert!(f.is_resolved());
+        assert_eq!(await(f), Ok(1));
+
+        // Register, then set
+        let calls = Arc::new(Mutex::new(vec![]));
+        let calls2 = calls.clone();
+        let (f, setter) = new::<i64, ()>();
+        f.register(move |result| calls2.lock().unwrap().push(result));
+        assert!(setter.callback_set());
+        setter.set_result(Ok(2): Result<i64, ()>);
+        assert_eq!(*calls.lock().unwrap(), vec![Ok(2)]);
+
+        // Forward, then set, through a chain of forwards
+        let (inner, inner_setter) = new::<i64, ()>();
+        let (middle, middle_setter) = new::<i64, ()>();
+        let (outer, outer_setter) = new::<i64, ()>();
+        middle_setter.forward_from(inner);
+        outer_setter.forward_from(middle);
+        inner_setter.set_result(Ok(3): Result<i64, ()>);
+        assert_eq!(await(outer), Ok(3));
+
+        // Drop the setter, registered or not
+        let (f, setter) = new::<i64, ()>();
+        drop(setter);
+        assert!(await_safe(f).is_err());
+        let (f, setter) = new::<i64, ()>();
+        let f = f.map(|i| i + 1);
+        drop(setter);
+        assert!(await_safe(f).is_err());
+    }
+
+    /// A second handle on the same cell as `f`, which the public API never allows.
+    fn alias<A, E>(f: &Future<A, E>) -> Future<A, E> {
+        Future {
+            lock: f.lock.clone(),
+            callback: f.callback.clone(),
+            result: f.result.clone(),
+            #[cfg(feature = "graph")]
+            node: f.node
+        }
+    }
+
+    /// A second setter of the same cell as `f`, which the public API never allows.
+    fn alias_setter<A, E>(f: &Future<A, E>) -> FutureSetter<A, E> {
+        #[cfg(feature = "graph")]
+        let node = debug::NodeHandle::new();
+        FutureSetter {
+            lock: f.lock.clone(),
+            callback: f.callback.clone(),
+            result: f.result.clone(),
+            drop_guard: DropGuard { lock: f.lock.clone(), armed: false, derived: false },
+            #[cfg(feature = "graph")]
+            node: node,
+            #[cfg(feature = "registry")]
+            entry: registry::Entry::new(&f.lock)
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Registered a second callback on a Future")]
+    fn registering_twice_breaks_an_invariant() {
+        let (f, _setter) = new::<i64, ()>();
+        let second = alias(&f);
+        f.register(|_| {});
+        second.register(|_| {});
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Set the result of a Future twice")]
+    fn setting_twice_breaks_an_invariant() {
+        let (f, setter) = new::<i64, ()>();
+        let second = alias_setter(&f);
+        f.register(|_| {});
+        setter.set_result(Ok(1): Result<i64, ()>);
+        second.set_result(Ok(2): Result<i64, ()>);
+    }
+
+    #[test]
+    fn callbacks_can_reenter_their_own_chain() {
+        let (x, setter) = new::<i64, ()>();
+        let slot: Arc<Mutex<Option<Future<i64, ()>>>> = Arc::new(Mutex::new(None));
+        let slot2 = slot.clone();
+        let z = x.on_completion(move |_| {
+            // Raising the priority walks back upstream to `x`, whose result is being set.
+            let z = slot2.lock().unwrap().take().unwrap();
+            *slot2.lock().unwrap() = Some(z.with_priority(Priority::High));
+        });
+        *slot.lock().unwrap() = Some(z);
+
+        setter.set_result(Ok(1): Result<i64, ()>);
+        let z = slot.lock().unwrap().take().unwrap();
+        assert_eq!(z.priority(), Some(Priority::High));
+
+        // A callback added to a resolved `Future` runs at once, and may itself add callbacks.
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+        z.then(move |result| {
+            let seen3 = seen2.clone();
+            value::<i64, ()>(2).then(move |inner| seen3.lock().unwrap().push(inner));
+            seen2.lock().unwrap().push(result);
+        });
+        assert_eq!(*seen.lock().unwrap(), vec![Ok(2), Ok(1)]);
+    }
+
+    #[test]
+    fn try_resolve_hands_back_callbacks_that_could_never_run() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+        let (f, setter) = new::<i64, ()>();
+        assert_eq!(f.try_resolve(move |result| seen2.lock().unwrap().push(result)).map_err(|_| ()), Ok(Registration::Registered));
+        setter.set_result(Ok(1): Result<i64, ()>);
+        assert_eq!(*seen.lock().unwrap(), vec![Ok(1)]);
+
+        let (f, setter) = new::<i64, ()>();
+        drop(setter);
+        let (f, _callback) = match f.try_resolve(|_| {}) {
+            Ok(registration) => panic!("Expected the callback back, got {:?}", registration),
+            Err(returned) => returned
+        };
+        assert!(await_safe(f).is_err());
+    }
+
+    #[test]
+    fn labels_name_each_stage_of_the_chain() {
+        let (root, _setter) = new::<i64, TimeoutError>();
+        let labelled = root.map(|i| i + 1).with_label("fetch_user");
+        let timed = labelled.map(|i| i * 2).within(Duration::from_millis(1));
+        assert_eq!(format!("{:?}", timed), "Future { label: Some(\"fetch_user+2\"), depth: 3, resolved: false }");
+
+        let error = await(timed).unwrap_err();
+        assert_eq!(error.to_string(), "Future 'fetch_user+1' timed out after 1ms");
+    }
+
+    #[test]
+    fn tee_to_copies_errors_to_the_sink() {
+        let (audit, sink) = new();
+        let f = err::<i64, String>(String::from("denied")).tee_to(sink).map(|i| i + 1);
+        assert_eq!(await(f), Err(String::from("denied")));
+        assert_eq!(await(audit), Ok(Err(String::from("denied"))));
+    }
+
     fn incr_string(s: String) -> String {
         format!("{}", s.parse::<i64>().unwrap() + 1)
     }
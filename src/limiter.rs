@@ -0,0 +1,243 @@
+use super::{err, new, Future, FutureSetter, Service};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// A `Service` capping the number of calls in flight to an underlying service. Calls over the
+/// cap are queued, returning a pending `Future`, and started in order as earlier calls resolve.
+/// With `max_queued`, calls beyond the queue's capacity fail immediately with `Rejected` instead;
+/// `max_queued(0)` fails fast as soon as the cap is reached. Clones share the same cap and queue.
+/// # Examples
+/// ```
+/// use future;
+/// use future::{ConcurrentLimiter, Rejected, Service};
+/// use future::timer;
+/// use std::time::Duration;
+///
+/// let slow = |req: u64| -> future::Future<u64, Rejected> {
+///     timer::sleep(Duration::from_millis(20)).transform(move |_| Ok(req))
+/// };
+/// let limited = ConcurrentLimiter::wrap(slow, 1).max_queued(1);
+///
+/// let first = limited.call(1);
+/// let queued = limited.call(2);
+/// assert_eq!(1, limited.queued());
+/// assert_eq!(Err(Rejected), future::await(limited.call(3)));
+/// assert_eq!(Ok(1), future::await(first));
+/// assert_eq!(Ok(2), future::await(queued));
+/// ```
+pub struct ConcurrentLimiter<Req, Resp: 'static, E: 'static> {
+    service: Arc<Service<Req, Resp, E>>,
+    limit: usize,
+    max_queued: Option<usize>,
+    state: Arc<Mutex<LimiterState<Req, Resp, E>>>
+}
+
+struct LimiterState<Req, Resp: 'static, E: 'static> {
+    in_flight: usize,
+    queue: VecDeque<(Req, FutureSetter<Resp, E>)>,
+    /// Whether a thread is starting queued calls, which then starts the next call itself when
+    /// one resolves immediately
+    pumping: bool
+}
+
+impl<Req: 'static, Resp: 'static, E: 'static> ConcurrentLimiter<Req, Resp, E> {
+    /// Allows at most `limit` calls to `service` in flight at once, queueing the rest without
+    /// bound.
+    /// # Panics
+    /// This will panic if `limit` is 0.
+    pub fn wrap<S>(service: S, limit: usize) -> ConcurrentLimiter<Req, Resp, E>
+        where S: Service<Req, Resp, E> + 'static
+    {
+        assert!(limit > 0, "A ConcurrentLimiter requires a limit of at least 1");
+        ConcurrentLimiter {
+            service: Arc::new(service),
+            limit: limit,
+            max_queued: None,
+            state: Arc::new(Mutex::new(LimiterState { in_flight: 0, queue: VecDeque::new(), pumping: false }))
+        }
+    }
+
+    /// Rejects calls over the limit once `max_queued` calls are already waiting.
+    pub fn max_queued(mut self, max_queued: usize) -> ConcurrentLimiter<Req, Resp, E> {
+        self.max_queued = Some(max_queued);
+        self
+    }
+
+    /// The number of calls currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.state.lock().unwrap().in_flight
+    }
+
+    /// The number of calls waiting for one in flight to resolve.
+    pub fn queued(&self) -> usize {
+        self.state.lock().unwrap().queue.len()
+    }
+
+    /// Starts queued calls while under the limit, unless another thread already is. Calls that
+    /// resolve immediately leave starting the next one to this loop, rather than recursing once
+    /// per queued call.
+    fn pump(&self) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.pumping {
+                return;
+            }
+            state.pumping = true;
+        }
+        loop {
+            let (req, setter) = {
+                let mut state = self.state.lock().unwrap();
+                let next = if state.in_flight < self.limit { state.queue.pop_front() } else { None };
+                match next {
+                    Some(next) => {
+                        state.in_flight += 1;
+                        next
+                    },
+                    None => {
+                        state.pumping = false;
+                        return;
+                    }
+                }
+            };
+
+            // Dropped with the callback, which releases the slot whether or not it runs.
+            let mut call = LimitedCall { limiter: self.clone(), setter: Some(setter) };
+            self.service.call(req).register(move |result| {
+                if let Some(setter) = call.setter.take() {
+                    setter.set_result(result);
+                }
+            });
+        }
+    }
+
+    /// Releases the slot of a call that ended, starting the next queued call unless a pump is
+    /// running.
+    fn release(&self) {
+        let pumping = {
+            let mut state = match self.state.lock() {
+                Ok(state) => state,
+                Err(_) => return
+            };
+            state.in_flight -= 1;
+            state.pumping
+        };
+        if !pumping {
+            self.pump();
+        }
+    }
+}
+
+/// A call in flight. If the service's `FutureSetter` is dropped, the call's own setter is dropped
+/// too, and its slot is released regardless.
+struct LimitedCall<Req: 'static, Resp: 'static, E: 'static> {
+    limiter: ConcurrentLimiter<Req, Resp, E>,
+    setter: Option<FutureSetter<Resp, E>>
+}
+
+impl<Req: 'static, Resp: 'static, E: 'static> Drop for LimitedCall<Req, Resp, E> {
+    fn drop(&mut self) {
+        drop(self.setter.take());
+        self.limiter.release();
+    }
+}
+
+impl<Req, Resp, E> Service<Req, Resp, E> for ConcurrentLimiter<Req, Resp, E>
+    where Req: 'static,
+          Resp: 'static,
+          E: From<Rejected> + 'static
+{
+    fn call(&self, req: Req) -> Future<Resp, E> {
+        let future = {
+            let mut state = self.state.lock().unwrap();
+            if state.in_flight >= self.limit &&
+                self.max_queued.map_or(false, |max_queued| state.queue.len() >= max_queued)
+            {
+                return err(E::from(Rejected));
+            }
+            let (future, setter) = new();
+            state.queue.push_back((req, setter));
+            future
+        };
+        self.pump();
+        future
+    }
+}
+
+impl<Req, Resp: 'static, E: 'static> Clone for ConcurrentLimiter<Req, Resp, E> {
+    fn clone(&self) -> ConcurrentLimiter<Req, Resp, E> {
+        ConcurrentLimiter {
+            service: self.service.clone(),
+            limit: self.limit,
+            max_queued: self.max_queued,
+            state: self.state.clone()
+        }
+    }
+}
+
+/// An Error indicating that a call was rejected because a `ConcurrentLimiter` was at its limit
+/// and its queue was full.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Rejected;
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Rejected")
+    }
+}
+
+impl Error for Rejected {
+    fn description(&self) -> &str {
+        "The call was rejected because the limit of calls in flight was reached and the queue was full"
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, await_safe};
+
+    #[test]
+    fn queued_calls_start_as_earlier_ones_resolve() {
+        let setters = Arc::new(Mutex::new(vec![]));
+        let setters2 = setters.clone();
+        let limited = ConcurrentLimiter::wrap(move |req: i64| {
+            let (future, setter) = new::<i64, Rejected>();
+            setters2.lock().unwrap().push((req, setter));
+            future
+        }, 2);
+
+        let futures = (0..5).map(|i| limited.call(i)).collect::<Vec<_>>();
+        assert_eq!((limited.in_flight(), limited.queued()), (2, 3));
+
+        for _ in 0..5 {
+            let (req, setter) = setters.lock().unwrap().remove(0);
+            setter.set_result(Ok(req * 10): Result<i64, Rejected>);
+        }
+        assert_eq!((limited.in_flight(), limited.queued()), (0, 0));
+        assert_eq!(futures.into_iter().map(await).collect::<Vec<_>>(), vec![Ok(0), Ok(10), Ok(20), Ok(30), Ok(40)]);
+    }
+
+    #[test]
+    fn dropped_setters_release_their_slot() {
+        let setters = Arc::new(Mutex::new(vec![]));
+        let setters2 = setters.clone();
+        let limited = ConcurrentLimiter::wrap(move |req: i64| {
+            let (future, setter) = new::<(), Rejected>();
+            setters2.lock().unwrap().push(setter);
+            future.map(move |()| req)
+        }, 1).max_queued(0);
+
+        for _ in 0..3 {
+            let dropped = limited.call(0);
+            drop(setters.lock().unwrap().remove(0));
+            assert!(await_safe(dropped).is_err());
+            assert_eq!(limited.in_flight(), 0);
+        }
+
+        let last = limited.call(1);
+        let setter = setters.lock().unwrap().remove(0);
+        setter.set_result(Ok(()): Result<(), Rejected>);
+        assert_eq!(await(last), Ok(1));
+    }
+}
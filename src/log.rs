@@ -0,0 +1,156 @@
+//! A pluggable logging hook used by this crate's logging combinators, such as `Future::log_err`.
+//!
+//! By default messages are written to stderr; use `set_logger` to route them to the logging
+//! library of your choice.
+
+use super::Future;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE"
+        };
+        f.pad(name)
+    }
+}
+
+type Logger = Arc<Fn(Level, &str, &str) -> () + Send + Sync>;
+
+static LOGGER_INIT: Once = ONCE_INIT;
+static mut LOGGER: *const Mutex<Logger> = 0 as *const _;
+
+/// Route every message logged by this crate to `logger`, which is called with the level, target
+/// and message of each.
+/// # Examples
+/// ```
+/// use future::log;
+///
+/// log::set_logger(|level, target, message| println!("[{}] {}: {}", level, target, message));
+/// ```
+pub fn set_logger<F>(logger: F)
+    where F: Fn(Level, &str, &str) -> () + Send + Sync + 'static
+{
+    *logger_cell().lock().unwrap() = Arc::new(logger);
+}
+
+/// Logs `message` through the current logger.
+pub fn log(level: Level, target: &str, message: &str) {
+    let logger = logger_cell().lock().unwrap().clone();
+    logger(level, target, message);
+}
+
+fn logger_cell() -> &'static Mutex<Logger> {
+    unsafe {
+        LOGGER_INIT.call_once(|| {
+            let stderr: Logger = Arc::new(|level, target, message| {
+                let _ = writeln!(io::stderr(), "[{}] {}: {}", level, target, message);
+            });
+            LOGGER = Box::into_raw(box Mutex::new(stderr));
+        });
+        &*LOGGER
+    }
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Logs the error, if any, at `level` under `target`, passing the result through unchanged.
+    /// If this `Future` is dropped without ever resolving (e.g. because its `FutureSetter` was
+    /// dropped), a notice of that is logged instead, so failures can't disappear silently.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::log::Level;
+    ///
+    /// let f = future::err::<(), String>(String::from("connection refused"))
+    ///     .log_err("db", Level::Warn);
+    /// assert!(future::await(f).is_err());
+    /// ```
+    pub fn log_err(self, target: &'static str, level: Level) -> Future<A, E>
+        where E: fmt::Debug
+    {
+        let mut notice = UnresolvedNotice { target: target, level: level, armed: true };
+        self.on_completion(move |result| {
+            notice.armed = false;
+            if let Err(ref e) = *result {
+                log(level, target, &format!("{:?}", e));
+            }
+        })
+    }
+
+    /// Consumes this `Future` for its side effects alone, dropping a success and logging an error
+    /// at `Level::Error` under the "future" target, as with `log_err`. Use this rather than
+    /// dropping a `Future` whose result isn't needed, which would lose its error.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let (f, setter) = future::new::<(), String>();
+    /// f.swallow_err_with_log();
+    /// setter.set_result(Err(String::from("cache write failed")): Result<(), String>);
+    /// ```
+    pub fn swallow_err_with_log(self)
+        where E: fmt::Debug
+    {
+        self.log_err("future", Level::Error).resolve(|_| {})
+    }
+}
+
+/// Logs a notice when dropped while still armed, i.e. when the callback owning it is dropped
+/// without having run.
+struct UnresolvedNotice {
+    target: &'static str,
+    level: Level,
+    armed: bool
+}
+
+impl Drop for UnresolvedNotice {
+    fn drop(&mut self) {
+        if self.armed {
+            log(self.level, self.target, "Future was dropped without resolving");
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, err, new};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn log_err_logs_errors_and_dropped_futures() {
+        let logged = Arc::new(Mutex::new(vec![]));
+        let logged2 = logged.clone();
+        set_logger(move |level, target, message| {
+            if target == "log_err_test" {
+                logged2.lock().unwrap().push((level, String::from(message)));
+            }
+        });
+
+        let f = err::<(), &str>("boom").log_err("log_err_test", Level::Error);
+        assert_eq!(await(f), Err("boom"));
+
+        let (f, setter) = new::<(), &str>();
+        let _f = f.log_err("log_err_test", Level::Warn);
+        drop(setter);
+
+        assert_eq!(*logged.lock().unwrap(), vec![
+            (Level::Error, String::from("\"boom\"")),
+            (Level::Warn, String::from("Future was dropped without resolving"))
+        ]);
+    }
+}
@@ -0,0 +1,302 @@
+use super::{new, value, Future, FutureSetter, Service};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Wraps `f` in a `Memoized`, which caches its successful values by argument, and shares a single
+/// call between concurrent requests for the same argument. A single call site change adds caching
+/// to an asynchronous function.
+/// # Examples
+/// ```
+/// use future;
+/// use std::sync::{Arc, Mutex};
+/// use std::time::Duration;
+///
+/// let calls = Arc::new(Mutex::new(0));
+/// let calls2 = calls.clone();
+/// let lookup = future::memoize_async(move |id: u64| {
+///     *calls2.lock().unwrap() += 1;
+///     future::value::<String, ()>(format!("user {}", id))
+/// }).capacity(1000).ttl(Duration::from_secs(60));
+///
+/// assert_eq!(Ok(String::from("user 7")), future::await(lookup.call(7)));
+/// assert_eq!(Ok(String::from("user 7")), future::await(lookup.call(7)));
+/// assert_eq!(1, *calls.lock().unwrap());
+/// ```
+pub fn memoize_async<F, K, V, E>(f: F) -> Memoized<K, V, E>
+    where F: Fn(K) -> Future<V, E> + 'static,
+          K: Hash + Eq + Clone + 'static,
+          V: Clone + 'static,
+          E: Clone + 'static
+{
+    Memoized {
+        f: Arc::new(f),
+        capacity: None,
+        ttl: None,
+        state: Arc::new(Mutex::new(MemoState { entries: HashMap::new(), uses: 0 }))
+    }
+}
+
+/// An asynchronous function whose values are cached. Created with `future::memoize_async`; clones
+/// share the same cache.
+///
+/// Errors aren't cached, but every call for an argument that is already in flight shares that
+/// call's result, success or failure. With a `capacity`, the least recently used value is evicted
+/// to make room for a new one; with a `ttl`, values are recomputed once they are older than it.
+pub struct Memoized<K, V, E>
+    where K: 'static, V: 'static, E: 'static
+{
+    f: Arc<Fn(K) -> Future<V, E>>,
+    capacity: Option<usize>,
+    ttl: Option<Duration>,
+    state: Arc<Mutex<MemoState<K, V, E>>>
+}
+
+struct MemoState<K, V, E>
+    where K: 'static, V: 'static, E: 'static
+{
+    entries: HashMap<K, MemoEntry<V, E>>,
+    /// Counts calls, to order values by when they were last used
+    uses: u64
+}
+
+enum MemoEntry<V, E>
+    where V: 'static, E: 'static
+{
+    InFlight(Vec<FutureSetter<V, E>>),
+    Cached { value: V, stored: Instant, last_used: u64 }
+}
+
+impl<K, V, E> Memoized<K, V, E>
+    where K: Hash + Eq + Clone + 'static,
+          V: Clone + 'static,
+          E: Clone + 'static
+{
+    /// Caches at most `capacity` values.
+    pub fn capacity(mut self, capacity: usize) -> Memoized<K, V, E> {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Recomputes values once they are older than `ttl`.
+    pub fn ttl(mut self, ttl: Duration) -> Memoized<K, V, E> {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Returns the cached value for `key`, or joins the call in flight for it, or calls the
+    /// wrapped function.
+    pub fn call(&self, key: K) -> Future<V, E> {
+        let future = {
+            let mut state = self.state.lock().unwrap();
+            state.uses += 1;
+            let uses = state.uses;
+            let ttl = self.ttl;
+            match state.entries.get_mut(&key) {
+                Some(&mut MemoEntry::Cached { value: ref cached, stored, ref mut last_used })
+                    if ttl.map_or(true, |ttl| stored.elapsed() < ttl) => {
+                    *last_used = uses;
+                    return value(cached.clone());
+                },
+                Some(&mut MemoEntry::InFlight(ref mut waiters)) => {
+                    let (future, setter) = new();
+                    waiters.push(setter);
+                    return future;
+                },
+                _ => {}
+            }
+            let (future, setter) = new();
+            state.entries.insert(key.clone(), MemoEntry::InFlight(vec![setter]));
+            future
+        };
+
+        // Dropped with the callback, which gives up on the call if it never runs.
+        let mut pending = PendingCall { memo: self.clone(), key: Some(key.clone()) };
+        (self.f)(key).register(move |result| {
+            let key = pending.key.take().unwrap();
+            pending.memo.store(key, result)
+        });
+        future
+    }
+
+    /// The number of values cached, excluding calls in flight.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.values().filter(|entry| match **entry {
+            MemoEntry::Cached { .. } => true,
+            MemoEntry::InFlight(_) => false
+        }).count()
+    }
+
+    /// Forgets every cached value. Calls in flight are unaffected.
+    pub fn clear(&self) {
+        self.state.lock().unwrap().entries.retain(|_, entry| match *entry {
+            MemoEntry::Cached { .. } => false,
+            MemoEntry::InFlight(_) => true
+        });
+    }
+
+    /// Hands the result of the call for `key` to its waiters, caching it if it succeeded.
+    fn store(&self, key: K, result: Result<V, E>) {
+        let waiters = {
+            let mut state = self.state.lock().unwrap();
+            let waiters = match state.entries.remove(&key) {
+                Some(MemoEntry::InFlight(waiters)) => waiters,
+                _ => vec![]
+            };
+            if let Ok(ref v) = result {
+                if self.capacity != Some(0) {
+                    state.evict_for(self.capacity);
+                    let uses = state.uses;
+                    state.entries.insert(key, MemoEntry::Cached { value: v.clone(), stored: Instant::now(), last_used: uses });
+                }
+            }
+            waiters
+        };
+        for waiter in waiters {
+            waiter.set_result(result.clone());
+        }
+    }
+
+    /// Forgets the call for `key`, whose `FutureSetter` was dropped without setting a result, and
+    /// drops its waiters' setters in turn, so that the next call for `key` starts afresh.
+    fn give_up(&self, key: &K) {
+        let waiters = {
+            let mut state = match self.state.lock() {
+                Ok(state) => state,
+                Err(_) => return
+            };
+            match state.entries.remove(key) {
+                Some(MemoEntry::InFlight(waiters)) => waiters,
+                Some(cached) => {
+                    state.entries.insert(key.clone(), cached);
+                    vec![]
+                },
+                None => vec![]
+            }
+        };
+        drop(waiters);
+    }
+}
+
+/// A call of the wrapped function whose result hasn't been stored yet.
+struct PendingCall<K, V, E>
+    where K: Hash + Eq + Clone + 'static, V: Clone + 'static, E: Clone + 'static
+{
+    memo: Memoized<K, V, E>,
+    key: Option<K>
+}
+
+impl<K, V, E> Drop for PendingCall<K, V, E>
+    where K: Hash + Eq + Clone + 'static, V: Clone + 'static, E: Clone + 'static
+{
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.memo.give_up(&key);
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V, E> MemoState<K, V, E> {
+    /// Evicts the least recently used value if another would exceed `capacity`.
+    fn evict_for(&mut self, capacity: Option<usize>) {
+        let capacity = match capacity {
+            Some(capacity) => capacity,
+            None => return
+        };
+        let cached = self.entries.values().filter(|entry| match **entry {
+            MemoEntry::Cached { .. } => true,
+            MemoEntry::InFlight(_) => false
+        }).count();
+        if cached < capacity {
+            return;
+        }
+        let oldest = self.entries.iter()
+            .filter_map(|(key, entry)| match *entry {
+                MemoEntry::Cached { last_used, .. } => Some((last_used, key)),
+                MemoEntry::InFlight(_) => None
+            })
+            .min_by_key(|&(last_used, _)| last_used)
+            .map(|(_, key)| key.clone());
+        if let Some(oldest) = oldest {
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+impl<K, V, E> Service<K, V, E> for Memoized<K, V, E>
+    where K: Hash + Eq + Clone + 'static,
+          V: Clone + 'static,
+          E: Clone + 'static
+{
+    fn call(&self, key: K) -> Future<V, E> {
+        Memoized::call(self, key)
+    }
+}
+
+impl<K, V, E> Clone for Memoized<K, V, E> {
+    fn clone(&self) -> Memoized<K, V, E> {
+        Memoized {
+            f: self.f.clone(),
+            capacity: self.capacity,
+            ttl: self.ttl,
+            state: self.state.clone()
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, await_safe};
+
+    #[test]
+    fn memoized_calls_share_in_flight_work_and_evict_the_least_recently_used() {
+        let setters = Arc::new(Mutex::new(vec![]));
+        let setters2 = setters.clone();
+        let lookup = memoize_async(move |id: i64| {
+            let (f, setter) = new::<i64, String>();
+            setters2.lock().unwrap().push((id, setter));
+            f
+        }).capacity(2);
+
+        let first = lookup.call(1);
+        let second = lookup.call(1);
+        assert_eq!(setters.lock().unwrap().len(), 1);
+        let (id, setter) = setters.lock().unwrap().remove(0);
+        setter.set_result(Ok(id * 10): Result<i64, String>);
+        assert_eq!((await(first), await(second)), (Ok(10), Ok(10)));
+
+        for id in 2..4 {
+            let f = lookup.call(id);
+            let (id, setter) = setters.lock().unwrap().remove(0);
+            setter.set_result(Ok(id * 10): Result<i64, String>);
+            await(f).unwrap();
+        }
+        assert_eq!(lookup.len(), 2);
+        assert_eq!(await(lookup.call(3)), Ok(30));
+        let _evicted = lookup.call(1);
+        assert_eq!(setters.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn calls_whose_setter_is_dropped_are_forgotten() {
+        let setters = Arc::new(Mutex::new(vec![]));
+        let setters2 = setters.clone();
+        let lookup = memoize_async(move |id: i64| {
+            let (f, setter) = new::<i64, String>();
+            setters2.lock().unwrap().push((id, setter));
+            f
+        });
+
+        let first = lookup.call(1);
+        let joined = lookup.call(1);
+        drop(setters.lock().unwrap().remove(0));
+        assert!(await_safe(first).is_err());
+        assert!(await_safe(joined).is_err());
+
+        let retried = lookup.call(1);
+        let (id, setter) = setters.lock().unwrap().remove(0);
+        setter.set_result(Ok(id * 10): Result<i64, String>);
+        assert_eq!(await(retried), Ok(10));
+    }
+}
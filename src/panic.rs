@@ -0,0 +1,210 @@
+use super::{Future, FutureSetter, IntoFuture};
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+/// The payload of a panic caught by one of the `_catching` combinators, such as
+/// `Future::map_catching`.
+pub struct PanicPayload(pub Box<Any + Send>);
+
+impl PanicPayload {
+    /// The panic message, if the panic was raised with a `&str` or `String` (as `panic!` does).
+    pub fn message(&self) -> Option<&str> {
+        match self.0.downcast_ref::<&'static str>() {
+            Some(s) => Some(*s),
+            None => self.0.downcast_ref::<String>().map(|s| &s[..])
+        }
+    }
+}
+
+impl fmt::Debug for PanicPayload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PanicPayload({:?})", self.message())
+    }
+}
+
+impl fmt::Display for PanicPayload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.message() {
+            Some(message) => write!(f, "Callback panicked: {}", message),
+            None => write!(f, "Callback panicked")
+        }
+    }
+}
+
+impl Error for PanicPayload {
+    fn description(&self) -> &str {
+        "A callback panicked while transforming a Future"
+    }
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Like `map`, except a panic in `f` is caught and converted into an error rather than
+    /// unwinding through the thread that resolves the `Future`.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::{Future, PanicPayload};
+    ///
+    /// let f: Future<i64, PanicPayload> = future::value(0);
+    /// let f = f.map_catching(|i| if i == 0 { panic!("zero") } else { 10 / i });
+    /// assert_eq!(Some("zero"), future::await(f).unwrap_err().message());
+    /// ```
+    pub fn map_catching<F, B>(self, f: F) -> Future<B, E>
+        where F: FnOnce(A) -> B, F: 'static,
+              B: 'static,
+              E: From<PanicPayload>
+    {
+        self.and_then(move |a| catch_panic(move || f(a)))
+    }
+
+    /// Like `and_then`, except a panic in `f` is caught and converted into an error.
+    pub fn and_then_catching<F, B, E2>(self, f: F) -> Future<B, E>
+        where F: FnOnce(A) -> Result<B, E2>, F: 'static,
+              E2: Into<E>, E2: 'static,
+              B: 'static,
+              E: From<PanicPayload>
+    {
+        self.and_then(move |a| match catch_panic(move || f(a)) {
+            Ok(result) => result.map_err(E2::into),
+            Err(panic) => Err(E::from(panic))
+        })
+    }
+
+    /// Like `handle`, except a panic in `f` is caught and converted into an error.
+    pub fn handle_catching<F>(self, f: F) -> Future<A, E>
+        where F: FnOnce(E) -> A, F: 'static,
+              E: From<PanicPayload>
+    {
+        self.rescue(move |e| catch_panic(move || f(e)))
+    }
+
+    /// Like `rescue`, except a panic in `f` is caught and converted into an error.
+    pub fn rescue_catching<F, E2>(self, f: F) -> Future<A, E>
+        where F: FnOnce(E) -> Result<A, E2>, F: 'static,
+              E2: Into<E>, E2: 'static,
+              E: From<PanicPayload>
+    {
+        self.rescue(move |e| match catch_panic(move || f(e)) {
+            Ok(result) => result.map_err(E2::into),
+            Err(panic) => Err(E::from(panic))
+        })
+    }
+
+    /// Like `transform`, except a panic in `f` is caught and converted into an error.
+    pub fn transform_catching<F, B, E2>(self, f: F) -> Future<B, E2>
+        where F: FnOnce(Result<A, E>) -> Result<B, E2>, F: 'static,
+              E2: From<PanicPayload>, E2: 'static,
+              B: 'static
+    {
+        self.transform(move |result| match catch_panic(move || f(result)) {
+            Ok(result) => result,
+            Err(panic) => Err(E2::from(panic))
+        })
+    }
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Wraps this `Future` in an `UnwindSafeFuture`, which can be moved into `catch_unwind` whatever
+    /// `A` and `E` are. Like `AssertUnwindSafe`, this is an assertion by the caller rather than
+    /// something the compiler checks.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use std::cell::Cell;
+    /// use std::panic;
+    /// use std::rc::Rc;
+    ///
+    /// let (f, setter) = future::new::<Rc<Cell<i64>>, String>();
+    /// let buffered = f.buffer_unwind_safe();
+    /// let handled = panic::catch_unwind(move || buffered.into_future().map(|cell| cell.get() + 1));
+    /// setter.set_result(Ok::<_, String>(Rc::new(Cell::new(1))));
+    /// assert_eq!(Ok(2), future::await(handled.unwrap()));
+    /// ```
+    pub fn buffer_unwind_safe(self) -> UnwindSafeFuture<A, E> {
+        UnwindSafeFuture { future: AssertUnwindSafe(self) }
+    }
+}
+
+/// A `Future` that can cross a `catch_unwind` boundary, for servers that isolate the handling of
+/// each request. Created with `Future::buffer_unwind_safe`. Unwrap it with `into_future` once
+/// outside the boundary.
+///
+/// This is `AssertUnwindSafe` for a `Future`. A panic can't leave the `Future` itself
+/// half-updated, as its state only changes while its lock is held and callbacks run after that
+/// lock is released. The callbacks registered on it, including those run when it is abandoned,
+/// may capture anything, though, and it is up to the caller that whatever they capture is safe to
+/// observe after a panic.
+pub struct UnwindSafeFuture<A: 'static, E: 'static> {
+    future: AssertUnwindSafe<Future<A, E>>
+}
+
+impl<A: 'static, E: 'static> UnwindSafeFuture<A, E> {
+    pub fn into_future(self) -> Future<A, E> {
+        self.future.0
+    }
+}
+
+impl<A: 'static, E: 'static> IntoFuture for UnwindSafeFuture<A, E> {
+    type Item = A;
+    type Error = E;
+
+    fn into_future(self) -> Future<A, E> {
+        UnwindSafeFuture::into_future(self)
+    }
+
+    fn set_into<E2>(self, setter: FutureSetter<A, E2>)
+        where E: Into<E2>, E2: 'static
+    {
+        UnwindSafeFuture::into_future(self).set_into(setter)
+    }
+}
+
+fn catch_panic<F, B>(f: F) -> Result<B, PanicPayload>
+    where F: FnOnce() -> B
+{
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(PanicPayload)
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, err, new, value};
+
+    #[test]
+    fn catching_combinators_convert_panics_into_errors() {
+        let f = value::<i64, PanicPayload>(1)
+            .and_then_catching(|_| -> Result<i64, PanicPayload> { panic!("and_then") });
+        assert_eq!(await(f).unwrap_err().message(), Some("and_then"));
+
+        let f = err::<i64, PanicPayload>(PanicPayload(Box::new(())))
+            .handle_catching(|_| panic!("{}", "handle"));
+        assert_eq!(await(f).unwrap_err().message(), Some("handle"));
+    }
+
+    #[test]
+    fn catching_combinators_pass_through_without_panics() {
+        let f = value::<i64, PanicPayload>(1)
+            .map_catching(|i| i + 1)
+            .transform_catching(|r| r.map(|i| i * 2));
+        assert_eq!(await(f).unwrap(), 4);
+    }
+
+    #[test]
+    fn futures_cross_catch_unwind() {
+        let (f, setter) = new::<i64, String>();
+        let buffered = f.map(|i| i + 1).buffer_unwind_safe();
+        let result = panic::catch_unwind(move || {
+            drop(buffered);
+            panic!("handler")
+        });
+        assert!(result.is_err());
+        drop(setter);
+
+        let (f, setter) = new::<i64, String>();
+        let buffered = f.buffer_unwind_safe();
+        let buffered = panic::catch_unwind(move || buffered).unwrap();
+        setter.set_result(Ok(1): Result<i64, String>);
+        assert_eq!(await(buffered.into_future()), Ok(1));
+    }
+}
@@ -0,0 +1,198 @@
+use super::{new, timer, value, Future, FutureSetter, Jitter, Never};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Runs `f` every `period`, starting one `period` from now, until the returned handle is stopped
+/// or dropped. `f` is called on the timer thread, so should hand off any long-running work (e.g.
+/// with `future::run`), and the next run is skipped while the `Future` returned by the previous
+/// one hasn't resolved. Runs stay on the schedule set by the first one, rather than drifting by
+/// the time each run takes.
+/// # Examples
+/// ```
+/// use future;
+/// use std::sync::{Arc, Mutex};
+/// use std::thread;
+/// use std::time::Duration;
+///
+/// let ticks = Arc::new(Mutex::new(0));
+/// let ticks2 = ticks.clone();
+/// let handle = future::spawn_periodic(Duration::from_millis(5), move || {
+///     *ticks2.lock().unwrap() += 1;
+///     future::value::<(), ()>(())
+/// });
+///
+/// thread::sleep(Duration::from_millis(30));
+/// future::await(handle.stop()).unwrap();
+/// assert!(*ticks.lock().unwrap() > 0);
+/// ```
+pub fn spawn_periodic<F, A, E>(period: Duration, f: F) -> PeriodicHandle
+    where F: FnMut() -> Future<A, E> + Send + 'static,
+          A: 'static,
+          E: 'static
+{
+    interval_jittered(period, Jitter::None, f)
+}
+
+/// Like `spawn_periodic`, except each wait between runs is `period` randomized by `jitter`, so
+/// that many clients started together, e.g. polling or refreshing a shared dependency, drift
+/// apart rather than hitting it in lockstep. Use `Jitter::Equal` to keep the average rate
+/// close to once per `period`.
+/// # Examples
+/// ```
+/// use future;
+/// use future::Jitter;
+/// use std::time::Duration;
+///
+/// let handle = future::interval_jittered(Duration::from_secs(30), Jitter::Equal, || {
+///     // refresh a cached config here
+///     future::value::<(), ()>(())
+/// });
+/// future::await(handle.stop()).unwrap();
+/// ```
+pub fn interval_jittered<F, A, E>(period: Duration, jitter: Jitter, f: F) -> PeriodicHandle
+    where F: FnMut() -> Future<A, E> + Send + 'static,
+          A: 'static,
+          E: 'static
+{
+    let state = Arc::new(Mutex::new(PeriodicState { stopped: false, in_flight: false, stop_setters: vec![] }));
+    let task = Arc::new(PeriodicTask { period: period, jitter: jitter, f: Mutex::new(f), state: state.clone() });
+    let wait = jitter.apply(period, None);
+    PeriodicTask::schedule(task, Instant::now() + wait, wait);
+    PeriodicHandle { state: state }
+}
+
+/// A handle on a task started with `future::spawn_periodic`. Dropping the handle stops the task.
+pub struct PeriodicHandle {
+    state: Arc<Mutex<PeriodicState>>
+}
+
+struct PeriodicState {
+    stopped: bool,
+    in_flight: bool,
+    stop_setters: Vec<FutureSetter<(), Never>>
+}
+
+struct PeriodicTask<F> {
+    period: Duration,
+    jitter: Jitter,
+    f: Mutex<F>,
+    state: Arc<Mutex<PeriodicState>>
+}
+
+impl PeriodicHandle {
+    /// Stops the task, returning a `Future` that resolves once the run in flight, if any, has
+    /// finished.
+    pub fn stop(self) -> Future<(), Never> {
+        let mut state = self.state.lock().unwrap();
+        state.stopped = true;
+        if !state.in_flight {
+            return value(());
+        }
+        let (future, setter) = new();
+        state.stop_setters.push(setter);
+        future
+    }
+}
+
+impl Drop for PeriodicHandle {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().stopped = true;
+    }
+}
+
+impl<F, A, E> PeriodicTask<F>
+    where F: FnMut() -> Future<A, E> + Send + 'static,
+          A: 'static,
+          E: 'static
+{
+    /// Runs the task at `deadline`, `wait` after the run before.
+    fn schedule(task: Arc<Self>, deadline: Instant, wait: Duration) {
+        timer::at(deadline).register(move |_| {
+            {
+                let mut state = task.state.lock().unwrap();
+                if state.stopped {
+                    return;
+                }
+                if !state.in_flight {
+                    state.in_flight = true;
+                    drop(state);
+                    PeriodicTask::run(&task);
+                }
+            }
+            let wait = task.jitter.apply(task.period, Some(wait));
+            PeriodicTask::schedule(task, deadline + wait, wait);
+        });
+    }
+
+    fn run(task: &Arc<Self>) {
+        let future = (&mut *task.f.lock().unwrap())();
+        // Dropped with the callback, which ends the run whether or not it runs.
+        let run = PeriodicRun { state: task.state.clone() };
+        future.register(move |_| drop(run));
+    }
+}
+
+/// The run in flight. Dropped once its `Future` resolves, or once its `FutureSetter` is dropped,
+/// which lets later runs start and resolves any pending `stop`.
+struct PeriodicRun {
+    state: Arc<Mutex<PeriodicState>>
+}
+
+impl Drop for PeriodicRun {
+    fn drop(&mut self) {
+        let stop_setters = {
+            // A poisoned lock means this is being dropped by an unwinding panic.
+            let mut state = match self.state.lock() {
+                Ok(state) => state,
+                Err(_) => return
+            };
+            state.in_flight = false;
+            state.stop_setters.drain(..).collect::<Vec<_>>()
+        };
+        for setter in stop_setters {
+            setter.set_result(Ok(()): Result<(), Never>);
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::await;
+    use std::thread;
+
+    #[test]
+    fn stop_waits_for_the_run_in_flight() {
+        let setters = Arc::new(Mutex::new(vec![]));
+        let setters2 = setters.clone();
+        let handle = spawn_periodic(Duration::from_millis(1), move || {
+            let (future, setter) = new::<(), ()>();
+            setters2.lock().unwrap().push(setter);
+            future
+        });
+        while setters.lock().unwrap().is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let stopped = handle.stop();
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(setters.lock().unwrap().len(), 1);
+        assert!(!stopped.is_resolved());
+
+        setters.lock().unwrap().pop().unwrap().set_result(Ok(()): Result<(), ()>);
+        assert_eq!(await(stopped), Ok(()));
+    }
+
+    #[test]
+    fn dropped_setters_end_the_run() {
+        let runs = Arc::new(Mutex::new(0));
+        let runs2 = runs.clone();
+        let handle = spawn_periodic(Duration::from_millis(1), move || {
+            *runs2.lock().unwrap() += 1;
+            new::<(), ()>().0
+        });
+        while *runs.lock().unwrap() < 3 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(await(handle.stop()), Ok(()));
+    }
+}
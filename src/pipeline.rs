@@ -0,0 +1,330 @@
+use super::{new, value, Future, FutureSetter};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// A reusable chain of asynchronous stages that many inputs can be pushed through. Each stage
+/// limits how many of its `Future`s may be in flight at once, queueing inputs beyond that limit,
+/// and keeps counts that can be read with `metrics`.
+///
+/// # Examples
+///
+/// ```
+/// use future;
+/// use future::Pipeline;
+///
+/// let pipeline = Pipeline::<String, String, String>::builder()
+///     .stage("parse", 4, |line: String| future::done(line.parse::<i64>().map_err(|e| e.to_string())))
+///     .stage("double", 2, |i| future::value(i * 2))
+///     .build();
+///
+/// let results: Vec<_> = vec!["1", "2", "x"].into_iter()
+///     .map(|line| pipeline.push(String::from(line)))
+///     .collect();
+/// let results: Vec<_> = results.into_iter().map(future::await).collect();
+/// assert_eq!(Ok(2), results[0]);
+/// assert_eq!(Ok(4), results[1]);
+/// assert!(results[2].is_err());
+/// assert_eq!(2, pipeline.metrics()[0].completed);
+/// ```
+pub struct Pipeline<I, O, E>
+    where I: 'static, O: 'static, E: 'static
+{
+    run: Arc<Fn(I) -> Future<O, E>>,
+    stages: Vec<Arc<StageStats>>
+}
+
+/// Builds a `Pipeline` one stage at a time. Created with `Pipeline::builder`.
+pub struct PipelineBuilder<I, O, E>
+    where I: 'static, O: 'static, E: 'static
+{
+    run: Arc<Fn(I) -> Future<O, E>>,
+    stages: Vec<Arc<StageStats>>
+}
+
+/// A snapshot of the counts kept by a `Pipeline` stage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageMetrics {
+    pub name: String,
+    /// The number of `Future`s started by this stage that haven't resolved yet
+    pub in_flight: usize,
+    /// The number of inputs waiting for the stage's concurrency limit
+    pub queued: usize,
+    /// The number of inputs this stage has finished successfully
+    pub completed: u64,
+    /// The number of inputs this stage has failed
+    pub failed: u64
+}
+
+impl<I: 'static, E: 'static> Pipeline<I, I, E> {
+    /// Starts building a pipeline with no stages, which would resolve every input to itself.
+    pub fn builder() -> PipelineBuilder<I, I, E> {
+        PipelineBuilder { run: Arc::new(|i| value(i)), stages: vec![] }
+    }
+}
+
+impl<I: 'static, O: 'static, E: 'static> Pipeline<I, O, E> {
+    /// Pushes `input` through every stage, returning a `Future` of the final stage's result. An
+    /// input failing at any stage skips the remaining stages.
+    pub fn push(&self, input: I) -> Future<O, E> {
+        (self.run)(input)
+    }
+
+    /// The current metrics of each stage, in the order the stages were added.
+    pub fn metrics(&self) -> Vec<StageMetrics> {
+        self.stages.iter().map(|stage| stage.metrics()).collect()
+    }
+}
+
+impl<I: 'static, O: 'static, E: 'static> Clone for Pipeline<I, O, E> {
+    fn clone(&self) -> Pipeline<I, O, E> {
+        Pipeline { run: self.run.clone(), stages: self.stages.clone() }
+    }
+}
+
+impl<I: 'static, T: 'static, E: 'static> PipelineBuilder<I, T, E> {
+    /// Adds a stage running `f` on the output of the previous stage, with at most `limit` of its
+    /// `Future`s in flight at once.
+    /// # Panics
+    /// This will panic if `limit` is 0.
+    pub fn stage<F, U>(self, name: &str, limit: usize, f: F) -> PipelineBuilder<I, U, E>
+        where F: Fn(T) -> Future<U, E>, F: 'static,
+              U: 'static
+    {
+        assert!(limit > 0, "A pipeline stage requires a limit of at least 1");
+        let stage = Arc::new(Stage {
+            name: String::from(name),
+            limit: limit,
+            f: box f,
+            state: Mutex::new(StageState {
+                in_flight: 0,
+                queue: VecDeque::new(),
+                completed: 0,
+                failed: 0,
+                pumping: false
+            })
+        });
+
+        let mut stages = self.stages;
+        stages.push(stage.clone());
+        let prev = self.run;
+        PipelineBuilder {
+            run: Arc::new(move |i| {
+                let stage = stage.clone();
+                prev(i).and_thenf(move |t| Stage::submit(&stage, t))
+            }),
+            stages: stages
+        }
+    }
+
+    pub fn build(self) -> Pipeline<I, T, E> {
+        Pipeline { run: self.run, stages: self.stages }
+    }
+}
+
+struct Stage<T, U, E>
+    where T: 'static, U: 'static, E: 'static
+{
+    name: String,
+    limit: usize,
+    f: Box<Fn(T) -> Future<U, E>>,
+    state: Mutex<StageState<T, U, E>>
+}
+
+struct StageState<T, U, E>
+    where T: 'static, U: 'static, E: 'static
+{
+    in_flight: usize,
+    queue: VecDeque<(T, FutureSetter<U, E>)>,
+    completed: u64,
+    failed: u64,
+    /// Whether a thread is starting queued inputs, which then starts the next input itself when
+    /// one resolves immediately
+    pumping: bool
+}
+
+trait StageStats {
+    fn metrics(&self) -> StageMetrics;
+}
+
+impl<T: 'static, U: 'static, E: 'static> Stage<T, U, E> {
+    fn submit(stage: &Arc<Self>, t: T) -> Future<U, E> {
+        let (future, setter) = new();
+        stage.state.lock().unwrap().queue.push_back((t, setter));
+        Stage::pump(stage);
+        future
+    }
+
+    /// Starts queued inputs while the stage is under its limit, unless another thread already is.
+    /// Inputs that resolve immediately leave starting the next one to this loop, rather than
+    /// recursing once per queued input.
+    fn pump(stage: &Arc<Self>) {
+        {
+            let mut state = stage.state.lock().unwrap();
+            if state.pumping {
+                return;
+            }
+            state.pumping = true;
+        }
+        loop {
+            let (t, setter) = {
+                let mut state = stage.state.lock().unwrap();
+                let next = if state.in_flight < stage.limit { state.queue.pop_front() } else { None };
+                match next {
+                    Some(next) => {
+                        state.in_flight += 1;
+                        next
+                    },
+                    None => {
+                        state.pumping = false;
+                        return;
+                    }
+                }
+            };
+
+            // Dropped with the callback, which ends the call whether or not it runs.
+            let mut call = StageCall { stage: stage.clone(), setter: Some(setter) };
+            (stage.f)(t).register(move |result| {
+                if let Some(setter) = call.setter.take() {
+                    call.stage.record(result.is_ok());
+                    setter.set_result(result);
+                }
+            });
+        }
+    }
+
+    fn record(&self, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        if success {
+            state.completed += 1;
+        } else {
+            state.failed += 1;
+        }
+    }
+
+    /// Ends a call in flight, starting the next input unless a pump is running.
+    fn finish(stage: &Arc<Self>) {
+        let pumping = {
+            let mut state = match stage.state.lock() {
+                Ok(state) => state,
+                Err(_) => return
+            };
+            state.in_flight -= 1;
+            state.pumping
+        };
+        if !pumping {
+            Stage::pump(stage);
+        }
+    }
+}
+
+/// A call in flight. If the stage's `FutureSetter` is dropped, the input's own setter is dropped
+/// too, the input is counted as failed, and the next input is started regardless.
+struct StageCall<T, U, E>
+    where T: 'static, U: 'static, E: 'static
+{
+    stage: Arc<Stage<T, U, E>>,
+    setter: Option<FutureSetter<U, E>>
+}
+
+impl<T: 'static, U: 'static, E: 'static> Drop for StageCall<T, U, E> {
+    fn drop(&mut self) {
+        if let Some(setter) = self.setter.take() {
+            if let Ok(mut state) = self.stage.state.lock() {
+                state.failed += 1;
+            }
+            drop(setter);
+        }
+        Stage::finish(&self.stage);
+    }
+}
+
+impl<T: 'static, U: 'static, E: 'static> StageStats for Stage<T, U, E> {
+    fn metrics(&self) -> StageMetrics {
+        let state = self.state.lock().unwrap();
+        StageMetrics {
+            name: self.name.clone(),
+            in_flight: state.in_flight,
+            queued: state.queue.len(),
+            completed: state.completed,
+            failed: state.failed
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, await_safe};
+    use std::cell::RefCell;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn stages_queue_inputs_beyond_their_limit() {
+        let setters = Arc::new(Mutex::new(VecDeque::new()));
+        let setters2 = setters.clone();
+        let pipeline = Pipeline::<i64, i64, ()>::builder()
+            .stage("slow", 1, move |i| {
+                let (future, setter) = new();
+                setters2.lock().unwrap().push_back((i, setter));
+                future
+            })
+            .build();
+
+        let first = pipeline.push(1);
+        let second = pipeline.push(2);
+        let metrics = pipeline.metrics();
+        assert_eq!((metrics[0].in_flight, metrics[0].queued), (1, 1));
+
+        for _ in 0..2 {
+            let (i, setter) = setters.lock().unwrap().pop_front().unwrap();
+            setter.set_result(Ok(i * 10): Result<i64, ()>);
+        }
+        assert_eq!(await(first), Ok(10));
+        assert_eq!(await(second), Ok(20));
+
+        let metrics = pipeline.metrics();
+        assert_eq!((metrics[0].in_flight, metrics[0].queued, metrics[0].completed), (0, 0, 2));
+    }
+
+    #[test]
+    fn dropped_setters_start_the_next_input() {
+        let setters = Arc::new(Mutex::new(VecDeque::new()));
+        let setters2 = setters.clone();
+        let pipeline = Pipeline::<i64, i64, ()>::builder()
+            .stage("dropping", 1, move |i| {
+                let (future, setter) = new::<(), ()>();
+                setters2.lock().unwrap().push_back(setter);
+                future.map(move |()| i)
+            })
+            .build();
+
+        let first = pipeline.push(1);
+        let second = pipeline.push(2);
+        drop(setters.lock().unwrap().pop_front().unwrap());
+        assert!(await_safe(first).is_err());
+        let setter = setters.lock().unwrap().pop_front().unwrap();
+        setter.set_result(Ok(()): Result<(), ()>);
+        assert_eq!(await(second), Ok(2));
+
+        let metrics = pipeline.metrics();
+        assert_eq!((metrics[0].in_flight, metrics[0].queued), (0, 0));
+        assert_eq!((metrics[0].completed, metrics[0].failed), (1, 1));
+    }
+
+    #[test]
+    fn queues_of_resolved_inputs_dont_recurse() {
+        let (blocker, blocker_setter) = new::<i64, ()>();
+        let blocker = RefCell::new(Some(blocker));
+        let pipeline = Pipeline::<i64, i64, ()>::builder()
+            .stage("immediate", 1, move |i| match blocker.borrow_mut().take() {
+                Some(blocker) => blocker,
+                None => value(i)
+            })
+            .build();
+
+        let results = (0..100000).map(|i| pipeline.push(i)).collect::<Vec<_>>();
+        assert_eq!(pipeline.metrics()[0].queued, 99999);
+        blocker_setter.set_result(Ok(0): Result<i64, ()>);
+        assert_eq!(await(results.into_iter().last().unwrap()), Ok(99999));
+        assert_eq!(pipeline.metrics()[0].in_flight, 0);
+    }
+}
@@ -0,0 +1,275 @@
+use super::{new, priority, timeout, Future, Meta, Priority};
+use std::boxed::FnBox;
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread;
+
+/// A fixed-size pool of worker threads. Like `future::run`, except work is queued onto the
+/// pool's threads rather than each call spawning a new thread. Clones of a `ThreadPool` share the
+/// same workers, which exit once every clone has been dropped and the queue has drained.
+///
+/// Queued work is run highest `Priority` first, and in the order it was queued otherwise. The
+/// priority of work started with `run_with_priority` follows its `Future`, so it is raised when a
+/// higher-priority `Future` depends on it.
+///
+/// # Examples
+///
+/// ```
+/// use future;
+/// use future::ThreadPool;
+///
+/// let pool = ThreadPool::new(4);
+/// let f = pool.run(|| Ok(2 + 2): Result<i64, ()>);
+/// assert_eq!(4, future::await(f).unwrap());
+/// ```
+pub struct ThreadPool {
+    shared: Arc<PoolShared>,
+    _handle: Arc<PoolHandle>
+}
+
+struct PoolShared {
+    queue: Mutex<PoolQueue>,
+    condvar: Condvar
+}
+
+struct PoolQueue {
+    /// Queued jobs by id
+    jobs: HashMap<u64, Job>,
+    /// The ids of queued jobs at each priority, lowest first, in the order they were queued at
+    /// it. A job raised to a higher priority leaves its id behind, which is skipped when popped.
+    levels: [VecDeque<u64>; 3],
+    next_id: u64,
+    shutdown: bool
+}
+
+struct Job {
+    priority: Priority,
+    f: Box<FnBox() -> () + Send>
+}
+
+/// A handle on a queued job whose priority follows the `Future` of its result, kept in that
+/// `Future`'s `Meta`.
+#[derive(Clone)]
+pub(crate) struct QueuedJob {
+    shared: Weak<PoolShared>,
+    id: u64
+}
+
+/// Shuts the workers down once the last `ThreadPool` clone is dropped.
+struct PoolHandle {
+    shared: Arc<PoolShared>
+}
+
+impl ThreadPool {
+    /// Creates a pool with `size` worker threads.
+    /// # Panics
+    /// This will panic if `size` is 0.
+    pub fn new(size: usize) -> ThreadPool {
+        assert!(size > 0, "A ThreadPool requires at least one thread");
+        let shared = Arc::new(PoolShared {
+            queue: Mutex::new(PoolQueue {
+                jobs: HashMap::new(),
+                levels: [VecDeque::new(), VecDeque::new(), VecDeque::new()],
+                next_id: 0,
+                shutdown: false
+            }),
+            condvar: Condvar::new()
+        });
+        for i in 0..size {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name(format!("future-pool-{}", i))
+                .spawn(move || work(&shared))
+                .unwrap();
+        }
+        ThreadPool { shared: shared.clone(), _handle: Arc::new(PoolHandle { shared: shared }) }
+    }
+
+    /// Execute function `F` on the pool, returning a `Future` of the result.
+    pub fn run<F, A, E>(&self, f: F) -> Future<A, E>
+        where F: FnOnce() -> Result<A, E> + Send + 'static,
+              A: 'static,
+              E: 'static
+    {
+        self.run_with_priority(Priority::Normal, f)
+    }
+
+    /// Like `run`, except at `priority` rather than `Priority::Normal`.
+    pub fn run_with_priority<F, A, E>(&self, priority: Priority, f: F) -> Future<A, E>
+        where F: FnOnce() -> Result<A, E> + Send + 'static,
+              A: 'static,
+              E: 'static
+    {
+        let (future, setter) = new();
+        future.lock.lock().unwrap().priority = Some(priority);
+        let job = move || setter.set_result(f());
+        self.push_inherited(&future.lock, box job);
+        timeout::with_default_timeout(future)
+    }
+
+    /// Like `Future::then_spawn`, except `f` is queued onto the pool, at the priority of the
+    /// returned `Future`.
+    pub fn then_spawn_on<F, A, E, B, E2>(&self, future: Future<A, E>, f: F) -> Future<B, E2>
+        where F: FnOnce(Result<A, E>) -> Result<B, E2> + Send + 'static,
+              A: Send + 'static,
+              E: Send + 'static,
+              B: 'static,
+              E2: 'static
+    {
+        let (derived, setter) = future.derive();
+        let pool = self.clone();
+        let meta = derived.lock.clone();
+        future.register(move |result| {
+            let job = move || setter.set_result(f(result));
+            pool.push_inherited(&meta, box job);
+        });
+        derived
+    }
+
+    /// Execute the side-effecting `f` on the pool.
+    pub fn execute<F>(&self, f: F)
+        where F: FnOnce() -> () + Send + 'static
+    {
+        self.push(Priority::Normal, box f);
+    }
+
+    fn push(&self, priority: Priority, f: Box<FnBox() -> () + Send>) -> u64 {
+        let id = self.shared.queue.lock().unwrap().push(Job { priority: priority, f: f });
+        self.shared.condvar.notify_one();
+        id
+    }
+
+    /// Queues `f` at the priority of `meta`, following it if it's raised while `f` is queued.
+    fn push_inherited(&self, meta: &Arc<Mutex<Meta>>, f: Box<FnBox() -> () + Send>) {
+        let priority = meta.lock().unwrap().priority.unwrap_or_default();
+        let job = QueuedJob { shared: Arc::downgrade(&self.shared), id: self.push(priority, f) };
+        // Only one lock is held at a time, as in `priority::boost`. A raise that lands before the
+        // handle is stored is caught by reading the priority again.
+        let priority = {
+            let mut meta = meta.lock().unwrap();
+            meta.pool_job = Some(job.clone());
+            meta.priority.unwrap_or_default()
+        };
+        job.raise(priority);
+    }
+}
+
+impl Clone for ThreadPool {
+    fn clone(&self) -> ThreadPool {
+        ThreadPool { shared: self.shared.clone(), _handle: self._handle.clone() }
+    }
+}
+
+impl Drop for PoolHandle {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().shutdown = true;
+        self.shared.condvar.notify_all();
+    }
+}
+
+impl QueuedJob {
+    /// Moves the job to the queue of `priority`, if it's still queued at a lower one.
+    pub(crate) fn raise(&self, priority: Priority) {
+        if let Some(shared) = self.shared.upgrade() {
+            shared.queue.lock().unwrap().raise(self.id, priority);
+        }
+    }
+}
+
+impl PoolQueue {
+    fn push(&mut self, job: Job) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.levels[job.priority as usize].push_back(id);
+        self.jobs.insert(id, job);
+        id
+    }
+
+    fn raise(&mut self, id: u64, priority: Priority) {
+        if let Some(job) = self.jobs.get_mut(&id) {
+            if priority > job.priority {
+                job.priority = priority;
+                self.levels[priority as usize].push_back(id);
+            }
+        }
+    }
+
+    /// Removes the earliest queued job of the highest priority.
+    fn pop(&mut self) -> Option<Job> {
+        for level in (0..self.levels.len()).rev() {
+            while let Some(id) = self.levels[level].pop_front() {
+                if self.jobs.get(&id).map_or(false, |job| job.priority as usize == level) {
+                    return self.jobs.remove(&id);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Runs jobs until the pool is shut down and the queue has drained. A panicking job is contained
+/// so that it doesn't take the worker thread down with it.
+fn work(shared: &PoolShared) {
+    loop {
+        let job = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if let Some(job) = queue.pop() {
+                    break job;
+                }
+                if queue.shutdown {
+                    return;
+                }
+                queue = shared.condvar.wait(queue).unwrap();
+            }
+        };
+        let priority = job.priority;
+        let f = job.f;
+        priority::run_as(Some(priority), move || {
+            let _ = panic::catch_unwind(AssertUnwindSafe(move || f()));
+        });
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, new};
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn raised_jobs_run_before_earlier_jobs() {
+        let pool = ThreadPool::new(1);
+        let order = Arc::new(Mutex::new(vec![]));
+
+        let (started, start) = new::<(), ()>();
+        let (open, gate) = channel::<()>();
+        pool.execute(move || {
+            start.set_result(Ok(()): Result<(), ()>);
+            gate.recv().unwrap();
+        });
+        await(started).unwrap();
+
+        let (o1, o2) = (order.clone(), order.clone());
+        let first = pool.run_with_priority(Priority::Low, move || Ok(o1.lock().unwrap().push(1)): Result<(), ()>);
+        let second = pool.run_with_priority(Priority::Low, move || Ok(o2.lock().unwrap().push(2)): Result<(), ()>);
+        let second = second.map(|_| ()).with_priority(Priority::High);
+
+        open.send(()).unwrap();
+        await(second).unwrap();
+        await(first).unwrap();
+        assert_eq!(*order.lock().unwrap(), vec![2, 1]);
+    }
+
+    #[test]
+    fn then_spawn_on_runs_continuations_on_the_pool() {
+        let pool = ThreadPool::new(1);
+        let (f, setter) = new::<i64, ()>();
+        let f = pool.then_spawn_on(f, |result| {
+            result.map(|i| (i, thread::current().name().map(String::from)))
+        });
+        setter.set_result(Ok(1): Result<i64, ()>);
+        assert_eq!(await(f), Ok((1, Some(String::from("future-pool-0")))));
+    }
+}
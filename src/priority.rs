@@ -0,0 +1,112 @@
+use super::{Future, Meta};
+use std::cell::Cell;
+use std::sync::{Arc, Mutex};
+
+/// The scheduling priority of work run on a `ThreadPool`.
+///
+/// Priorities are inherited: a `Future` derived from others (through transformations, `and_thenf`
+/// or the `join` functions) links back to them, and raising its priority with
+/// `Future::with_priority` raises theirs too. A `Future` awaited from a pool job is raised to the
+/// priority of that job. Queued pool jobs whose `Future`s are raised are then scheduled at the new
+/// priority, so high-priority work waiting on low-priority jobs doesn't wait behind other
+/// low-priority work.
+///
+/// # Examples
+///
+/// ```
+/// use future;
+/// use future::{Priority, ThreadPool};
+///
+/// let pool = ThreadPool::new(1);
+/// let background = pool.run_with_priority(Priority::Low, || Ok(1): Result<i64, ()>);
+/// let urgent = background.map(|i| i + 1).with_priority(Priority::High);
+/// assert_eq!(Ok(2), future::await(urgent));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    Normal,
+    High
+}
+
+impl Default for Priority {
+    fn default() -> Priority {
+        Priority::Normal
+    }
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Raises the priority of this `Future`, and of every `Future` it was derived from, to at least
+    /// `priority`.
+    pub fn with_priority(self, priority: Priority) -> Future<A, E> {
+        boost(&self.lock, Some(priority));
+        self
+    }
+
+    /// The priority of this `Future`, or `None` if it has neither been given nor inherited one.
+    pub fn priority(&self) -> Option<Priority> {
+        self.lock.lock().unwrap().priority
+    }
+}
+
+thread_local!(static CURRENT: Cell<Option<Priority>> = Cell::new(None));
+
+/// The priority of the pool job running on this thread, if any.
+pub(crate) fn current() -> Option<Priority> {
+    CURRENT.with(|current| current.get())
+}
+
+/// Runs `f` as a job of the given priority.
+pub(crate) fn run_as<F: FnOnce() -> ()>(priority: Option<Priority>, f: F) {
+    let previous = CURRENT.with(|current| current.replace(priority));
+    f();
+    CURRENT.with(|current| current.set(previous));
+}
+
+/// Records that `downstream` depends on `upstream`, raising `upstream` to its priority.
+pub(crate) fn link(downstream: &Arc<Mutex<Meta>>, upstream: &Arc<Mutex<Meta>>) {
+    let priority = {
+        let mut meta = downstream.lock().unwrap();
+        meta.upstream.push(upstream.clone());
+        meta.priority
+    };
+    boost(upstream, priority);
+}
+
+/// Raises `meta` and everything upstream of it to at least `priority`. Only one lock is held at a
+/// time, since other threads may be locking the same chain in the opposite order.
+pub(crate) fn boost(meta: &Arc<Mutex<Meta>>, priority: Option<Priority>) {
+    let (upstream, pool_job) = {
+        let mut meta = meta.lock().unwrap();
+        if meta.priority >= priority {
+            return;
+        }
+        meta.priority = priority;
+        (meta.upstream.clone(), meta.pool_job.clone())
+    };
+    if let (Some(job), Some(priority)) = (pool_job, priority) {
+        job.raise(priority);
+    }
+    for meta in upstream {
+        boost(&meta, priority);
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, join2, new};
+
+    #[test]
+    fn priority_propagates_to_joined_futures() {
+        let (fa, sa) = new::<i64, ()>();
+        let (fb, sb) = new::<i64, ()>();
+        let fb = fb.with_priority(Priority::Low);
+        let b = fb.lock.clone();
+        let joined = join2(fa, fb).with_priority(Priority::High);
+
+        assert_eq!(b.lock().unwrap().priority, Some(Priority::High));
+        sa.set_result(Ok(1): Result<i64, ()>);
+        sb.set_result(Ok(2): Result<i64, ()>);
+        assert_eq!(await(joined), Ok((1, 2)));
+    }
+}
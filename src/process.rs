@@ -0,0 +1,86 @@
+//! `Future`s of child processes, each managed by its own waiting thread.
+
+use super::{new, stream, Future, FutureStream};
+use std::io::{self, BufRead, BufReader};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::thread;
+
+/// Runs `command` to completion in a new thread, collecting all of its output.
+/// # Examples
+/// ```
+/// use future;
+/// use std::process::Command;
+///
+/// let mut echo = Command::new("echo");
+/// echo.arg("hello");
+/// let output = future::await(future::process::spawn(echo)).unwrap();
+/// assert_eq!(b"hello\n", &output.stdout[..]);
+/// ```
+pub fn spawn(mut command: Command) -> Future<Output, io::Error> {
+    let (future, setter) = new();
+    thread::spawn(move || setter.set_result(command.output()));
+    future
+}
+
+/// Runs `command` in a new thread, streaming its stdout line by line. The returned `Future`
+/// resolves with the exit status once the process exits. If the process can't be started, both
+/// the stream and the `Future` fail.
+/// # Examples
+/// ```
+/// use future;
+/// use std::process::Command;
+///
+/// let mut printf = Command::new("printf");
+/// printf.arg("a\nb\n");
+/// let (lines, status) = future::process::spawn_lines(printf);
+/// assert_eq!(vec!["a", "b"], future::await(lines.collect()).unwrap());
+/// assert!(future::await(status).unwrap().success());
+/// ```
+pub fn spawn_lines(mut command: Command) -> (FutureStream<String, io::Error>, Future<ExitStatus, io::Error>) {
+    let (lines, lines_setter) = stream();
+    let (status, status_setter) = new();
+    command.stdout(Stdio::piped());
+
+    thread::spawn(move || {
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                lines_setter.fail(io::Error::new(e.kind(), e.to_string()));
+                return status_setter.set_result(Err(e): Result<ExitStatus, io::Error>);
+            }
+        };
+
+        let mut read_result = Ok(());
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => lines_setter.send(line),
+                    Err(e) => {
+                        read_result = Err(e);
+                        break;
+                    }
+                }
+            }
+        }
+        match read_result {
+            Ok(()) => lines_setter.close(),
+            Err(e) => lines_setter.fail(e)
+        }
+        status_setter.set_result(child.wait());
+    });
+
+    (lines, status)
+}
+
+mod test {
+    use super::*;
+    use super::super::await;
+    use std::process::Command;
+
+    #[test]
+    fn spawn_fails_for_missing_programs() {
+        let (lines, status) = spawn_lines(Command::new("this-program-does-not-exist"));
+        assert!(await(lines.collect()).is_err());
+        assert!(await(status).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+use super::{new, Future, FutureSetter};
+use std::sync::{Arc, Mutex};
+
+/// Create a new (`FutureWithProgress`, `ProgressSetter`) pair, like `future::new`, where the
+/// setter can also report progress of type `P` before setting the result.
+/// # Examples
+/// ```
+/// use future;
+/// use std::sync::{Arc, Mutex};
+/// use std::thread;
+///
+/// let (transfer, setter) = future::new_with_progress::<u64, String, f64>();
+/// let reported = Arc::new(Mutex::new(vec![]));
+/// let reported2 = reported.clone();
+/// let bytes = transfer
+///     .on_progress(move |p| reported2.lock().unwrap().push(*p))
+///     .into_future();
+///
+/// thread::spawn(move || {
+///     for chunk in 1..5 {
+///         setter.progress(chunk as f64 / 4.0);
+///     }
+///     setter.set_result(Ok(4096): Result<u64, String>);
+/// });
+/// assert_eq!(4096, future::await(bytes).unwrap());
+/// assert_eq!(vec![0.25, 0.5, 0.75, 1.0], *reported.lock().unwrap());
+/// ```
+pub fn new_with_progress<A, E, P>() -> (FutureWithProgress<A, E, P>, ProgressSetter<A, E, P>)
+    where A: 'static, E: 'static, P: Send + 'static
+{
+    let (future, setter) = new();
+    let progress = Arc::new(Mutex::new(ProgressState { latest: None, observers: vec![], done: false }));
+    (
+        FutureWithProgress { future: future, progress: progress.clone() },
+        ProgressSetter { setter: setter, progress: progress }
+    )
+}
+
+/// A `Future` whose producer reports intermediate progress. Created with
+/// `future::new_with_progress`.
+pub struct FutureWithProgress<A, E, P>
+    where A: 'static, E: 'static, P: 'static
+{
+    future: Future<A, E>,
+    progress: Arc<Mutex<ProgressState<P>>>
+}
+
+/// The mechanism by which progress and the result of a `FutureWithProgress` are set.
+pub struct ProgressSetter<A, E, P>
+    where A: 'static, E: 'static, P: 'static
+{
+    setter: FutureSetter<A, E>,
+    progress: Arc<Mutex<ProgressState<P>>>
+}
+
+struct ProgressState<P> {
+    latest: Option<P>,
+    observers: Vec<Box<FnMut(&P) -> () + Send>>,
+    done: bool
+}
+
+impl<A: 'static, E: 'static, P: 'static> FutureWithProgress<A, E, P> {
+    /// Runs `f` on each progress update until the result is set, starting with the latest update
+    /// if there has been one. `f` runs on the thread reporting progress.
+    pub fn on_progress<F>(self, mut f: F) -> FutureWithProgress<A, E, P>
+        where F: FnMut(&P) -> () + Send + 'static
+    {
+        {
+            let mut state = self.progress.lock().unwrap();
+            if !state.done {
+                if let Some(ref latest) = state.latest {
+                    f(latest);
+                }
+                state.observers.push(box f);
+            }
+        }
+        self
+    }
+
+    /// The latest progress update, if any.
+    pub fn latest(&self) -> Option<P>
+        where P: Clone
+    {
+        self.progress.lock().unwrap().latest.clone()
+    }
+
+    /// Returns the `Future` of the result. Observers already added keep receiving progress.
+    pub fn into_future(self) -> Future<A, E> {
+        self.future
+    }
+}
+
+impl<A: 'static, E: 'static, P: 'static> ProgressSetter<A, E, P> {
+    /// Reports progress to every observer. Observers must not report progress themselves.
+    pub fn progress(&self, p: P) {
+        let mut state = self.progress.lock().unwrap();
+        if state.done {
+            return;
+        }
+        for observer in state.observers.iter_mut() {
+            observer(&p);
+        }
+        state.latest = Some(p);
+    }
+
+    /// Sets the result of the associated `FutureWithProgress`, after which no more progress is
+    /// reported.
+    pub fn set_result<E2: Into<E>>(self, result: Result<A, E2>) {
+        {
+            let mut state = self.progress.lock().unwrap();
+            state.done = true;
+            state.observers.clear();
+        }
+        self.setter.set_result(result);
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::await;
+
+    #[test]
+    fn late_observers_see_the_latest_progress() {
+        let (f, setter) = new_with_progress::<(), (), u32>();
+        setter.progress(1);
+        setter.progress(2);
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+        let f = f.on_progress(move |p| seen2.lock().unwrap().push(*p));
+        setter.progress(3);
+        assert_eq!(f.latest(), Some(3));
+
+        setter.set_result(Ok(()): Result<(), ()>);
+        assert_eq!(await(f.into_future()), Ok(()));
+        assert_eq!(*seen.lock().unwrap(), vec![2, 3]);
+    }
+}
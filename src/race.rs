@@ -0,0 +1,277 @@
+use super::{abandon, new, CompletionSink, Future, FutureSetter, Meta, Never};
+use std::sync::{Arc, Mutex};
+
+/// What a race does with the `Future`s that lose it.
+pub enum LoserPolicy<A, E> {
+    /// Let the losers run to completion, discarding their results. This is the default.
+    Drop,
+    /// Abandon the losers still in flight, firing the cancellation of those started with
+    /// `future::run_cancellable`, so their work can stop early.
+    Cancel,
+    /// Deliver the results of the losers to the sink as they resolve, with their index in the race
+    /// as the id, e.g. to release resources they acquired.
+    ForwardTo(CompletionSink<A, E>)
+}
+
+/// Which `Future` wins a race among those that have already resolved when it starts, whose order
+/// of resolution can't be observed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TieBreak {
+    /// The earliest in the order given wins. This is the default.
+    First,
+    /// The latest in the order given wins.
+    Last,
+    /// The earliest success wins, or the earliest failure if none succeeded.
+    PreferSuccess
+}
+
+/// How `race` and `Future::race_result` settle a race.
+/// # Examples
+/// ```
+/// use future;
+/// use future::{CompletionSink, LoserPolicy, RacePolicy, TieBreak};
+///
+/// let released = CompletionSink::new();
+/// let policy = RacePolicy::new()
+///     .losers(LoserPolicy::ForwardTo(released.clone()))
+///     .ties(TieBreak::PreferSuccess);
+///
+/// let primary = future::err::<&str, &str>("connection refused");
+/// let replica = future::value::<&str, &str>("replica");
+/// assert_eq!(Ok("replica"), future::await(primary.race_result(replica, policy)));
+/// assert_eq!(Some((0, Err("connection refused"))), released.recv());
+/// ```
+pub struct RacePolicy<A, E> {
+    losers: LoserPolicy<A, E>,
+    ties: TieBreak
+}
+
+impl<A, E> RacePolicy<A, E> {
+    /// Drops losers, breaking ties in favor of the first `Future`.
+    pub fn new() -> RacePolicy<A, E> {
+        RacePolicy { losers: LoserPolicy::Drop, ties: TieBreak::First }
+    }
+
+    pub fn losers(mut self, losers: LoserPolicy<A, E>) -> RacePolicy<A, E> {
+        self.losers = losers;
+        self
+    }
+
+    pub fn ties(mut self, ties: TieBreak) -> RacePolicy<A, E> {
+        self.ties = ties;
+        self
+    }
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Resolves with the result, success or failure, of whichever of this `Future` and `other`
+    /// resolves first, settling ties and the loser according to `policy`.
+    pub fn race_result(self, other: Future<A, E>, policy: RacePolicy<A, E>) -> Future<A, E> {
+        race(vec![self, other], policy).transform(|result| match result {
+            Ok((_, result)) => result,
+            Err(never) => match never {}
+        })
+    }
+}
+
+/// Resolves with the index and result of whichever of `futures` resolves first, settling ties and
+/// the losers according to `policy`. Any `Future`s already resolved when this is called tie, and
+/// the winner among them is chosen by `policy`'s `TieBreak`; otherwise the first to resolve wins.
+/// # Examples
+/// ```
+/// use future;
+/// use future::{LoserPolicy, RacePolicy};
+/// use std::sync::mpsc::channel;
+///
+/// let (tx, rx) = channel();
+/// let slow = future::run_cancellable(move |token| {
+///     while !token.is_cancelled() {
+///         // some work here
+///     }
+///     tx.send("stopped").unwrap();
+///     Ok(1): Result<i64, ()>
+/// });
+/// let cached = future::value::<i64, ()>(2);
+///
+/// let winner = future::race(vec![slow, cached], RacePolicy::new().losers(LoserPolicy::Cancel));
+/// assert_eq!(Ok((1, Ok(2))), future::await(winner));
+/// assert_eq!("stopped", rx.recv().unwrap());
+/// ```
+/// # Panics
+/// This will panic if `futures` is empty.
+pub fn race<A, E>(futures: Vec<Future<A, E>>, policy: RacePolicy<A, E>) -> Future<(usize, Result<A, E>), Never>
+    where A: 'static, E: 'static
+{
+    assert!(!futures.is_empty(), "race requires at least one Future");
+    let policy = Arc::new(policy);
+    let (winner, setter) = new();
+
+    // Take the results of those already resolved, which run at once.
+    let mut resolved = vec![];
+    let mut pending = vec![];
+    for (i, future) in futures.into_iter().enumerate() {
+        if future.is_resolved() {
+            let slot = Arc::new(Mutex::new(None));
+            let result_slot = slot.clone();
+            future.register(move |result| *result_slot.lock().unwrap() = Some(result));
+            let result = slot.lock().unwrap().take().unwrap();
+            resolved.push((i, result));
+        } else {
+            pending.push((i, future));
+        }
+    }
+
+    if !resolved.is_empty() {
+        let won = match policy.ties {
+            TieBreak::First => 0,
+            TieBreak::Last => resolved.len() - 1,
+            TieBreak::PreferSuccess => resolved.iter().position(|&(_, ref result)| result.is_ok()).unwrap_or(0)
+        };
+        let winning = resolved.remove(won);
+        setter.set_result::<Never>(Ok(winning));
+        for (i, result) in resolved {
+            lose(&policy, i, result);
+        }
+        for (i, future) in pending {
+            match policy.losers {
+                // Dropping an unconsumed `Future` abandons it.
+                LoserPolicy::Cancel => drop(future),
+                _ => {
+                    let policy = policy.clone();
+                    future.register(move |result| lose(&policy, i, result));
+                }
+            }
+        }
+        return winner;
+    }
+
+    let state = Arc::new(Mutex::new(RaceState {
+        setter: Some(setter),
+        in_flight: pending.iter().map(|&(i, ref future)| (i, future.lock.clone())).collect()
+    }));
+    for (i, future) in pending {
+        let state = state.clone();
+        let policy = policy.clone();
+        future.register(move |result| {
+            let (setter, in_flight) = {
+                let mut race = state.lock().unwrap();
+                race.in_flight.retain(|&(j, _)| j != i);
+                match race.setter.take() {
+                    Some(setter) => (setter, race.in_flight.drain(..).collect::<Vec<_>>()),
+                    None => {
+                        drop(race);
+                        return lose(&policy, i, result);
+                    }
+                }
+            };
+            setter.set_result::<Never>(Ok((i, result)));
+            if let LoserPolicy::Cancel = policy.losers {
+                for (_, lock) in in_flight {
+                    abandon(&lock);
+                }
+            }
+        });
+    }
+    winner
+}
+
+/// The result of `select2`: which of the two `Future`s resolved first, with its value.
+///
+/// This is only needed because the two values may have different types. Choosing between two
+/// chains doesn't need it, since every chain is the concrete `Future<A, E>`; an `Either` of
+/// `Future`s was declined for that reason.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B)
+}
+
+/// Resolves with the result of whichever of `left` and `right` resolves first, cancelling the other
+/// as `LoserPolicy::Cancel` does. If both have already resolved, `left` wins.
+/// # Examples
+/// ```
+/// use future;
+/// use future::Either;
+///
+/// let (slow, _setter) = future::new::<i64, String>();
+/// let cached = future::value::<&str, String>("cached");
+/// assert_eq!(Ok(Either::Right("cached")), future::await(future::select2(slow, cached)));
+/// ```
+pub fn select2<A, B, E>(left: Future<A, E>, right: Future<B, E>) -> Future<Either<A, B>, E>
+    where A: 'static, B: 'static, E: 'static
+{
+    let futures = vec![left.map(Either::Left), right.map(Either::Right)];
+    race(futures, RacePolicy::new().losers(LoserPolicy::Cancel)).transform(|result| match result {
+        Ok((_, result)) => result,
+        Err(never) => match never {}
+    })
+}
+
+/// Resolves with the index and result of whichever of `futures` resolves first, cancelling the
+/// rest as `LoserPolicy::Cancel` does. Ties go to the earliest in `futures`; see `race` for other
+/// policies.
+/// # Panics
+/// This will panic if `futures` is empty.
+pub fn select_all<A, E>(futures: Vec<Future<A, E>>) -> Future<(usize, A), E>
+    where A: 'static, E: 'static
+{
+    race(futures, RacePolicy::new().losers(LoserPolicy::Cancel)).transform(|result| match result {
+        Ok((i, result)) => result.map(|a| (i, a)),
+        Err(never) => match never {}
+    })
+}
+
+struct RaceState<A: 'static, E: 'static> {
+    setter: Option<FutureSetter<(usize, Result<A, E>), Never>>,
+    /// The index and meta of each `Future` that hasn't resolved yet
+    in_flight: Vec<(usize, Arc<Mutex<Meta>>)>
+}
+
+/// Applies the loser policy to the result of the `i`th `Future`, which lost the race.
+fn lose<A: 'static, E: 'static>(policy: &RacePolicy<A, E>, i: usize, result: Result<A, E>) {
+    if let LoserPolicy::ForwardTo(ref sink) = policy.losers {
+        sink.deliver(i, result);
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, value};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn first_to_resolve_wins_and_losers_reach_the_sink() {
+        let sink = CompletionSink::new();
+        let (a, a_setter) = new::<i64, ()>();
+        let (b, b_setter) = new::<i64, ()>();
+        let winner = race(vec![a, b], RacePolicy::new().losers(LoserPolicy::ForwardTo(sink.clone())));
+
+        b_setter.set_result(Ok(2): Result<i64, ()>);
+        a_setter.set_result(Ok(1): Result<i64, ()>);
+        assert_eq!(await(winner), Ok((1, Ok(2))));
+        assert_eq!(sink.recv(), Some((0, Ok(1))));
+
+        let (a, a_setter) = new::<i64, ()>();
+        let (b, _b_setter) = new::<i64, ()>();
+        let first = select_all(vec![a, b]);
+        a_setter.set_result(Err(()): Result<i64, ()>);
+        assert_eq!(await(first), Err(()));
+
+        let tied = vec![value::<i64, ()>(1), value(2), value(3)];
+        assert_eq!(await(race(tied, RacePolicy::new().ties(TieBreak::Last))), Ok((2, Ok(3))));
+    }
+
+    #[test]
+    fn select2_cancels_the_loser() {
+        let (slow, _slow_setter) = new::<i64, ()>();
+        let (cancelled, on_cancel) = channel();
+        slow.on_abandoned(move || cancelled.send(()).unwrap());
+        let (fast, fast_setter) = new::<&str, ()>();
+        let first = select2(slow, fast);
+
+        assert!(on_cancel.try_recv().is_err());
+        fast_setter.set_result(Ok("fast"): Result<&str, ()>);
+        assert_eq!(await(first), Ok(Either::Right("fast")));
+        assert_eq!(on_cancel.try_recv(), Ok(()));
+    }
+}
@@ -0,0 +1,150 @@
+//! Low-level primitives for building custom executors and combinators.
+//!
+//! A `RawFutureCell` is a one-shot slot holding either a result or a single waiter, where the
+//! waiter is a plain function pointer and a data pointer rather than a boxed closure. This avoids
+//! an allocation per callback, at the cost of the caller upholding the safety requirements of
+//! `register_waiter`.
+//!
+//! # Examples
+//!
+//! ```
+//! use future;
+//! use future::raw::{self, RawFutureCell};
+//! use std::sync::Arc;
+//!
+//! let cell = Arc::new(RawFutureCell::<i64, ()>::new());
+//! let f = raw::into_future(cell.clone());
+//!
+//! raw::complete_raw(&cell, Ok(5)).unwrap();
+//! assert_eq!(Ok(5), future::await(f));
+//! ```
+
+use super::{new, Future, FutureSetter};
+use std::mem;
+use std::sync::{Arc, Mutex};
+
+/// A function run with the data pointer it was registered with and the result of a
+/// `RawFutureCell`.
+pub type RawWaiter<A, E> = unsafe fn(*mut (), Result<A, E>);
+
+/// A one-shot slot for a result and at most one waiter. See the module documentation.
+pub struct RawFutureCell<A, E> {
+    state: Mutex<RawState<A, E>>
+}
+
+enum RawState<A, E> {
+    Empty,
+    Waiting(RawWaiter<A, E>, *mut ()),
+    Complete(Result<A, E>),
+    Done
+}
+
+// The only non-`Send` contents are waiter data pointers, which `register_waiter` requires to be
+// usable from whichever thread completes the cell.
+unsafe impl<A: Send, E: Send> Send for RawFutureCell<A, E> {}
+unsafe impl<A: Send, E: Send> Sync for RawFutureCell<A, E> {}
+
+impl<A, E> RawFutureCell<A, E> {
+    pub fn new() -> RawFutureCell<A, E> {
+        RawFutureCell { state: Mutex::new(RawState::Empty) }
+    }
+
+    /// Whether a result has been stored or delivered.
+    pub fn is_complete(&self) -> bool {
+        match *self.state.lock().unwrap() {
+            RawState::Complete(_) | RawState::Done => true,
+            _ => false
+        }
+    }
+
+    /// Takes the stored result, if the cell was completed before any waiter was registered.
+    pub fn take(&self) -> Option<Result<A, E>> {
+        let mut state = self.state.lock().unwrap();
+        match mem::replace(&mut *state, RawState::Done) {
+            RawState::Complete(result) => Some(result),
+            other => {
+                *state = other;
+                None
+            }
+        }
+    }
+}
+
+/// Registers `waiter` to be run with `data` and the result of `cell`. If the cell is already
+/// complete, `waiter` runs immediately on the calling thread; otherwise it runs on the thread
+/// calling `complete_raw`. No lock is held while `waiter` runs.
+///
+/// # Safety
+/// `data` must remain valid until `waiter` runs, and must be safe to use from whichever thread
+/// completes the cell. `waiter` is run at most once and is never run if the cell is dropped
+/// without being completed, so any resources owned through `data` leak in that case.
+///
+/// # Panics
+/// This will panic if a waiter is already registered, or if the result has already been taken.
+pub unsafe fn register_waiter<A, E>(cell: &RawFutureCell<A, E>, waiter: RawWaiter<A, E>, data: *mut ()) {
+    let result = {
+        let mut state = cell.state.lock().unwrap();
+        match mem::replace(&mut *state, RawState::Done) {
+            RawState::Empty => {
+                *state = RawState::Waiting(waiter, data);
+                return;
+            },
+            RawState::Complete(result) => result,
+            RawState::Waiting(..) => panic!("A waiter is already registered on this RawFutureCell"),
+            RawState::Done => panic!("The result of this RawFutureCell has already been taken")
+        }
+    };
+    waiter(data, result);
+}
+
+/// Completes `cell` with `result`, running its waiter if one is registered. Returns the rejected
+/// result if the cell was already complete.
+pub fn complete_raw<A, E>(cell: &RawFutureCell<A, E>, result: Result<A, E>) -> Result<(), Result<A, E>> {
+    let (waiter, data) = {
+        let mut state = cell.state.lock().unwrap();
+        match mem::replace(&mut *state, RawState::Done) {
+            RawState::Empty => {
+                *state = RawState::Complete(result);
+                return Ok(());
+            },
+            RawState::Waiting(waiter, data) => (waiter, data),
+            other => {
+                *state = other;
+                return Err(result);
+            }
+        }
+    };
+    unsafe { waiter(data, result) };
+    Ok(())
+}
+
+/// Bridges `cell` into a regular `Future`, registering a waiter that sets the `Future`'s result.
+/// # Panics
+/// This will panic if a waiter is already registered on `cell`.
+pub fn into_future<A, E>(cell: Arc<RawFutureCell<A, E>>) -> Future<A, E>
+    where A: 'static, E: 'static
+{
+    let (future, setter) = new();
+    let data = Box::into_raw(box setter) as *mut ();
+    unsafe { register_waiter(&cell, set_boxed_setter::<A, E>, data) };
+    future
+}
+
+unsafe fn set_boxed_setter<A: 'static, E: 'static>(data: *mut (), result: Result<A, E>) {
+    let setter = Box::from_raw(data as *mut FutureSetter<A, E>);
+    setter.set_result(result);
+}
+
+mod test {
+    use super::*;
+    use super::super::await;
+
+    #[test]
+    fn results_completed_before_registration_are_delivered() {
+        let cell = Arc::new(RawFutureCell::<i64, ()>::new());
+        assert_eq!(complete_raw(&cell, Ok(1)), Ok(()));
+        assert_eq!(complete_raw(&cell, Ok(2)), Err(Ok(2)));
+        assert!(cell.is_complete());
+        assert_eq!(await(into_future(cell)), Ok(1));
+    }
+}
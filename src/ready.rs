@@ -0,0 +1,143 @@
+use super::{done, Future, FutureSetter};
+
+/// Create an already successful `Ready` from an `A`, without the allocations of `future::value`.
+pub fn ready_ok<A, E>(a: A) -> Ready<A, E> {
+    Ready { result: Ok(a) }
+}
+
+/// Create an already failed `Ready` from an `E`, without the allocations of `future::err`.
+pub fn ready_err<A, E>(e: E) -> Ready<A, E> {
+    Ready { result: Err(e) }
+}
+
+/// A result that is available immediately, for the synchronous paths of code returning
+/// `Future`s. Unlike a resolved `Future`, a `Ready` is just its result: no setter, lock or
+/// callback is ever created for it. Returned from `and_thenf`, `rescuef` or `transformf`, it sets
+/// the result directly, and it converts to a `Future` with `IntoFuture` where one is needed.
+/// # Examples
+/// ```
+/// use future;
+/// use future::Future;
+/// use std::collections::HashMap;
+///
+/// let mut cache = HashMap::new();
+/// cache.insert(1, String::from("cached"));
+///
+/// let user = future::value::<u64, String>(1).and_thenf(move |id| match cache.get(&id) {
+///     Some(user) => future::ready_ok(user.clone()),
+///     None => future::ready_err(String::from("not found"))
+/// });
+/// assert_eq!(Ok(String::from("cached")), future::await(user));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ready<A, E> {
+    result: Result<A, E>
+}
+
+impl<A, E> Ready<A, E> {
+    pub fn map<F, B>(self, f: F) -> Ready<B, E>
+        where F: FnOnce(A) -> B
+    {
+        Ready { result: self.result.map(f) }
+    }
+
+    pub fn map_err<F, E2>(self, f: F) -> Ready<A, E2>
+        where F: FnOnce(E) -> E2
+    {
+        Ready { result: self.result.map_err(f) }
+    }
+
+    pub fn and_then<F, B>(self, f: F) -> Ready<B, E>
+        where F: FnOnce(A) -> Result<B, E>
+    {
+        Ready { result: self.result.and_then(f) }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    pub fn into_result(self) -> Result<A, E> {
+        self.result
+    }
+}
+
+/// Conversion into a `Future`, for anything standing in for one: a `Future` itself, a `Ready`,
+/// or a plain `Result`.
+///
+/// It is accepted in place of a `Future` as the return value of the callbacks of `and_thenf`,
+/// `bind`, `rescuef` and `transformf`. The joins, `race` and `select` still take `Future`s only;
+/// call `into_future` to use a `Ready` with them.
+pub trait IntoFuture {
+    type Item: 'static;
+    type Error: 'static;
+
+    fn into_future(self) -> Future<Self::Item, Self::Error>;
+
+    /// Sets the result of `setter` to this, without an intermediate `Future` where possible.
+    fn set_into<E>(self, setter: FutureSetter<Self::Item, E>)
+        where Self::Error: Into<E>, E: 'static;
+}
+
+impl<A: 'static, E: 'static> IntoFuture for Future<A, E> {
+    type Item = A;
+    type Error = E;
+
+    fn into_future(self) -> Future<A, E> {
+        self
+    }
+
+    fn set_into<E2>(self, setter: FutureSetter<A, E2>)
+        where E: Into<E2>, E2: 'static
+    {
+        setter.forward_converted(self)
+    }
+}
+
+impl<A: 'static, E: 'static> IntoFuture for Ready<A, E> {
+    type Item = A;
+    type Error = E;
+
+    fn into_future(self) -> Future<A, E> {
+        done(self.result)
+    }
+
+    fn set_into<E2>(self, setter: FutureSetter<A, E2>)
+        where E: Into<E2>, E2: 'static
+    {
+        setter.set_result(self.result)
+    }
+}
+
+impl<A: 'static, E: 'static> IntoFuture for Result<A, E> {
+    type Item = A;
+    type Error = E;
+
+    fn into_future(self) -> Future<A, E> {
+        done(self)
+    }
+
+    fn set_into<E2>(self, setter: FutureSetter<A, E2>)
+        where E: Into<E2>, E2: 'static
+    {
+        setter.set_result(self)
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, value};
+
+    #[test]
+    fn ready_results_chain_like_futures() {
+        let f = value::<i64, String>(1)
+            .and_thenf(|i| ready_ok::<i64, String>(i + 1).map(|i| i * 10))
+            .rescuef(|_| ready_ok::<i64, String>(0))
+            .transformf(|result| result.map(|i| i + 1));
+        assert_eq!(await(f), Ok(21));
+
+        let f = value::<i64, String>(1).and_thenf(|_| ready_err::<i64, &str>("unknown"));
+        assert_eq!(await(f), Err(String::from("unknown")));
+        assert_eq!(await(ready_ok::<i64, ()>(1).into_future()), Ok(1));
+    }
+}
@@ -0,0 +1,102 @@
+use super::Future;
+use std::sync::Arc;
+
+/// A reusable chain of transformations, defined once and applied to many `Future`s with `apply`.
+/// Since the steps are synchronous, a `Recipe` can be tested directly on `Result`s with `run`,
+/// without constructing any `Future`s. Clones of a `Recipe` share the same steps.
+/// # Examples
+/// ```
+/// use future;
+/// use future::Recipe;
+///
+/// let normalize = Recipe::<String, String, String>::new()
+///     .map(|name| name.trim().to_lowercase())
+///     .and_then(|name| if name.is_empty() { Err(String::from("empty name")) } else { Ok(name) });
+///
+/// assert_eq!(Ok(String::from("ada")), normalize.run(Ok(String::from(" Ada "))));
+/// assert_eq!(Err(String::from("empty name")), normalize.run(Ok(String::from("  "))));
+///
+/// let f = normalize.apply(future::value(String::from("GRACE")));
+/// assert_eq!(Ok(String::from("grace")), future::await(f));
+/// ```
+pub struct Recipe<A, B, E>
+    where A: 'static, B: 'static, E: 'static
+{
+    steps: Arc<Fn(Result<A, E>) -> Result<B, E>>
+}
+
+impl<A: 'static, E: 'static> Recipe<A, A, E> {
+    /// Creates a `Recipe` with no steps, which passes every result through unchanged.
+    pub fn new() -> Recipe<A, A, E> {
+        Recipe { steps: Arc::new(|result| result) }
+    }
+}
+
+impl<A: 'static, B: 'static, E: 'static> Recipe<A, B, E> {
+    /// Adds a step transforming a successful value, like `Future::map`.
+    pub fn map<F, C>(self, f: F) -> Recipe<A, C, E>
+        where F: Fn(B) -> C + 'static,
+              C: 'static
+    {
+        self.transform(move |result| result.map(&f))
+    }
+
+    /// Adds a step transforming a successful value when the transformation can fail, like
+    /// `Future::and_then`.
+    pub fn and_then<F, C>(self, f: F) -> Recipe<A, C, E>
+        where F: Fn(B) -> Result<C, E> + 'static,
+              C: 'static
+    {
+        self.transform(move |result| result.and_then(&f))
+    }
+
+    /// Adds a step recovering from an error, like `Future::rescue`.
+    pub fn rescue<F>(self, f: F) -> Recipe<A, B, E>
+        where F: Fn(E) -> Result<B, E> + 'static
+    {
+        self.transform(move |result| result.or_else(&f))
+    }
+
+    /// Adds a step transforming the result, whatever it is, like `Future::transform`.
+    pub fn transform<F, C>(self, f: F) -> Recipe<A, C, E>
+        where F: Fn(Result<B, E>) -> Result<C, E> + 'static,
+              C: 'static
+    {
+        let steps = self.steps;
+        Recipe { steps: Arc::new(move |result| f(steps(result))) }
+    }
+
+    /// Runs the steps on `result`.
+    pub fn run(&self, result: Result<A, E>) -> Result<B, E> {
+        (self.steps)(result)
+    }
+
+    /// Runs the steps on the result of `future` once it resolves.
+    pub fn apply(&self, future: Future<A, E>) -> Future<B, E> {
+        let steps = self.steps.clone();
+        future.transform(move |result| steps(result))
+    }
+}
+
+impl<A: 'static, B: 'static, E: 'static> Clone for Recipe<A, B, E> {
+    fn clone(&self) -> Recipe<A, B, E> {
+        Recipe { steps: self.steps.clone() }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, err};
+
+    #[test]
+    fn recipes_apply_the_same_steps_to_results_and_futures() {
+        let recipe = Recipe::<i64, i64, String>::new()
+            .and_then(|i| if i < 0 { Err(String::from("negative")) } else { Ok(i) })
+            .rescue(|_| Ok(0))
+            .map(|i| i * 10);
+
+        assert_eq!(recipe.run(Ok(2)), Ok(20));
+        assert_eq!(recipe.run(Ok(-2)), Ok(0));
+        assert_eq!(await(recipe.apply(err(String::from("failed")))), Ok(0));
+    }
+}
@@ -0,0 +1,149 @@
+//! Recycles the `Meta` cells of dropped `Future`s, so that creating a `Future` in steady state
+//! reuses an allocation rather than making a new one. Enabled with the `recycle` feature.
+//!
+//! Each thread keeps its own free list of up to `CAPACITY` cells. A cell is recycled by whichever
+//! of its `Future` and `FutureSetter` is dropped last, and only if nothing else, such as a derived
+//! `Future` or the `registry`, still refers to it. `with_arena` reports how well a piece of work
+//! is served by the free list.
+//!
+//! Only `Meta` cells are recycled. The callback and result cells are typed by the `Future`'s `A`
+//! and `E`, so reusing them would need a free list per type, looked up on every `future::new`.
+
+use super::Meta;
+use std::cell::{Cell, RefCell};
+use std::sync::{Arc, Mutex};
+
+/// The most cells kept per thread
+const CAPACITY: usize = 1024;
+
+thread_local!(static CELLS: RefCell<Vec<Arc<Mutex<Meta>>>> = RefCell::new(vec![]));
+thread_local!(static STATS: Cell<ArenaStats> = Cell::new(ArenaStats::default()));
+
+/// How the cells of the `Future`s created and dropped on one thread were allocated.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// Cells taken from the free list
+    pub reused: usize,
+    /// Cells allocated because the free list was empty
+    pub allocated: usize,
+    /// Cells returned to the free list
+    pub released: usize,
+    /// Cells freed because the free list was full
+    pub discarded: usize,
+    /// Cells left in the free list
+    pub pooled: usize
+}
+
+/// Runs `f`, returning its result along with how the cells of the `Future`s created and dropped
+/// on this thread while it ran were allocated. Cells are recycled whether or not this is used;
+/// it only scopes the stats.
+/// # Examples
+/// ```
+/// use future;
+///
+/// let (sum, stats) = future::with_arena(|| {
+///     (0..100).map(|i| future::await(future::value::<i64, ()>(i)).unwrap()).sum::<i64>()
+/// });
+/// assert_eq!(4950, sum);
+/// assert!(stats.reused >= 99);
+/// ```
+pub fn with_arena<F, R>(f: F) -> (R, ArenaStats)
+    where F: FnOnce() -> R
+{
+    let before = stats();
+    let result = f();
+    let after = stats();
+    (result, ArenaStats {
+        reused: after.reused - before.reused,
+        allocated: after.allocated - before.allocated,
+        released: after.released - before.released,
+        discarded: after.discarded - before.discarded,
+        pooled: after.pooled
+    })
+}
+
+fn stats() -> ArenaStats {
+    let mut stats = STATS.with(|stats| stats.get());
+    stats.pooled = CELLS.with(|cells| cells.borrow().len());
+    stats
+}
+
+fn count<F: FnOnce(&mut ArenaStats)>(f: F) {
+    let _ = STATS.try_with(|stats| {
+        let mut counted = stats.get();
+        f(&mut counted);
+        stats.set(counted);
+    });
+}
+
+/// Returns a cell holding `meta`, reusing a recycled one if this thread has any.
+pub(crate) fn meta_cell(meta: Meta) -> Arc<Mutex<Meta>> {
+    let cell = CELLS.try_with(|cells| cells.borrow_mut().pop()).ok().and_then(|cell| cell);
+    match cell {
+        Some(cell) => {
+            count(|stats| stats.reused += 1);
+            *cell.lock().unwrap() = meta;
+            cell
+        },
+        None => {
+            count(|stats| stats.allocated += 1);
+            Arc::new(Mutex::new(meta))
+        }
+    }
+}
+
+/// Keeps `cell` for reuse if the caller holds the last reference to it.
+pub(crate) fn release(cell: &Arc<Mutex<Meta>>) {
+    if Arc::strong_count(cell) != 1 || Arc::weak_count(cell) != 0 {
+        return;
+    }
+    // Drop what the cell holds now, so that nothing (e.g. abandonment callbacks) outlives the
+    // `Future`. The rest is overwritten when the cell is reused.
+    match cell.lock() {
+        Ok(mut meta) => clear(&mut meta),
+        Err(_) => return
+    }
+    let _ = CELLS.try_with(|cells| {
+        let mut cells = cells.borrow_mut();
+        if cells.len() < CAPACITY {
+            cells.push(cell.clone());
+            count(|stats| stats.released += 1);
+        } else {
+            count(|stats| stats.discarded += 1);
+        }
+    });
+}
+
+/// Drops everything `meta` holds that may capture user state or keep other cells alive.
+fn clear(meta: &mut Meta) {
+    meta.drop_policy = None;
+    meta.upstream.clear();
+    meta.abandoned.clear();
+    meta.trail = None;
+    meta.label = None;
+    meta.pool_job = None;
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, new};
+
+    /// The number of cells this thread has ready for reuse.
+    fn pooled() -> usize {
+        CELLS.with(|cells| cells.borrow().len())
+    }
+
+    #[test]
+    fn cells_are_reused_once_released() {
+        let (f, setter) = new::<i64, ()>();
+        let f = f.with_label("recycled");
+        setter.set_result(Ok(1): Result<i64, ()>);
+        assert_eq!(await(f), Ok(1));
+
+        let pooled_before = pooled();
+        assert!(pooled_before > 0);
+        let (f, _setter) = new::<i64, ()>();
+        assert_eq!(pooled(), pooled_before - 1);
+        assert!(f.lock.lock().unwrap().label.is_none());
+    }
+}
@@ -0,0 +1,106 @@
+//! A registry of the `Future`s still waiting for a result, for spotting leaks in long-running
+//! services. Enabled with the `registry` feature.
+//!
+//! A `Future` is registered until its `FutureSetter` sets a result or is dropped. Only `Future`s
+//! created by `future::new` (or the functions built on it) are counted, not those derived from
+//! them by combinators, so each pending chain counts once.
+
+use super::Meta;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::sync::{Arc, Mutex, Once, Weak, ONCE_INIT};
+
+static NEXT_ENTRY: AtomicUsize = ATOMIC_USIZE_INIT;
+static REGISTRY_INIT: Once = ONCE_INIT;
+static mut REGISTRY: *const Mutex<HashMap<usize, Weak<Mutex<Meta>>>> = 0 as *const _;
+
+/// The pending `Future`s at the time of a call to `snapshot`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The number of pending `Future`s with each label
+    pub by_label: HashMap<String, usize>,
+    /// The number of pending `Future`s without a label
+    pub unlabeled: usize
+}
+
+impl Snapshot {
+    pub fn total(&self) -> usize {
+        self.unlabeled + self.by_label.values().sum::<usize>()
+    }
+}
+
+/// Counts the pending `Future`s by label.
+/// # Examples
+/// ```
+/// use future::{registry, Builder};
+///
+/// let (_f, _setter) = Builder::new().label("fetch_user").build::<i64, ()>();
+/// assert!(registry::snapshot().by_label["fetch_user"] >= 1);
+/// ```
+pub fn snapshot() -> Snapshot {
+    // Collect the entries first: callbacks create `Future`s while holding a `Future`'s lock, so the
+    // registry can't be locked while taking one.
+    let entries = registry().lock().unwrap().values().cloned().collect::<Vec<_>>();
+    let mut snapshot = Snapshot { by_label: HashMap::new(), unlabeled: 0 };
+    for meta in entries.iter().filter_map(|entry| entry.upgrade()) {
+        let meta = meta.lock().unwrap();
+        if meta.depth > 0 {
+            continue;
+        }
+        match meta.label {
+            Some(ref label) => *snapshot.by_label.entry((**label).clone()).or_insert(0) += 1,
+            None => snapshot.unlabeled += 1
+        }
+    }
+    snapshot
+}
+
+/// The number of pending `Future`s.
+pub fn futures_count() -> usize {
+    snapshot().total()
+}
+
+/// Keeps a `Future` in the registry while alive. Owned by the `FutureSetter`.
+pub(crate) struct Entry {
+    id: usize
+}
+
+impl Entry {
+    pub(crate) fn new(meta: &Arc<Mutex<Meta>>) -> Entry {
+        let id = NEXT_ENTRY.fetch_add(1, Ordering::SeqCst);
+        registry().lock().unwrap().insert(id, Arc::downgrade(meta));
+        Entry { id: id }
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        registry().lock().unwrap().remove(&self.id);
+    }
+}
+
+fn registry() -> &'static Mutex<HashMap<usize, Weak<Mutex<Meta>>>> {
+    unsafe {
+        REGISTRY_INIT.call_once(|| {
+            REGISTRY = Box::into_raw(box Mutex::new(HashMap::new()));
+        });
+        &*REGISTRY
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{Builder, Future};
+
+    #[test]
+    fn futures_leave_the_registry_once_resolved() {
+        let builder = Builder::new().label("registry_test");
+        let (f, setter) = builder.build::<i64, ()>();
+        let _mapped: Future<i64, ()> = f.map(|i| i + 1);
+        let (_f2, _setter2) = builder.build::<i64, ()>();
+        assert_eq!(snapshot().by_label.get("registry_test"), Some(&2));
+
+        setter.set_result(Ok(1): Result<i64, ()>);
+        assert_eq!(snapshot().by_label.get("registry_test"), Some(&1));
+    }
+}
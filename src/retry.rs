@@ -0,0 +1,366 @@
+use super::{err, timer, Future, Jitter};
+use super::trace::Span;
+use std::cmp;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::u32;
+
+/// How long to wait between attempts made by `retry`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Backoff {
+    /// Retry immediately
+    None,
+    /// Wait the same amount of time before every retry
+    Constant(Duration),
+    /// Wait `initial` before the first retry, doubling the wait on each subsequent retry up to `max`
+    Exponential { initial: Duration, max: Duration }
+}
+
+impl Backoff {
+    /// The delay before retry number `retry`, where the first retry is 0.
+    pub fn delay(&self, retry: u32) -> Duration {
+        match *self {
+            Backoff::None => Duration::from_millis(0),
+            Backoff::Constant(delay) => delay,
+            Backoff::Exponential { initial, max } => {
+                let factor = 1u32.checked_shl(retry).unwrap_or(u32::MAX);
+                initial.checked_mul(factor).map_or(max, |delay| cmp::min(delay, max))
+            }
+        }
+    }
+}
+
+/// Decides whether, and when, `retry` makes another attempt after a failure.
+///
+/// # Examples
+///
+/// ```
+/// use future::{Backoff, RetryPolicy};
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new(3)
+///     .backoff(Backoff::Exponential {
+///         initial: Duration::from_millis(10),
+///         max: Duration::from_secs(1)
+///     })
+///     .retry_if(|err: &String| err.starts_with("transient"));
+/// ```
+pub struct RetryPolicy<E> {
+    max_attempts: u32,
+    backoff: Backoff,
+    jitter: Jitter,
+    retry_if: Arc<Fn(&E) -> bool + Send + Sync>,
+    budget: Option<RetryBudget>
+}
+
+impl<E: 'static> RetryPolicy<E> {
+    /// A policy making up to `max_attempts` attempts in total, retrying every error immediately.
+    pub fn new(max_attempts: u32) -> RetryPolicy<E> {
+        RetryPolicy {
+            max_attempts: max_attempts,
+            backoff: Backoff::None,
+            jitter: Jitter::None,
+            retry_if: Arc::new(|_: &E| true),
+            budget: None
+        }
+    }
+
+    pub fn backoff(mut self, backoff: Backoff) -> RetryPolicy<E> {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Randomizes each backoff delay, so that clients failing together don't retry together.
+    pub fn jitter(mut self, jitter: Jitter) -> RetryPolicy<E> {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Only retry errors for which `f` returns true.
+    pub fn retry_if<F>(mut self, f: F) -> RetryPolicy<E>
+        where F: Fn(&E) -> bool + Send + Sync + 'static
+    {
+        self.retry_if = Arc::new(f);
+        self
+    }
+
+    /// Only retry while `budget` allows, depositing into it on each success. Share one budget
+    /// between every policy used by a client to bound its retries as a whole.
+    pub fn budget(mut self, budget: RetryBudget) -> RetryPolicy<E> {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Whether to retry after `attempt` (starting at 1) failed with `e`.
+    pub fn should_retry(&self, attempt: u32, e: &E) -> bool {
+        attempt < self.max_attempts && (self.retry_if)(e)
+    }
+
+    /// The delay before the attempt following `attempt` (starting at 1). An `attempt` of 0 is
+    /// treated as 1.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.backoff.delay(attempt.saturating_sub(1))
+    }
+}
+
+impl<E> Clone for RetryPolicy<E> {
+    fn clone(&self) -> RetryPolicy<E> {
+        RetryPolicy {
+            max_attempts: self.max_attempts,
+            backoff: self.backoff,
+            jitter: self.jitter,
+            retry_if: self.retry_if.clone(),
+            budget: self.budget.clone()
+        }
+    }
+}
+
+/// A token bucket limiting the retries made by `retry`, so that a failing dependency isn't met
+/// with a storm of retries from every caller at once. Each retry withdraws a token, and each
+/// success deposits a fraction of one, so retries are bounded to roughly that fraction of
+/// successful requests, plus the initial balance. Clones share the same balance.
+/// # Examples
+/// ```
+/// use future::{RetryBudget, RetryPolicy};
+///
+/// // Allow bursts of up to 10 retries, and one retry per 5 successes after that.
+/// let budget = RetryBudget::new(10, 0.2);
+/// let users = RetryPolicy::<String>::new(3).budget(budget.clone());
+/// let orders = RetryPolicy::<String>::new(3).budget(budget.clone());
+/// assert_eq!(10, budget.metrics().balance);
+/// ```
+#[derive(Clone)]
+pub struct RetryBudget {
+    state: Arc<Mutex<RetryBudgetState>>
+}
+
+struct RetryBudgetState {
+    balance: f64,
+    max_balance: f64,
+    per_success: f64,
+    metrics: RetryBudgetMetrics
+}
+
+/// A snapshot of the counts kept by a `RetryBudget`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryBudgetMetrics {
+    /// The number of whole retries currently allowed
+    pub balance: u32,
+    /// The number of successes deposited
+    pub deposits: u64,
+    /// The number of retries allowed
+    pub withdrawals: u64,
+    /// The number of retries refused for lack of budget
+    pub rejected: u64
+}
+
+impl RetryBudget {
+    /// A budget starting with, and holding at most, `max_retries` retries, replenished by
+    /// `retries_per_success` for each success.
+    pub fn new(max_retries: u32, retries_per_success: f64) -> RetryBudget {
+        RetryBudget {
+            state: Arc::new(Mutex::new(RetryBudgetState {
+                balance: max_retries as f64,
+                max_balance: max_retries as f64,
+                per_success: retries_per_success,
+                metrics: RetryBudgetMetrics { balance: max_retries, deposits: 0, withdrawals: 0, rejected: 0 }
+            }))
+        }
+    }
+
+    /// Records a success, replenishing the budget.
+    pub fn deposit(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.balance = (state.balance + state.per_success).min(state.max_balance);
+        state.metrics.deposits += 1;
+    }
+
+    /// Withdraws a retry, returning whether the budget allowed it.
+    pub fn try_withdraw(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.balance >= 1.0 {
+            state.balance -= 1.0;
+            state.metrics.withdrawals += 1;
+            true
+        } else {
+            state.metrics.rejected += 1;
+            false
+        }
+    }
+
+    pub fn metrics(&self) -> RetryBudgetMetrics {
+        let state = self.state.lock().unwrap();
+        RetryBudgetMetrics { balance: state.balance as u32, ..state.metrics.clone() }
+    }
+}
+
+/// Calls `f` to start an attempt, starting another each time the attempt fails and `policy`
+/// allows a retry. Resolves with the first success, or the error of the final attempt.
+/// # Examples
+/// ```
+/// use future;
+/// use future::RetryPolicy;
+///
+/// let mut attempts = 0;
+/// let f = future::retry(RetryPolicy::new(3), move || {
+///     attempts += 1;
+///     if attempts < 3 { future::err(attempts) } else { future::value(attempts) }
+/// });
+/// assert_eq!(Ok(3), future::await(f));
+/// ```
+pub fn retry<F, A, E>(policy: RetryPolicy<E>, f: F) -> Future<A, E>
+    where F: FnMut() -> Future<A, E>, F: 'static,
+          A: 'static,
+          E: 'static
+{
+    let span = Span::start("retry", None);
+    attempt(Arc::new(Mutex::new(f)), policy, 1, None, span.clone()).traced(span)
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Passes the result through unchanged, except that after an error is passed on it also starts
+    /// `retry(policy, f)` in the background, handing its value to `sink` if it eventually succeeds.
+    /// The "serve stale, refresh asynchronously" pattern: the caller falls back on a stale value at
+    /// once, while a later read finds the refreshed one.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::RetryPolicy;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let cache = Arc::new(Mutex::new(String::from("stale")));
+    /// let cache2 = cache.clone();
+    /// let f = future::err::<String, ()>(())
+    ///     .unwrap_or_spawn_retry_in_background(RetryPolicy::new(3), || future::value(String::from("fresh")),
+    ///                                          move |fresh| *cache2.lock().unwrap() = fresh);
+    ///
+    /// assert_eq!(Err(()), future::await(f));
+    /// assert_eq!("fresh", *cache.lock().unwrap());
+    /// ```
+    pub fn unwrap_or_spawn_retry_in_background<F, S>(self, policy: RetryPolicy<E>, f: F, sink: S) -> Future<A, E>
+        where F: FnMut() -> Future<A, E>, F: 'static,
+              S: FnOnce(A) -> (), S: 'static
+    {
+        let (future, setter) = self.derive();
+        self.register(move |result| {
+            let failed = result.is_err();
+            setter.set_result(result);
+            if failed {
+                retry(policy, f).register(move |result| if let Ok(a) = result {
+                    sink(a)
+                });
+            }
+        });
+        future
+    }
+}
+
+/// Makes attempt `n`, `previous` being the delay before it, if any.
+fn attempt<F, A, E>(f: Arc<Mutex<F>>,
+                    policy: RetryPolicy<E>,
+                    n: u32,
+                    previous: Option<Duration>,
+                    span: Option<Arc<Span>>) -> Future<A, E>
+    where F: FnMut() -> Future<A, E>, F: 'static,
+          A: 'static,
+          E: 'static
+{
+    let mut future = (&mut *f.lock().unwrap())();
+    if let Some(budget) = policy.budget.clone() {
+        future = future.on_success(move |_| budget.deposit());
+    }
+    future.rescuef(move |e| {
+        if !policy.should_retry(n, &e) {
+            return err(e);
+        }
+        if !policy.budget.as_ref().map_or(true, |budget| budget.try_withdraw()) {
+            if let Some(ref span) = span {
+                span.event("retry budget exhausted");
+            }
+            return err(e);
+        }
+        let delay = policy.jitter.apply(policy.delay(n), previous);
+        if let Some(ref span) = span {
+            span.event(&format!("attempt {} failed; retrying after {:?}", n, delay));
+        }
+        if delay == Duration::from_millis(0) {
+            attempt(f, policy, n + 1, Some(delay), span)
+        } else {
+            timer::sleep(delay).transformf(move |_| attempt(f, policy, n + 1, Some(delay), span))
+        }
+    })
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, new, value};
+
+    #[test]
+    fn retry_stops_at_non_retryable_errors() {
+        let mut attempts = 0;
+        let policy = RetryPolicy::new(5).retry_if(|e: &i64| *e < 2);
+        let f = retry(policy, move || -> Future<(), i64> {
+            attempts += 1;
+            err(attempts)
+        });
+        assert_eq!(await(f), Err(2));
+    }
+
+    #[test]
+    fn delays_follow_the_backoff_from_the_first_attempt() {
+        let initial = Duration::from_millis(10);
+        let policy = RetryPolicy::<()>::new(5).backoff(Backoff::Exponential { initial: initial, max: initial * 4 });
+        assert_eq!(policy.delay(0), initial);
+        assert_eq!(policy.delay(1), initial);
+        assert_eq!(policy.delay(3), initial * 4);
+    }
+
+    #[test]
+    fn retry_waits_between_attempts() {
+        let policy = RetryPolicy::new(2).backoff(Backoff::Constant(Duration::from_millis(10)));
+        let mut failed = false;
+        let f = retry(policy, move || if failed { value(()) } else { failed = true; err(()) });
+        assert_eq!(await(f), Ok(()));
+    }
+
+    #[test]
+    fn retry_stops_when_the_budget_is_exhausted() {
+        let budget = RetryBudget::new(1, 0.5);
+        let policy = RetryPolicy::new(5).budget(budget.clone());
+        let f = retry(policy.clone(), || -> Future<(), ()> { err(()) });
+        assert_eq!(await(f), Err(()));
+        assert_eq!(budget.metrics(), RetryBudgetMetrics { balance: 0, deposits: 0, withdrawals: 1, rejected: 1 });
+
+        await(retry(policy.clone(), || value::<(), ()>(()))).unwrap();
+        await(retry(policy, || value::<(), ()>(()))).unwrap();
+        assert_eq!(budget.metrics().balance, 1);
+    }
+
+    #[test]
+    fn background_retries_deliver_to_the_sink() {
+        let (f, setter) = new::<i64, ()>();
+        let refreshed = Arc::new(Mutex::new(None));
+        let refreshed2 = refreshed.clone();
+        let mut attempts = 0;
+        let f = f.unwrap_or_spawn_retry_in_background(RetryPolicy::new(2), move || {
+            attempts += 1;
+            if attempts < 2 { err(()) } else { value(attempts) }
+        }, move |attempts| *refreshed2.lock().unwrap() = Some(attempts));
+
+        setter.set_result(Err(()): Result<i64, ()>);
+        assert_eq!(await(f), Err(()));
+        assert_eq!(*refreshed.lock().unwrap(), Some(2));
+    }
+
+    #[test]
+    fn exponential_backoff_is_capped() {
+        let backoff = Backoff::Exponential {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(50)
+        };
+        assert_eq!(backoff.delay(0), Duration::from_millis(10));
+        assert_eq!(backoff.delay(2), Duration::from_millis(40));
+        assert_eq!(backoff.delay(3), Duration::from_millis(50));
+        assert_eq!(backoff.delay(40), Duration::from_millis(50));
+    }
+}
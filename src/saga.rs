@@ -0,0 +1,176 @@
+use super::{done, err, Future};
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+/// A multi-step operation spanning services that can't share a transaction. Each step takes the
+/// state produced by the previous one, and is paired with a compensation undoing it. If a step
+/// fails, the compensations of the steps already completed run in reverse order, each with the
+/// state its step produced, and the saga fails with a `SagaError` naming the failed step.
+/// # Examples
+/// ```
+/// use future;
+/// use future::Saga;
+///
+/// let saga = Saga::new()
+///     .step("reserve", |order: Vec<&'static str>| future::value(order), |_| future::value(()))
+///     .step("charge", |_| future::err(String::from("card declined")), |_| future::value(()));
+///
+/// let e = future::await(saga.run(vec!["book"])).unwrap_err();
+/// assert_eq!("charge", e.step());
+/// assert_eq!(&String::from("card declined"), e.error());
+/// ```
+pub struct Saga<S: 'static, E: 'static> {
+    steps: Arc<Vec<Step<S, E>>>
+}
+
+struct Step<S: 'static, E: 'static> {
+    name: &'static str,
+    action: Box<Fn(S) -> Future<S, E>>,
+    compensation: Box<Fn(S) -> Future<(), E>>
+}
+
+impl<S: Clone + 'static, E: 'static> Saga<S, E> {
+    pub fn new() -> Saga<S, E> {
+        Saga { steps: Arc::new(vec![]) }
+    }
+
+    /// Adds a step named `name`, which `compensation` undoes.
+    /// # Panics
+    /// This will panic if the `Saga` has been cloned.
+    pub fn step<F, C>(mut self, name: &'static str, action: F, compensation: C) -> Saga<S, E>
+        where F: Fn(S) -> Future<S, E> + 'static,
+              C: Fn(S) -> Future<(), E> + 'static
+    {
+        Arc::get_mut(&mut self.steps).expect("Saga::step called on a cloned Saga").push(Step {
+            name: name,
+            action: box action,
+            compensation: box compensation
+        });
+        self
+    }
+
+    /// Runs the steps in order, starting from `state`, resolving with the final state.
+    pub fn run(&self, state: S) -> Future<S, SagaError<E>> {
+        run_from(self.steps.clone(), 0, state, vec![])
+    }
+}
+
+impl<S: 'static, E: 'static> Clone for Saga<S, E> {
+    fn clone(&self) -> Saga<S, E> {
+        Saga { steps: self.steps.clone() }
+    }
+}
+
+fn run_from<S, E>(steps: Arc<Vec<Step<S, E>>>, i: usize, state: S, mut completed: Vec<S>) -> Future<S, SagaError<E>>
+    where S: Clone + 'static, E: 'static
+{
+    if i == steps.len() {
+        return done(Ok(state));
+    }
+    (steps[i].action)(state).transformf(move |result| match result {
+        Ok(state) => {
+            completed.push(state.clone());
+            run_from(steps, i + 1, state, completed)
+        },
+        Err(e) => {
+            let error = SagaError { step: steps[i].name, error: e, compensation_failures: vec![] };
+            compensate(steps, completed, error)
+        }
+    })
+}
+
+fn compensate<S, E>(steps: Arc<Vec<Step<S, E>>>, mut completed: Vec<S>, mut error: SagaError<E>) -> Future<S, SagaError<E>>
+    where S: Clone + 'static, E: 'static
+{
+    let state = match completed.pop() {
+        Some(state) => state,
+        None => return err(error)
+    };
+    let i = completed.len();
+    (steps[i].compensation)(state).transformf(move |result| {
+        if let Err(e) = result {
+            error.compensation_failures.push((steps[i].name, e));
+        }
+        compensate(steps, completed, error)
+    })
+}
+
+/// The error of a `Saga` whose step failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SagaError<E> {
+    step: &'static str,
+    error: E,
+    compensation_failures: Vec<(&'static str, E)>
+}
+
+impl<E> SagaError<E> {
+    /// The name of the step that failed.
+    pub fn step(&self) -> &'static str {
+        self.step
+    }
+
+    /// The error the step failed with.
+    pub fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// The steps whose compensations failed, with their errors, in the order they were run. The
+    /// effects of these steps may not have been undone.
+    pub fn compensation_failures(&self) -> &[(&'static str, E)] {
+        &self.compensation_failures
+    }
+
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for SagaError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Saga step {} failed: {}", self.step, self.error)?;
+        for &(step, ref e) in &self.compensation_failures {
+            write!(f, "; compensating step {} failed: {}", step, e)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: Error> Error for SagaError<E> {
+    fn description(&self) -> &str {
+        "A step of the saga failed"
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        Some(&self.error)
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, value};
+    use std::sync::Mutex;
+
+    #[test]
+    fn completed_steps_are_compensated_in_reverse() {
+        let undone = Arc::new(Mutex::new(vec![]));
+        let undo = |undone: &Arc<Mutex<Vec<i64>>>| {
+            let undone = undone.clone();
+            move |state: i64| { undone.lock().unwrap().push(state); value::<(), &str>(()) }
+        };
+        let saga = Saga::new()
+            .step("one", |s: i64| value(s + 1), undo(&undone))
+            .step("two", |s: i64| value(s * 10), |_| err("refund failed"))
+            .step("three", |s: i64| value(s + 1), undo(&undone))
+            .step("four", |_| err("out of stock"), undo(&undone));
+
+        let e = await(saga.run(1)).unwrap_err();
+        assert_eq!(e.step(), "four");
+        assert_eq!(e.error(), &"out of stock");
+        assert_eq!(e.compensation_failures(), &[("two", "refund failed")]);
+        assert_eq!(*undone.lock().unwrap(), vec![21, 2]);
+
+        let saga = Saga::new().step("one", |s: i64| value::<i64, ()>(s + 1), |_| value(()));
+        assert_eq!(await(saga.run(1)), Ok(2));
+    }
+}
@@ -0,0 +1,98 @@
+use super::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::usize;
+
+/// Decides which resolutions a sampled observer sees, for collecting metrics from pipelines too
+/// hot to observe every `Future`. Clones share the same count.
+/// # Examples
+/// ```
+/// use future;
+/// use future::Sampler;
+/// use std::sync::{Arc, Mutex};
+///
+/// let sampler = Sampler::one_in(10);
+/// let seen = Arc::new(Mutex::new(0));
+/// for i in 0..100 {
+///     let seen = seen.clone();
+///     future::value::<i64, ()>(i).on_completion_sampled(&sampler, move |_| *seen.lock().unwrap() += 1);
+/// }
+/// assert_eq!(10, *seen.lock().unwrap());
+/// ```
+#[derive(Clone)]
+pub struct Sampler {
+    rate: Rate,
+    state: Arc<AtomicUsize>
+}
+
+#[derive(Clone, Copy)]
+enum Rate {
+    OneIn(usize),
+    Probability(f64)
+}
+
+impl Sampler {
+    /// Samples every `n`th resolution.
+    /// # Panics
+    /// This will panic if `n` is 0.
+    pub fn one_in(n: usize) -> Sampler {
+        assert!(n > 0, "Sampler::one_in requires n > 0");
+        Sampler { rate: Rate::OneIn(n), state: Arc::new(AtomicUsize::new(0)) }
+    }
+
+    /// Samples each resolution with probability `p`.
+    pub fn probability(p: f64) -> Sampler {
+        Sampler { rate: Rate::Probability(p), state: Arc::new(AtomicUsize::new(0)) }
+    }
+
+    /// Whether to sample the next resolution.
+    pub fn sample(&self) -> bool {
+        match self.rate {
+            Rate::OneIn(n) => self.state.fetch_add(1, Ordering::Relaxed) % n == n - 1,
+            Rate::Probability(p) => random(&self.state) < p
+        }
+    }
+}
+
+/// A random number in [0, 1) from the generator whose state is `state`, seeded from the clock if
+/// it's 0. A linear congruential generator is plenty for sampling and jitter. Concurrent callers
+/// may see the same value, which only makes the result slightly less random.
+pub(crate) fn random(state: &AtomicUsize) -> f64 {
+    let mut x = state.load(Ordering::Relaxed);
+    if x == 0 {
+        x = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos() as usize).unwrap_or(1);
+    }
+    let x = x.wrapping_mul(6364136223846793005u64 as usize).wrapping_add(1442695040888963407u64 as usize);
+    state.store(x, Ordering::Relaxed);
+    x as f64 / (usize::MAX as f64 + 1.0)
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Like `on_completion`, except `f` only runs for the resolutions `sampler` selects. Whether
+    /// to sample is decided up front, so unsampled `Future`s are returned untouched.
+    pub fn on_completion_sampled<F>(self, sampler: &Sampler, f: F) -> Future<A, E>
+        where F: FnOnce(&Result<A, E>) -> (), F: 'static
+    {
+        if sampler.sample() {
+            self.on_completion(f)
+        } else {
+            self
+        }
+    }
+}
+
+mod test {
+    use super::*;
+
+    #[test]
+    fn probability_sampling_is_roughly_proportional() {
+        let sampler = Sampler::probability(0.25);
+        let sampled = (0..10000).filter(|_| sampler.sample()).count();
+        assert!(sampled > 2000 && sampled < 3000, "sampled {} of 10000", sampled);
+
+        assert_eq!((0..100).filter(|_| Sampler::probability(0.0).sample()).count(), 0);
+        let always = Sampler::probability(1.0);
+        assert_eq!((0..100).filter(|_| always.sample()).count(), 100);
+    }
+}
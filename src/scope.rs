@@ -0,0 +1,318 @@
+use super::{new, Future};
+use std::boxed::FnBox;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Runs `f` with a `Scope` on which work borrowing from the caller's stack can be spawned, and
+/// returns once all of it has finished. The work can read (or mutate, with the usual
+/// synchronization) data owned by the caller without cloning it into an `Arc`.
+///
+/// `future::run_scoped` returns an ordinary `Future`, whose values must be `'static`. For values
+/// that borrow from the caller too, use `Scope::run`, or `Future::boxed_local` to bring an
+/// ordinary `Future` into the scope; both return a `ScopedFuture`, whose values and callbacks need
+/// only outlive the scope.
+/// # Examples
+/// ```
+/// use future;
+///
+/// let readings = vec![3, 1, 4, 1, 5, 9, 2, 6];
+/// let (low, high) = readings.split_at(readings.len() / 2);
+/// let total = future::scoped(|scope| {
+///     let low = future::run_scoped(scope, || Ok(low.iter().sum()): Result<i64, ()>);
+///     let high = future::run_scoped(scope, || Ok(high.iter().sum()): Result<i64, ()>);
+///     future::await(future::join2(low, high).map(|(l, h)| l + h))
+/// });
+/// assert_eq!(Ok(31), total);
+/// ```
+pub fn scoped<'a, F, R>(f: F) -> R
+    where F: FnOnce(&Scope<'a>) -> R
+{
+    let scope = Scope {
+        threads: RefCell::new(vec![]),
+        adopted: Arc::new((Mutex::new(0), Condvar::new())),
+        _borrows: PhantomData
+    };
+    f(&scope)
+}
+
+/// Work spawned within a call to `future::scoped`, all of which finishes before the call
+/// returns, even if it panics.
+pub struct Scope<'a> {
+    threads: RefCell<Vec<JoinHandle<()>>>,
+    /// The number of `Future`s brought in with `boxed_local` that haven't resolved yet
+    adopted: Arc<(Mutex<usize>, Condvar)>,
+    // Invariant in 'a, so the borrows of spawned work can't be shortened to fit the scope.
+    _borrows: PhantomData<&'a mut &'a ()>
+}
+
+impl<'a> Scope<'a> {
+    /// Runs `f` on a new thread that's joined before the scope ends, so `f` may borrow data that
+    /// outlives the scope. See `future::run_scoped` for a `Future` of the result.
+    pub fn spawn<F>(&self, f: F)
+        where F: FnOnce() -> () + Send + 'a
+    {
+        let job: Box<FnBox() -> () + Send + 'a> = box f;
+        // The thread is joined before the scope ends, so nothing `job` borrows is dropped while
+        // it runs.
+        let job: Box<FnBox() -> () + Send + 'static> = unsafe { mem::transmute(job) };
+        self.threads.borrow_mut().push(thread::spawn(move || job()));
+    }
+
+    /// Runs `f` on a new thread, like `future::run_scoped`, except that the result may borrow
+    /// data that outlives the scope as well.
+    /// # Examples
+    /// ```
+    /// use future;
+    ///
+    /// let words = String::from("the quick red fox");
+    /// let longest = future::scoped(|scope| {
+    ///     let longest = scope.run(|| {
+    ///         words.split(' ').max_by_key(|word| word.len()).ok_or(())
+    ///     });
+    ///     longest.map(|word| &word[..1]).await()
+    /// });
+    /// assert_eq!(Ok("q"), longest);
+    /// ```
+    pub fn run<F, A, E>(&self, f: F) -> ScopedFuture<'a, A, E>
+        where F: FnOnce() -> Result<A, E> + Send + 'a,
+              A: Send + 'a,
+              E: Send + 'a
+    {
+        let (future, setter) = scoped_pair();
+        self.spawn(move || setter.set(f()));
+        future
+    }
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Brings this `Future` into `scope`, so that combinators on it may borrow data that outlives
+    /// the scope. The scope doesn't end until this `Future` resolves, or its `FutureSetter` is
+    /// dropped.
+    pub fn boxed_local<'a>(self, scope: &Scope<'a>) -> ScopedFuture<'a, A, E>
+        where A: Send, E: Send
+    {
+        let (future, setter) = scoped_pair();
+        *scope.adopted.0.lock().unwrap() += 1;
+        let mut adoption = Adoption { setter: Some(setter), _pending: PendingAdoption(scope.adopted.clone()) };
+        let job = move |result| adoption.setter.take().unwrap().set(result);
+        let job: Box<FnBox(Result<A, E>) -> () + 'a> = box job;
+        // The scope waits for `job` to be run or dropped before it ends, so nothing `job`
+        // borrows is dropped while it's alive.
+        let job: Box<FnBox(Result<A, E>) -> () + 'static> = unsafe { mem::transmute(job) };
+        self.register(move |result| job(result));
+        future
+    }
+}
+
+/// Execute function `F` in a new thread, like `future::run`, except that `F` may borrow data
+/// that outlives `scope`. The returned `Future` is resolved before the scope ends.
+pub fn run_scoped<'a, F, A, E>(scope: &Scope<'a>, f: F) -> Future<A, E>
+    where F: FnOnce() -> Result<A, E> + Send + 'a,
+          A: 'static,
+          E: 'static
+{
+    let (future, setter) = new();
+    scope.spawn(move || setter.set_result(f()));
+    future
+}
+
+impl<'a> Drop for Scope<'a> {
+    fn drop(&mut self) {
+        // A panic in spawned work drops its setter, which is reported through its `Future`.
+        for thread in self.threads.borrow_mut().drain(..) {
+            let _ = thread.join();
+        }
+        let (ref pending, ref condvar) = *self.adopted;
+        let mut pending = pending.lock().unwrap();
+        while *pending > 0 {
+            pending = condvar.wait(pending).unwrap();
+        }
+    }
+}
+
+/// A `Future` whose values and callbacks may borrow data that outlives a `Scope`. Created with
+/// `Scope::run` or `Future::boxed_local`.
+pub struct ScopedFuture<'a, A, E>
+    where A: 'a, E: 'a
+{
+    cell: Arc<ScopedCell<'a, A, E>>
+}
+
+/// Sets the result of a `ScopedFuture`. Dropping it unset makes `ScopedFuture::await` panic.
+struct ScopedSetter<'a, A, E>
+    where A: 'a, E: 'a
+{
+    cell: Option<Arc<ScopedCell<'a, A, E>>>
+}
+
+struct ScopedCell<'a, A, E>
+    where A: 'a, E: 'a
+{
+    state: Mutex<ScopedState<'a, A, E>>,
+    condvar: Condvar
+}
+
+enum ScopedState<'a, A, E>
+    where A: 'a, E: 'a
+{
+    Pending,
+    Callback(Box<FnBox(Result<A, E>) -> () + Send + 'a>),
+    Done(Result<A, E>),
+    SetterDropped,
+    Taken
+}
+
+fn scoped_pair<'a, A: 'a, E: 'a>() -> (ScopedFuture<'a, A, E>, ScopedSetter<'a, A, E>) {
+    let cell = Arc::new(ScopedCell { state: Mutex::new(ScopedState::Pending), condvar: Condvar::new() });
+    (ScopedFuture { cell: cell.clone() }, ScopedSetter { cell: Some(cell) })
+}
+
+impl<'a, A: Send + 'a, E: Send + 'a> ScopedFuture<'a, A, E> {
+    /// Like `Future::map`.
+    pub fn map<F, B>(self, f: F) -> ScopedFuture<'a, B, E>
+        where F: FnOnce(A) -> B + Send + 'a,
+              B: Send + 'a
+    {
+        let (future, setter) = scoped_pair();
+        self.resolve(move |result| setter.set(result.map(f)));
+        future
+    }
+
+    /// Like `Future::and_then`.
+    pub fn and_then<F, B>(self, f: F) -> ScopedFuture<'a, B, E>
+        where F: FnOnce(A) -> ScopedFuture<'a, B, E> + Send + 'a,
+              B: Send + 'a
+    {
+        let (future, setter) = scoped_pair();
+        self.resolve(move |result| match result {
+            Ok(a) => f(a).resolve(move |result| setter.set(result)),
+            Err(e) => setter.set(Err(e))
+        });
+        future
+    }
+
+    /// Resolves with both values once both `ScopedFuture`s have, or the first error.
+    pub fn join<B>(self, other: ScopedFuture<'a, B, E>) -> ScopedFuture<'a, (A, B), E>
+        where B: Send + 'a
+    {
+        self.and_then(move |a| other.map(move |b| (a, b)))
+    }
+
+    /// Runs `f` with the result once it's set, on the thread that sets it, or on this thread if
+    /// it's already set.
+    pub fn resolve<F>(self, f: F)
+        where F: FnOnce(Result<A, E>) -> () + Send + 'a
+    {
+        let mut state = self.cell.state.lock().unwrap();
+        match mem::replace(&mut *state, ScopedState::Taken) {
+            ScopedState::Done(result) => {
+                drop(state);
+                f(result)
+            },
+            ScopedState::Pending => *state = ScopedState::Callback(box f),
+            // `f` can never run; dropping it is all that's left.
+            ScopedState::SetterDropped => *state = ScopedState::SetterDropped,
+            ScopedState::Callback(_) | ScopedState::Taken => unreachable!()
+        }
+    }
+
+    /// Blocks until the result is set, like `future::await`.
+    /// # Panics
+    /// This will panic if the work setting the result panicked, or dropped its setter.
+    pub fn await(self) -> Result<A, E> {
+        let mut state = self.cell.state.lock().unwrap();
+        loop {
+            match mem::replace(&mut *state, ScopedState::Taken) {
+                ScopedState::Done(result) => return result,
+                ScopedState::Pending => *state = ScopedState::Pending,
+                ScopedState::SetterDropped => panic!("The setter of a ScopedFuture was dropped without setting a result"),
+                ScopedState::Callback(_) | ScopedState::Taken => unreachable!()
+            }
+            state = self.cell.condvar.wait(state).unwrap();
+        }
+    }
+}
+
+impl<'a, A: 'a, E: 'a> ScopedSetter<'a, A, E> {
+    fn set(mut self, result: Result<A, E>) {
+        let cell = self.cell.take().unwrap();
+        let mut state = cell.state.lock().unwrap();
+        match mem::replace(&mut *state, ScopedState::Taken) {
+            ScopedState::Callback(f) => {
+                drop(state);
+                f(result)
+            },
+            _ => {
+                *state = ScopedState::Done(result);
+                cell.condvar.notify_all();
+            }
+        }
+    }
+}
+
+impl<'a, A: 'a, E: 'a> Drop for ScopedSetter<'a, A, E> {
+    fn drop(&mut self) {
+        if let Some(ref cell) = self.cell {
+            if let Ok(mut state) = cell.state.lock() {
+                // Drops a waiting callback, which can never run.
+                *state = ScopedState::SetterDropped;
+                cell.condvar.notify_all();
+            }
+        }
+    }
+}
+
+/// The callback `Future::boxed_local` registers. Its fields drop in order, so the scope can't end
+/// while the setter, and any callback it holds, is still alive.
+struct Adoption<'a, A: 'a, E: 'a> {
+    setter: Option<ScopedSetter<'a, A, E>>,
+    _pending: PendingAdoption
+}
+
+struct PendingAdoption(Arc<(Mutex<usize>, Condvar)>);
+
+impl Drop for PendingAdoption {
+    fn drop(&mut self) {
+        let (ref pending, ref condvar) = *self.0;
+        *pending.lock().unwrap() -= 1;
+        condvar.notify_all();
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::new;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[test]
+    fn scoped_futures_borrow_from_the_caller() {
+        let names = vec![String::from("ada"), String::from("grace")];
+        let (id, setter) = new::<usize, ()>();
+        let name = scoped(|scope| {
+            let names = &names;
+            let first = scope.run(move || Ok(&names[0][..]));
+            let name = id.boxed_local(scope).map(move |i| &names[i][..]);
+            setter.set_result(Ok(1): Result<usize, ()>);
+            first.join(name).await()
+        });
+        assert_eq!(name, Ok(("ada", "grace")));
+    }
+
+    #[test]
+    fn spawned_work_finishes_before_the_scope_ends() {
+        let finished = Mutex::new(0);
+        scoped(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    thread::sleep(Duration::from_millis(5));
+                    *finished.lock().unwrap() += 1;
+                });
+            }
+        });
+        assert_eq!(*finished.lock().unwrap(), 4);
+    }
+}
@@ -0,0 +1,269 @@
+use super::{stream, Future, FutureStream, FutureStreamSetter};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+
+/// Create a new (`Sequencer`, `FutureStream`) pair, where the stream receives the results of the
+/// futures submitted to the sequencer in order of their sequence numbers, starting from 0,
+/// regardless of the order in which they resolve.
+/// # Examples
+/// ```
+/// use future;
+///
+/// let (sequencer, lines) = future::sequencer::<String, ()>();
+/// let (second, second_setter) = future::new::<String, ()>();
+/// sequencer.submit(1, second);
+/// sequencer.submit(0, future::value(String::from("first")));
+/// sequencer.close();
+///
+/// second_setter.set_result(Ok(String::from("second")): Result<String, ()>);
+/// assert_eq!(Ok(vec![String::from("first"), String::from("second")]), future::await(lines.collect()));
+/// ```
+pub fn sequencer<A, E>() -> (Sequencer<A, E>, FutureStream<A, E>)
+    where A: 'static, E: 'static
+{
+    let (stream, setter) = stream();
+    let state = SequencerState {
+        next: 0,
+        pending: BTreeSet::new(),
+        completed: BTreeMap::new(),
+        submitted: 0,
+        closed: false,
+        dropped: None,
+        setter: Some(setter),
+        flushing: false
+    };
+    (Sequencer { state: Arc::new(Mutex::new(state)) }, stream)
+}
+
+/// Reorders the results of futures produced by parallel work, buffering those that resolve
+/// early. Created with `future::sequencer`; clones submit to the same stream.
+///
+/// Every sequence number from 0 up must be submitted exactly once. The stream fails with the
+/// first error in sequence order, and closes once the sequencer is closed and every submitted
+/// result has been sent. If the `FutureSetter` of a submitted `Future` is dropped, the stream ends
+/// at that sequence number, and its consumer fails as if its own `FutureSetter` had been dropped.
+pub struct Sequencer<A, E>
+    where A: 'static, E: 'static
+{
+    state: Arc<Mutex<SequencerState<A, E>>>
+}
+
+struct SequencerState<A, E>
+    where A: 'static, E: 'static
+{
+    next: u64,
+    /// The sequence numbers submitted whose results haven't arrived
+    pending: BTreeSet<u64>,
+    completed: BTreeMap<u64, Result<A, E>>,
+    submitted: u64,
+    closed: bool,
+    /// The lowest sequence number whose `FutureSetter` was dropped, at which the stream ends
+    dropped: Option<u64>,
+    /// Taken out while a thread is sending results, which it does outside the lock
+    setter: Option<FutureStreamSetter<A, E>>,
+    /// Whether a thread is sending results, which then sends any that arrive meanwhile itself
+    flushing: bool
+}
+
+/// What flushing does next, decided under the lock and done outside it.
+enum Flush<A, E> {
+    Send(A),
+    Fail(E),
+    Abandon,
+    Close
+}
+
+impl<A: 'static, E: 'static> Sequencer<A, E> {
+    /// Submits the `Future` with sequence number `seq`.
+    /// # Panics
+    /// This will panic if `seq` has already been submitted.
+    pub fn submit(&self, seq: u64, future: Future<A, E>) {
+        {
+            let mut state = self.state.lock().unwrap();
+            assert!(seq >= state.next && !state.completed.contains_key(&seq) && state.pending.insert(seq),
+                    "sequence number {} submitted twice", seq);
+            state.submitted += 1;
+        }
+        // Dropped with the callback, which leaves a gap at `seq` if it never runs.
+        let mut pending = PendingSeq { state: self.state.clone(), seq: Some(seq) };
+        future.register(move |result| {
+            pending.seq = None;
+            {
+                let mut state = pending.state.lock().unwrap();
+                state.pending.remove(&seq);
+                state.completed.insert(seq, result);
+            }
+            SequencerState::flush(&pending.state);
+        });
+    }
+
+    /// The number of results that resolved out of order and are waiting for earlier ones.
+    pub fn buffered(&self) -> usize {
+        self.state.lock().unwrap().completed.len()
+    }
+
+    /// Signals that nothing more will be submitted, so the stream closes once every submitted
+    /// result has been sent.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        SequencerState::flush(&self.state);
+    }
+}
+
+impl<A: 'static, E: 'static> SequencerState<A, E> {
+    /// Sends every result that's next in sequence, unless another thread already is. Results are
+    /// sent outside the lock, so the stream's consumer may use the sequencer; only one thread
+    /// sends at a time, so results from different threads can't be sent out of order.
+    fn flush(state: &Mutex<Self>) {
+        let setter = {
+            // A poisoned lock means this is being dropped by an unwinding panic.
+            let mut state = match state.lock() {
+                Ok(state) => state,
+                Err(_) => return
+            };
+            if state.flushing {
+                return;
+            }
+            match state.setter.take() {
+                Some(setter) => {
+                    state.flushing = true;
+                    setter
+                },
+                None => return
+            }
+        };
+        loop {
+            let next = {
+                let mut state = match state.lock() {
+                    Ok(state) => state,
+                    Err(_) => return
+                };
+                let next = state.next;
+                match state.completed.remove(&next) {
+                    Some(result) => {
+                        state.next += 1;
+                        match result {
+                            Ok(a) => Flush::Send(a),
+                            Err(e) => Flush::Fail(e)
+                        }
+                    },
+                    None if state.dropped == Some(next) => Flush::Abandon,
+                    None if state.closed && next == state.submitted => Flush::Close,
+                    None => {
+                        state.setter = Some(setter);
+                        state.flushing = false;
+                        return;
+                    }
+                }
+            };
+            match next {
+                Flush::Send(a) => setter.send(a),
+                Flush::Fail(e) => return setter.fail(e),
+                Flush::Abandon => return setter.abandon(),
+                Flush::Close => return setter.close()
+            }
+        }
+    }
+}
+
+impl<A: 'static, E: 'static> Clone for Sequencer<A, E> {
+    fn clone(&self) -> Sequencer<A, E> {
+        Sequencer { state: self.state.clone() }
+    }
+}
+
+/// A submitted `Future` whose result hasn't arrived.
+struct PendingSeq<A, E>
+    where A: 'static, E: 'static
+{
+    state: Arc<Mutex<SequencerState<A, E>>>,
+    seq: Option<u64>
+}
+
+impl<A: 'static, E: 'static> Drop for PendingSeq<A, E> {
+    fn drop(&mut self) {
+        let seq = match self.seq {
+            Some(seq) => seq,
+            None => return
+        };
+        if let Ok(mut state) = self.state.lock() {
+            state.pending.remove(&seq);
+            if state.dropped.map_or(true, |dropped| seq < dropped) {
+                state.dropped = Some(seq);
+            }
+        } else {
+            return;
+        }
+        SequencerState::flush(&self.state);
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, await_safe, new, value, FutureSetter};
+
+    #[test]
+    fn results_are_sent_in_sequence_order() {
+        let (sequencer, stream) = sequencer::<u64, ()>();
+        let setters = (0..4).map(|seq| {
+            let (future, setter) = new::<u64, ()>();
+            sequencer.submit(seq, future);
+            setter
+        }).collect::<Vec<FutureSetter<u64, ()>>>();
+        sequencer.close();
+
+        for (seq, setter) in setters.into_iter().enumerate().rev() {
+            setter.set_result(Ok(seq as u64): Result<u64, ()>);
+            if seq > 0 {
+                assert_eq!(sequencer.buffered(), 4 - seq);
+            }
+        }
+        assert_eq!(sequencer.buffered(), 0);
+        assert_eq!(await(stream.collect()), Ok(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn dropped_setters_end_the_stream() {
+        let (sequencer, stream) = sequencer::<u64, ()>();
+        let (first, first_setter) = new::<u64, ()>();
+        let (second, second_setter) = new::<u64, ()>();
+        sequencer.submit(0, first);
+        sequencer.submit(1, second);
+        sequencer.close();
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+        let done = stream.for_each(move |i| seen2.lock().unwrap().push(i));
+        first_setter.set_result(Ok(0): Result<u64, ()>);
+        drop(second_setter);
+        assert!(await_safe(done).is_err());
+        assert_eq!(*seen.lock().unwrap(), vec![0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "submitted twice")]
+    fn pending_numbers_cant_be_submitted_again() {
+        let (sequencer, _stream) = sequencer::<u64, ()>();
+        let (first, _first_setter) = new::<u64, ()>();
+        sequencer.submit(0, first);
+        sequencer.submit(0, new::<u64, ()>().0);
+    }
+
+    #[test]
+    fn consumers_can_use_the_sequencer() {
+        let (sequencer, stream) = sequencer::<u64, ()>();
+        let seen = Arc::new(Mutex::new(vec![]));
+        let (seen2, sequencer2) = (seen.clone(), sequencer.clone());
+        let done = stream.for_each(move |i| {
+            seen2.lock().unwrap().push((i, sequencer2.buffered()));
+            if i < 2 {
+                sequencer2.submit(i + 1, value(i + 1));
+            } else {
+                sequencer2.close();
+            }
+        });
+        sequencer.submit(0, value(0));
+        assert_eq!(await(done), Ok(()));
+        assert_eq!(*seen.lock().unwrap(), vec![(0, 0), (1, 0), (2, 0)]);
+    }
+}
@@ -0,0 +1,107 @@
+use super::{timeout, Future};
+use super::trace::Span;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// An asynchronous function from a request to a `Future` of its response. Implemented for
+/// closures, and by `Filter::and_then` for a service wrapped in a filter.
+pub trait Service<Req, Resp: 'static, E: 'static> {
+    fn call(&self, req: Req) -> Future<Resp, E>;
+}
+
+impl<F, Req, Resp, E> Service<Req, Resp, E> for F
+    where F: Fn(Req) -> Future<Resp, E>,
+          Resp: 'static,
+          E: 'static
+{
+    fn call(&self, req: Req) -> Future<Resp, E> {
+        self(req)
+    }
+}
+
+/// Wraps a `Service` with a cross-cutting concern, such as a timeout, retries, logging or auth,
+/// deciding whether, when and how often to pass each request on. Implemented for closures.
+/// # Examples
+/// ```
+/// use future;
+/// use future::{Filter, Future, Service};
+/// use std::sync::Arc;
+///
+/// let lookup = |id: u64| future::value::<String, String>(format!("user {}", id));
+/// let authorize = |id: u64, next: &Arc<Service<u64, String, String>>| {
+///     if id == 0 { future::err(String::from("unauthorized")) } else { next.call(id) }
+/// };
+/// let log = |id: u64, next: &Arc<Service<u64, String, String>>| {
+///     println!("looking up {}", id);
+///     next.call(id)
+/// };
+///
+/// let service = log.and_then(authorize.and_then(lookup));
+/// assert_eq!(Ok(String::from("user 5")), future::await(service.call(5)));
+/// assert_eq!(Err(String::from("unauthorized")), future::await(service.call(0)));
+/// ```
+pub trait Filter<Req, Resp: 'static, E: 'static> {
+    /// Handles `req`, calling `next` to pass it on.
+    fn apply(&self, req: Req, next: &Arc<Service<Req, Resp, E>>) -> Future<Resp, E>;
+
+    /// Wraps `service` in this filter. Filters wrapping the result apply before this one.
+    fn and_then<S>(self, service: S) -> Filtered<Self, Req, Resp, E>
+        where S: Service<Req, Resp, E> + 'static,
+              Self: Sized
+    {
+        Filtered { filter: self, service: Arc::new(service), _types: PhantomData }
+    }
+}
+
+impl<F, Req, Resp, E> Filter<Req, Resp, E> for F
+    where F: Fn(Req, &Arc<Service<Req, Resp, E>>) -> Future<Resp, E>,
+          Resp: 'static,
+          E: 'static
+{
+    fn apply(&self, req: Req, next: &Arc<Service<Req, Resp, E>>) -> Future<Resp, E> {
+        self(req, next)
+    }
+}
+
+/// A `Service` wrapped in a `Filter`. Created with `Filter::and_then`.
+pub struct Filtered<F, Req, Resp: 'static, E: 'static> {
+    filter: F,
+    service: Arc<Service<Req, Resp, E>>,
+    _types: PhantomData<Fn(Req) -> (Resp, E)>
+}
+
+impl<F, Req, Resp, E> Service<Req, Resp, E> for Filtered<F, Req, Resp, E>
+    where F: Filter<Req, Resp, E>,
+          Resp: 'static,
+          E: 'static
+{
+    fn call(&self, req: Req) -> Future<Resp, E> {
+        let span = Span::start("service", None);
+        timeout::with_default_timeout(self.filter.apply(req, &self.service)).traced(span)
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, err, retry, value, RetryPolicy};
+    use std::sync::Mutex;
+
+    #[test]
+    fn filters_can_call_the_service_more_than_once() {
+        let calls = Arc::new(Mutex::new(0));
+        let calls2 = calls.clone();
+        let flaky = move |req: i64| {
+            let mut calls = calls2.lock().unwrap();
+            *calls += 1;
+            if *calls < 3 { err(()) } else { value(req * 2) }
+        };
+        let retrying = |req: i64, next: &Arc<Service<i64, i64, ()>>| {
+            let next = next.clone();
+            retry(RetryPolicy::new(3), move || next.call(req))
+        };
+
+        let service = retrying.and_then(flaky);
+        assert_eq!(await(service.call(4)), Ok(8));
+        assert_eq!(*calls.lock().unwrap(), 3);
+    }
+}
@@ -0,0 +1,90 @@
+use super::{Cancelled, Future, FutureSetter, Never, TimeoutError};
+use std::any::Any;
+
+/// Every way a `Future` can end, as produced by `Future::settle`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Settled<A, E> {
+    Ok(A),
+    Err(E),
+    /// The `FutureSetter` was dropped without setting a result
+    SetterDropped,
+    /// The `Future` failed with a `Cancelled` error
+    Cancelled,
+    /// The `Future` failed with a `TimeoutError`
+    TimedOut
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Resolves with how this `Future` ended, so that every outcome can be handled by one match.
+    /// Errors are reported as `Settled::Cancelled` or `Settled::TimedOut` only if the error type is
+    /// `Cancelled` or `TimeoutError` itself; errors that merely wrap one are `Settled::Err`.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::{Settled, TimeoutError};
+    /// use std::time::Duration;
+    ///
+    /// let (f, setter) = future::new::<i64, TimeoutError>();
+    /// drop(setter);
+    /// assert_eq!(Ok(Settled::SetterDropped), future::await(f.settle()));
+    ///
+    /// let (f, _setter) = future::new::<i64, TimeoutError>();
+    /// match future::await(f.within(Duration::from_millis(10)).settle()) {
+    ///     Ok(Settled::Ok(i)) => println!("Got {}", i),
+    ///     Ok(Settled::TimedOut) => println!("Timed out"),
+    ///     Ok(other) => panic!("Unexpected outcome {:?}", other),
+    ///     Err(never) => match never {}
+    /// }
+    /// ```
+    pub fn settle(self) -> Future<Settled<A, E>, Never> {
+        let (future, setter) = self.derive();
+        let mut guard = SettleGuard { setter: Some(setter) };
+        self.register(move |result| {
+            let setter = guard.setter.take().unwrap();
+            setter.set_result::<Never>(Ok(match result {
+                Ok(a) => Settled::Ok(a),
+                Err(e) => {
+                    if (&e as &Any).is::<Cancelled>() {
+                        Settled::Cancelled
+                    } else if (&e as &Any).is::<TimeoutError>() {
+                        Settled::TimedOut
+                    } else {
+                        Settled::Err(e)
+                    }
+                }
+            }));
+        });
+        future
+    }
+}
+
+/// Held by the callback registered by `settle`, which is dropped unrun if the `FutureSetter` is
+/// dropped without setting a result.
+struct SettleGuard<A: 'static, E: 'static> {
+    setter: Option<FutureSetter<Settled<A, E>, Never>>
+}
+
+impl<A: 'static, E: 'static> Drop for SettleGuard<A, E> {
+    fn drop(&mut self) {
+        if let Some(setter) = self.setter.take() {
+            setter.set_result::<Never>(Ok(Settled::SetterDropped));
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, err, new, value};
+
+    #[test]
+    fn settle_distinguishes_every_outcome() {
+        assert_eq!(await(value::<i64, String>(1).settle()), Ok(Settled::Ok(1)));
+        assert_eq!(await(err::<i64, String>(String::from("failed")).settle()), Ok(Settled::Err(String::from("failed"))));
+        assert_eq!(await(err::<i64, Cancelled>(Cancelled).settle()), Ok(Settled::Cancelled));
+
+        let (f, setter) = new::<i64, String>();
+        let settled = f.settle();
+        drop(setter);
+        assert_eq!(await(settled), Ok(Settled::SetterDropped));
+    }
+}
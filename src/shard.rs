@@ -0,0 +1,259 @@
+use super::{new, Future, FutureSetter, Service};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+/// Dispatches requests to a `Service` one key at a time: each request waits for the previous
+/// request with the same key to resolve before it's started, while requests with different keys
+/// run concurrently. The usual way to serialize writes to each record without serializing all of
+/// them. Clones share the same queues.
+/// # Examples
+/// ```
+/// use future;
+/// use future::ShardedDispatcher;
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+///
+/// let writes = Rc::new(RefCell::new(vec![]));
+/// let writes2 = writes.clone();
+/// let dispatcher = ShardedDispatcher::new(move |(account, amount): (&'static str, i64)| {
+///     let (f, setter) = future::new::<(), ()>();
+///     writes2.borrow_mut().push((account, amount, setter));
+///     f
+/// });
+///
+/// let first = dispatcher.dispatch("alice", ("alice", 10));
+/// let second = dispatcher.dispatch("alice", ("alice", -5));
+/// let other = dispatcher.dispatch("bob", ("bob", 7));
+/// // The second write to alice waits for the first.
+/// assert_eq!(2, writes.borrow().len());
+///
+/// let (_, _, setter) = writes.borrow_mut().remove(0);
+/// setter.set_result(Ok(()): Result<(), ()>);
+/// assert_eq!(Ok(()), future::await(first));
+/// assert_eq!(("alice", -5), { let w = writes.borrow(); (w[1].0, w[1].1) });
+/// ```
+pub struct ShardedDispatcher<K, Req, Resp, E>
+    where K: 'static, Req: 'static, Resp: 'static, E: 'static
+{
+    service: Arc<Service<Req, Resp, E>>,
+    shards: Arc<Mutex<HashMap<K, Shard<Req, Resp, E>>>>
+}
+
+/// The requests for one key, kept until none is queued or in flight.
+struct Shard<Req, Resp, E>
+    where Req: 'static, Resp: 'static, E: 'static
+{
+    queue: VecDeque<(Req, FutureSetter<Resp, E>)>,
+    in_flight: bool,
+    /// Whether a thread is starting requests for this key, which then starts the next request
+    /// itself when one resolves immediately
+    pumping: bool
+}
+
+impl<K, Req, Resp, E> ShardedDispatcher<K, Req, Resp, E>
+    where K: Hash + Eq + Clone + 'static,
+          Req: 'static, Resp: 'static, E: 'static
+{
+    pub fn new<S>(service: S) -> ShardedDispatcher<K, Req, Resp, E>
+        where S: Service<Req, Resp, E> + 'static
+    {
+        ShardedDispatcher { service: Arc::new(service), shards: Arc::new(Mutex::new(HashMap::new())) }
+    }
+
+    /// Calls the service with `req` once every request dispatched before it with the same key has
+    /// resolved.
+    pub fn dispatch(&self, key: K, req: Req) -> Future<Resp, E> {
+        let (future, setter) = new();
+        {
+            let mut shards = self.shards.lock().unwrap();
+            let shard = match shards.entry(key.clone()) {
+                Entry::Occupied(shard) => shard.into_mut(),
+                Entry::Vacant(shard) => shard.insert(Shard { queue: VecDeque::new(), in_flight: false, pumping: false })
+            };
+            shard.queue.push_back((req, setter));
+        }
+        self.pump(&key);
+        future
+    }
+
+    /// The number of keys with a request in flight.
+    pub fn active_keys(&self) -> usize {
+        self.shards.lock().unwrap().len()
+    }
+
+    /// The number of requests waiting behind the one in flight for `key`.
+    pub fn queued(&self, key: &K) -> usize {
+        self.shards.lock().unwrap().get(key).map_or(0, |shard| shard.queue.len())
+    }
+
+    /// Starts the next request for `key` unless one is in flight, and removes the shard once
+    /// nothing is queued. Requests that resolve immediately leave starting the next one to this
+    /// loop, rather than recursing once per queued request.
+    fn pump(&self, key: &K) {
+        loop {
+            let (req, setter) = {
+                let mut shards = self.shards.lock().unwrap();
+                let next = match shards.get_mut(key) {
+                    Some(ref shard) if shard.in_flight => {
+                        return;
+                    },
+                    Some(shard) => match shard.queue.pop_front() {
+                        Some(next) => {
+                            shard.in_flight = true;
+                            shard.pumping = true;
+                            Some(next)
+                        },
+                        None => None
+                    },
+                    None => return
+                };
+                match next {
+                    Some(next) => next,
+                    None => {
+                        shards.remove(key);
+                        return;
+                    }
+                }
+            };
+
+            // Dropped with the callback, which ends the request whether or not it runs.
+            let mut call = ShardCall { dispatcher: self.clone(), key: key.clone(), setter: Some(setter) };
+            self.service.call(req).register(move |result| {
+                if let Some(setter) = call.setter.take() {
+                    setter.set_result(result);
+                }
+            });
+
+            let mut shards = self.shards.lock().unwrap();
+            match shards.get_mut(key) {
+                Some(ref mut shard) if shard.in_flight => {
+                    shard.pumping = false;
+                    return;
+                },
+                _ => {}
+            }
+        }
+    }
+
+    /// Ends the request in flight for `key`, starting the next unless a pump is running.
+    fn finish(&self, key: &K) {
+        let pumping = {
+            let mut shards = match self.shards.lock() {
+                Ok(shards) => shards,
+                Err(_) => return
+            };
+            match shards.get_mut(key) {
+                Some(shard) => {
+                    shard.in_flight = false;
+                    shard.pumping
+                },
+                None => return
+            }
+        };
+        if !pumping {
+            self.pump(key);
+        }
+    }
+}
+
+/// A request in flight. If the service's `FutureSetter` is dropped, the request's own setter is
+/// dropped too, and the next request for the key is started regardless.
+struct ShardCall<K, Req, Resp, E>
+    where K: Hash + Eq + Clone + 'static, Req: 'static, Resp: 'static, E: 'static
+{
+    dispatcher: ShardedDispatcher<K, Req, Resp, E>,
+    key: K,
+    setter: Option<FutureSetter<Resp, E>>
+}
+
+impl<K, Req, Resp, E> Drop for ShardCall<K, Req, Resp, E>
+    where K: Hash + Eq + Clone + 'static, Req: 'static, Resp: 'static, E: 'static
+{
+    fn drop(&mut self) {
+        drop(self.setter.take());
+        self.dispatcher.finish(&self.key);
+    }
+}
+
+impl<K, Req, Resp, E> Clone for ShardedDispatcher<K, Req, Resp, E>
+    where K: 'static, Req: 'static, Resp: 'static, E: 'static
+{
+    fn clone(&self) -> ShardedDispatcher<K, Req, Resp, E> {
+        ShardedDispatcher { service: self.service.clone(), shards: self.shards.clone() }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, await_safe, value};
+    use std::cell::RefCell;
+
+    #[test]
+    fn keys_are_serialized_independently() {
+        let setters = Arc::new(Mutex::new(vec![]));
+        let setters2 = setters.clone();
+        let dispatcher = ShardedDispatcher::new(move |req: u32| {
+            let (f, setter) = new::<(), ()>();
+            setters2.lock().unwrap().push(setter);
+            f.map(move |()| req)
+        });
+
+        let a1 = dispatcher.dispatch('a', 1);
+        let a2 = dispatcher.dispatch('a', 2);
+        let b1 = dispatcher.dispatch('b', 3);
+        assert_eq!(dispatcher.active_keys(), 2);
+        assert_eq!(dispatcher.queued(&'a'), 1);
+        assert_eq!(setters.lock().unwrap().len(), 2);
+
+        let b_setter: FutureSetter<(), ()> = setters.lock().unwrap().pop().unwrap();
+        b_setter.set_result(Ok(()): Result<(), ()>);
+        assert_eq!(await(b1), Ok(3));
+        assert_eq!(dispatcher.active_keys(), 1);
+
+        for _ in 0..2 {
+            let a_setter: FutureSetter<(), ()> = setters.lock().unwrap().remove(0);
+            a_setter.set_result(Ok(()): Result<(), ()>);
+        }
+        assert_eq!(await(a1), Ok(1));
+        assert_eq!(await(a2), Ok(2));
+        assert_eq!(dispatcher.active_keys(), 0);
+    }
+
+    #[test]
+    fn dropped_setters_start_the_next_request() {
+        let setters = Arc::new(Mutex::new(vec![]));
+        let setters2 = setters.clone();
+        let dispatcher = ShardedDispatcher::new(move |req: u32| {
+            let (f, setter) = new::<u32, ()>();
+            setters2.lock().unwrap().push(setter);
+            f.map(move |_| req)
+        });
+
+        let first = dispatcher.dispatch('a', 1);
+        let second = dispatcher.dispatch('a', 2);
+        drop(setters.lock().unwrap().remove(0));
+        assert!(await_safe(first).is_err());
+        let setter = setters.lock().unwrap().remove(0);
+        setter.set_result(Ok(0): Result<u32, ()>);
+        assert_eq!(await(second), Ok(2));
+        assert_eq!(dispatcher.active_keys(), 0);
+    }
+
+    #[test]
+    fn queues_of_resolved_requests_dont_recurse() {
+        let (blocker, blocker_setter) = new::<u32, ()>();
+        let blocker = RefCell::new(Some(blocker));
+        let dispatcher = ShardedDispatcher::new(move |req: u32| match blocker.borrow_mut().take() {
+            Some(blocker) => blocker,
+            None => value(req)
+        });
+
+        let calls = (0..100000).map(|i| dispatcher.dispatch('a', i)).collect::<Vec<_>>();
+        assert_eq!(dispatcher.queued(&'a'), 99999);
+        blocker_setter.set_result(Ok(0): Result<u32, ()>);
+        assert_eq!(await(calls.into_iter().last().unwrap()), Ok(99999));
+        assert_eq!(dispatcher.active_keys(), 0);
+    }
+}
@@ -0,0 +1,152 @@
+//! `Future`s of Unix signals, for composing graceful shutdown with the rest of this crate. Enabled
+//! with the `signal` feature.
+//!
+//! Handlers are installed the first time a signal is asked for, and write the signal number to a
+//! pipe read by a single "future-signal" thread, which resolves the waiting `Future`s and streams.
+//! Callbacks added to them run on that thread. Once installed, a handler stays installed, so the
+//! signal's default action (e.g. exiting on `SIGTERM`) no longer happens.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use future;
+//! use future::signal::{self, SIGTERM};
+//!
+//! let shutdown = signal::signal(SIGTERM).on_success(|_| println!("Shutting down"));
+//! future::await(shutdown).unwrap();
+//! ```
+
+use super::{new, stream, Future, FutureSetter, FutureStream, FutureStreamSetter, Never};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::os::raw::{c_int, c_void};
+use std::os::unix::io::FromRawFd;
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+use std::thread;
+
+pub const SIGHUP: c_int = 1;
+pub const SIGINT: c_int = 2;
+pub const SIGQUIT: c_int = 3;
+pub const SIGTERM: c_int = 15;
+
+extern "C" {
+    #[link_name = "signal"]
+    fn install(signum: c_int, handler: usize) -> usize;
+    fn pipe(fds: *mut c_int) -> c_int;
+    fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+}
+
+/// Returns a `Future` that resolves the next time the process receives `signal`.
+/// # Panics
+/// This will panic if a handler can't be installed for `signal`, e.g. for `SIGKILL`.
+pub fn signal(signal: c_int) -> Future<(), Never> {
+    let (future, setter) = new();
+    let watcher = watcher();
+    let mut state = watcher.lock().unwrap();
+    state.install(signal);
+    state.once.entry(signal).or_insert_with(Vec::new).push(setter);
+    future
+}
+
+/// Returns a `FutureStream` receiving a value every time the process receives `signal`. The
+/// stream never closes.
+/// # Panics
+/// This will panic if a handler can't be installed for `signal`, e.g. for `SIGKILL`.
+pub fn signals(signal: c_int) -> FutureStream<(), Never> {
+    let (stream, setter) = stream();
+    let watcher = watcher();
+    let mut state = watcher.lock().unwrap();
+    state.install(signal);
+    state.streams.entry(signal).or_insert_with(Vec::new).push(Arc::new(setter));
+    stream
+}
+
+struct WatcherState {
+    installed: HashSet<c_int>,
+    once: HashMap<c_int, Vec<FutureSetter<(), Never>>>,
+    streams: HashMap<c_int, Vec<Arc<FutureStreamSetter<(), Never>>>>
+}
+
+impl WatcherState {
+    fn install(&mut self, signal: c_int) {
+        if self.installed.insert(signal) {
+            const SIG_ERR: usize = !0;
+            let previous = unsafe { install(signal, handle as usize) };
+            assert!(previous != SIG_ERR, "Unable to install a handler for signal {}", signal);
+        }
+    }
+}
+
+static mut WRITE_FD: c_int = -1;
+
+/// Runs in signal context, so only does the async-signal-safe work of writing to the pipe.
+extern "C" fn handle(signal: c_int) {
+    let byte = signal as u8;
+    unsafe { write(WRITE_FD, &byte as *const u8 as *const c_void, 1) };
+}
+
+static WATCHER_INIT: Once = ONCE_INIT;
+static mut WATCHER: *const Mutex<WatcherState> = 0 as *const _;
+
+fn watcher() -> &'static Mutex<WatcherState> {
+    unsafe {
+        WATCHER_INIT.call_once(|| {
+            let mut fds = [0 as c_int; 2];
+            assert!(pipe(fds.as_mut_ptr()) == 0, "Unable to create the signal pipe");
+            WRITE_FD = fds[1];
+            WATCHER = Box::into_raw(box Mutex::new(WatcherState {
+                installed: HashSet::new(),
+                once: HashMap::new(),
+                streams: HashMap::new()
+            }));
+            let pipe = File::from_raw_fd(fds[0]);
+            thread::Builder::new()
+                .name(String::from("future-signal"))
+                .spawn(move || watch(pipe, &*WATCHER))
+                .unwrap();
+        });
+        &*WATCHER
+    }
+}
+
+fn watch(mut pipe: File, watcher: &'static Mutex<WatcherState>) {
+    let mut byte = [0u8; 1];
+    while let Ok(1) = pipe.read(&mut byte) {
+        let signal = byte[0] as c_int;
+        let (once, streams) = {
+            let mut state = watcher.lock().unwrap();
+            let once = state.once.remove(&signal).unwrap_or_default();
+            let streams = state.streams.get(&signal).cloned().unwrap_or_default();
+            (once, streams)
+        };
+        for setter in streams {
+            setter.send(());
+        }
+        for setter in once {
+            setter.set_result(Ok(()): Result<(), Never>);
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::await;
+    use std::sync::{Arc, Mutex};
+
+    extern "C" {
+        fn raise(signum: c_int) -> c_int;
+    }
+
+    #[test]
+    fn raised_signals_resolve_futures_and_streams() {
+        let received = Arc::new(Mutex::new(0));
+        let received2 = received.clone();
+        let _done = signals(SIGHUP).for_each(move |_| *received2.lock().unwrap() += 1);
+        let next = signal(SIGHUP);
+
+        unsafe { raise(SIGHUP) };
+        assert_eq!(await(next), Ok(()));
+        assert_eq!(*received.lock().unwrap(), 1);
+    }
+}
@@ -0,0 +1,412 @@
+use super::{new, Future, FutureSetter};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A handle on a sequence of values produced asynchronously, terminated either by the producer
+/// closing the stream or by an error.
+///
+/// Values sent before a consumer is attached are buffered, and are delivered in order once one of
+/// the consuming methods (`for_each`, `fold`, `collect`) is called.
+///
+/// # Examples
+///
+/// ```
+/// use future;
+/// use std::thread;
+///
+/// let (stream, setter) = future::stream::<i64, String>();
+/// thread::spawn(move || {
+///     for i in 0..3 {
+///         setter.send(i);
+///     }
+///     setter.close();
+/// });
+///
+/// let sum = stream.map(|i| i * 2).fold(0, |acc, i| acc + i);
+/// assert_eq!(6, future::await(sum).unwrap());
+/// ```
+pub struct FutureStream<A, E>
+    where A: 'static, E: 'static
+{
+    inner: Arc<Mutex<StreamInner<A, E>>>
+}
+
+/// The mechanism by which values are sent to a `FutureStream`.
+pub struct FutureStreamSetter<A, E>
+    where A: 'static, E: 'static
+{
+    inner: Arc<Mutex<StreamInner<A, E>>>
+}
+
+struct StreamInner<A, E>
+    where A: 'static, E: 'static
+{
+    /// Values not yet handed to the consumer
+    buffer: VecDeque<A>,
+    closed: bool,
+    /// Whether the stream ended without a result, which fails its consumer's `Future` as a dropped
+    /// `FutureSetter` does
+    abandoned: bool,
+    /// How the stream ended, once it has, until the consumer has been handed every value
+    end: Option<Result<(), E>>,
+    /// Taken out while a thread is handing it values, which it does outside the lock. It runs on
+    /// whichever thread sends to the stream, so it must be `Send`.
+    consumer: Option<Box<FnMut(A) -> () + Send>>,
+    end_setter: Option<FutureSetter<(), E>>
+}
+
+/// Create a new (`FutureStream`, `FutureStreamSetter`) pair, by which the `FutureStreamSetter` is
+/// the mechanism to send values to the `FutureStream`
+pub fn stream<A, E>() -> (FutureStream<A, E>, FutureStreamSetter<A, E>)
+    where A: 'static, E: 'static
+{
+    let inner = Arc::new(Mutex::new(StreamInner {
+        buffer: VecDeque::new(),
+        closed: false,
+        abandoned: false,
+        end: None,
+        consumer: None,
+        end_setter: None
+    }));
+    (FutureStream { inner: inner.clone() }, FutureStreamSetter { inner: inner })
+}
+
+/// Drain the blocking iterator `iter` in a new thread, sending each value to the returned
+/// `FutureStream`. The stream fails with the first `Err` produced by the iterator, and closes once
+/// the iterator is exhausted. Use `collect` on the result to get a `Future<Vec<A>, E>` instead.
+/// # Examples
+/// ```
+/// use future;
+///
+/// let lines = vec!["1", "2", "3"].into_iter().map(|s| s.parse::<i64>());
+/// let numbers = future::from_iter_blocking(lines).collect();
+/// assert_eq!(vec![1, 2, 3], future::await(numbers).unwrap());
+/// ```
+pub fn from_iter_blocking<I, A, E>(iter: I) -> FutureStream<A, E>
+    where I: IntoIterator<Item = Result<A, E>> + Send + 'static,
+          A: Send + 'static,
+          E: Send + 'static
+{
+    let (stream, setter) = stream();
+    thread::spawn(move || {
+        for item in iter {
+            match item {
+                Ok(a) => setter.send(a),
+                Err(e) => return setter.fail(e)
+            }
+        }
+        setter.close();
+    });
+    stream
+}
+
+/// Interleave the values of `a` and `b` in the order they arrive. The merged stream closes once
+/// both streams have, and fails as soon as either does.
+/// # Examples
+/// ```
+/// use future;
+///
+/// let (a, a_setter) = future::stream::<i64, ()>();
+/// let (b, b_setter) = future::stream::<i64, ()>();
+/// let merged = future::merge(a, b).collect();
+///
+/// a_setter.send(1);
+/// b_setter.send(2);
+/// a_setter.send(3);
+/// a_setter.close();
+/// b_setter.close();
+/// assert_eq!(vec![1, 2, 3], future::await(merged).unwrap());
+/// ```
+pub fn merge<A, E>(a: FutureStream<A, E>, b: FutureStream<A, E>) -> FutureStream<A, E>
+    where A: Send + 'static, E: Send + 'static
+{
+    select_stream(vec![a, b])
+}
+
+/// Like `merge`, for any number of streams. The merged stream closes immediately if `streams` is
+/// empty.
+pub fn select_stream<A, E>(streams: Vec<FutureStream<A, E>>) -> FutureStream<A, E>
+    where A: Send + 'static, E: Send + 'static
+{
+    let (merged, setter) = stream();
+    if streams.is_empty() {
+        setter.close();
+        return merged;
+    }
+
+    // Dropped once every input has ended, which abandons the merged stream unless it was ended
+    // already, e.g. if an input's setter was dropped.
+    let setter = Arc::new(setter);
+    let remaining = Arc::new(Mutex::new(streams.len()));
+    for stream in streams {
+        let (inner, end) = (setter.inner.clone(), setter.clone());
+        let remaining = remaining.clone();
+        stream.for_each(move |a| push(&inner, a))
+            .register(move |result| {
+                let last = {
+                    let mut remaining = remaining.lock().unwrap();
+                    *remaining -= 1;
+                    *remaining == 0
+                };
+                if result.is_err() || last {
+                    end.finish(result);
+                }
+            });
+    }
+    merged
+}
+
+impl<A: 'static, E: 'static> FutureStream<A, E> {
+    /// Runs `f` on each value of the stream as it arrives. The returned `Future` resolves once the
+    /// stream is closed, or with the error the stream failed with. This consumes the stream.
+    pub fn for_each<F>(self, f: F) -> Future<(), E>
+        where F: FnMut(A) -> () + Send + 'static
+    {
+        let (future, setter) = new();
+        {
+            let mut inner = self.inner.lock().unwrap();
+            inner.consumer = Some(box f);
+            inner.end_setter = Some(setter);
+        }
+        deliver(&self.inner);
+        future
+    }
+
+    /// Transform each value of the stream.
+    pub fn map<F, B>(self, mut f: F) -> FutureStream<B, E>
+        where F: FnMut(A) -> B + Send + 'static,
+              B: Send + 'static,
+              E: Send
+    {
+        let (stream, setter) = stream();
+        let inner = setter.inner.clone();
+        self.for_each(move |a| push(&inner, f(a)))
+            .register(move |result| setter.finish(result));
+        stream
+    }
+
+    /// Reduce the stream into a single value, resolving once the stream is closed.
+    pub fn fold<F, B>(self, init: B, mut f: F) -> Future<B, E>
+        where F: FnMut(B, A) -> B + Send + 'static,
+              B: Send + 'static
+    {
+        let acc = Arc::new(Mutex::new(Some(init)));
+        let acc2 = acc.clone();
+        self.for_each(move |a| {
+            let mut acc = acc2.lock().unwrap();
+            let b = acc.take().unwrap();
+            *acc = Some(f(b, a));
+        }).map(move |_| acc.lock().unwrap().take().unwrap())
+    }
+
+    /// Collect every value of the stream, resolving once the stream is closed.
+    pub fn collect(self) -> Future<Vec<A>, E>
+        where A: Send
+    {
+        self.fold(vec![], |mut acc, a| {
+            acc.push(a);
+            acc
+        })
+    }
+}
+
+impl<A: 'static, E: 'static> FutureStreamSetter<A, E> {
+    /// Sends a value to the associated `FutureStream`.
+    pub fn send(&self, a: A) {
+        push(&self.inner, a);
+    }
+
+    /// Closes the associated `FutureStream`, signaling that no more values will be sent.
+    pub fn close(self) {
+        self.finish(Ok(()));
+    }
+
+    /// Fails the associated `FutureStream` with `e`. Values already sent are still delivered.
+    pub fn fail<E2: Into<E>>(self, e: E2) {
+        self.finish(Err(e.into()));
+    }
+
+    fn finish(&self, result: Result<(), E>) {
+        finish(&self.inner, result);
+    }
+
+    /// Ends the associated `FutureStream` without a result, once the values already sent are
+    /// delivered, so that its consumer's `Future` fails as if its `FutureSetter` were dropped.
+    pub(crate) fn abandon(self) {
+        abandon(&self.inner);
+    }
+}
+
+impl<A: 'static, E: 'static> Drop for FutureStreamSetter<A, E> {
+    /// Abandons the stream unless it was closed or failed.
+    fn drop(&mut self) {
+        abandon(&self.inner);
+    }
+}
+
+impl<A: 'static, E: 'static> Future<FutureStream<A, E>, E> {
+    /// Flatten a `Future<FutureStream<A, E>, E>` into a `FutureStream<A, E>`, which fails if the
+    /// outer `Future` does.
+    pub fn flatten_stream(self) -> FutureStream<A, E>
+        where A: Send, E: Send
+    {
+        let (stream, setter) = stream();
+        self.register(move |result| match result {
+            Ok(inner) => {
+                let inner_setter = setter.inner.clone();
+                inner.for_each(move |a| push(&inner_setter, a))
+                    .register(move |result| setter.finish(result));
+            },
+            Err(e) => setter.fail(e)
+        });
+        stream
+    }
+}
+
+fn push<A, E>(inner: &Mutex<StreamInner<A, E>>, a: A) {
+    {
+        let mut inner = inner.lock().unwrap();
+        if inner.closed {
+            return;
+        }
+        inner.buffer.push_back(a);
+    }
+    deliver(inner);
+}
+
+fn finish<A, E>(inner: &Mutex<StreamInner<A, E>>, result: Result<(), E>) {
+    {
+        let mut inner = inner.lock().unwrap();
+        if inner.closed {
+            return;
+        }
+        inner.closed = true;
+        inner.end = Some(result);
+    }
+    deliver(inner);
+}
+
+fn abandon<A, E>(inner: &Mutex<StreamInner<A, E>>) {
+    {
+        // A poisoned lock means this is being dropped by an unwinding panic.
+        let mut inner = match inner.lock() {
+            Ok(inner) => inner,
+            Err(_) => return
+        };
+        if inner.closed {
+            return;
+        }
+        inner.closed = true;
+        inner.abandoned = true;
+    }
+    deliver(inner);
+}
+
+/// Hands buffered values, and then the end of the stream, to the consumer, unless none is
+/// attached or another thread is already doing so. The consumer and the end setter run outside
+/// the lock, so they may push to or finish the stream themselves; anything they push is handed
+/// over by this same loop.
+fn deliver<A, E>(inner: &Mutex<StreamInner<A, E>>) {
+    let mut consumer = match inner.lock().unwrap().consumer.take() {
+        Some(consumer) => consumer,
+        None => return
+    };
+    loop {
+        let next = {
+            let mut inner = inner.lock().unwrap();
+            match inner.buffer.pop_front() {
+                Some(a) => Ok(a),
+                None => match inner.end.take() {
+                    Some(result) => Err((Some(result), inner.end_setter.take())),
+                    None if inner.abandoned => Err((None, inner.end_setter.take())),
+                    None => {
+                        inner.consumer = Some(consumer);
+                        return;
+                    }
+                }
+            }
+        };
+        match next {
+            Ok(a) => consumer(a),
+            Err((result, end_setter)) => {
+                drop(consumer);
+                if let (Some(result), Some(end_setter)) = (result, end_setter) {
+                    end_setter.set_result(result);
+                }
+                return;
+            }
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, await_safe};
+
+    #[test]
+    fn values_sent_before_consumption_are_buffered() {
+        let (stream, setter) = stream::<i64, ()>();
+        setter.send(1);
+        setter.send(2);
+        setter.close();
+        assert_eq!(await(stream.collect()), Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn from_iter_blocking_fails_on_first_err() {
+        let items = vec![Ok(1), Err("bad"), Ok(3)];
+        let stream = from_iter_blocking(items).map(|i: i64| i + 1);
+        assert_eq!(await(stream.collect()), Err("bad"));
+    }
+
+    #[test]
+    fn select_stream_fails_with_the_first_error() {
+        let (a, a_setter) = stream::<i64, &str>();
+        let (b, b_setter) = stream::<i64, &str>();
+        let (c, c_setter) = stream::<i64, &str>();
+        let merged = select_stream(vec![a, b, c]).collect();
+
+        a_setter.send(1);
+        a_setter.close();
+        b_setter.fail("broken");
+        c_setter.send(2);
+        c_setter.close();
+        assert_eq!(await(merged), Err("broken"));
+        assert_eq!(await(select_stream::<i64, ()>(vec![]).collect()), Ok(vec![]));
+    }
+
+    #[test]
+    fn consumers_can_push_to_and_finish_their_own_stream() {
+        let (stream, setter) = stream::<i64, ()>();
+        setter.send(1);
+        let mut setter = Some(setter);
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+        let done = stream.for_each(move |i| {
+            seen2.lock().unwrap().push(i);
+            if i < 3 {
+                setter.as_ref().unwrap().send(i + 1);
+            } else {
+                setter.take().unwrap().close();
+            }
+        });
+
+        assert_eq!(await(done), Ok(()));
+        assert_eq!(*seen.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn dropped_setters_end_the_stream() {
+        let (stream, setter) = stream::<i64, ()>();
+        setter.send(1);
+        drop(setter);
+        assert!(await_safe(stream.collect()).is_err());
+
+        let items = (0..3).map(|i| if i < 2 { Ok(i) } else { panic!("iterator failed") });
+        let (a, a_setter) = stream::<i64, ()>();
+        let merged = merge(a, from_iter_blocking(items));
+        a_setter.close();
+        assert!(await_safe(merged.collect()).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use super::Waiters;
+use super::super::{value, Future, Never};
+use std::sync::{Arc, Mutex};
+
+/// A one-shot gate. Every `Future` returned by `wait` resolves once `open` is called, including
+/// those requested after the latch is already open. Clones of a `Latch` refer to the same gate.
+///
+/// # Examples
+///
+/// ```
+/// use future;
+/// use future::sync::Latch;
+/// use std::thread;
+///
+/// let started = Latch::new();
+/// let started2 = started.clone();
+/// thread::spawn(move || {
+///     // bind sockets, etc.
+///     started2.open();
+/// });
+///
+/// future::await(started.wait()).unwrap();
+/// ```
+pub struct Latch {
+    inner: Arc<Mutex<LatchInner>>
+}
+
+struct LatchInner {
+    open: bool,
+    waiters: Waiters<()>
+}
+
+impl Latch {
+    pub fn new() -> Latch {
+        Latch { inner: Arc::new(Mutex::new(LatchInner { open: false, waiters: Waiters::new() })) }
+    }
+
+    /// Opens the latch, resolving every waiting `Future`. Opening an open latch has no effect.
+    pub fn open(&self) {
+        let waiters = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.open = true;
+            inner.waiters.take()
+        };
+        for waiter in waiters {
+            waiter.set_result(Ok(()): Result<(), Never>);
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.inner.lock().unwrap().open
+    }
+
+    /// Returns a `Future` that resolves once the latch is open.
+    pub fn wait(&self) -> Future<(), Never> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.open {
+            value(())
+        } else {
+            inner.waiters.wait()
+        }
+    }
+}
+
+impl Clone for Latch {
+    fn clone(&self) -> Latch {
+        Latch { inner: self.inner.clone() }
+    }
+}
+
+mod test {
+    use super::*;
+
+    #[test]
+    fn wait_resolves_for_current_and_later_waiters() {
+        let latch = Latch::new();
+        let before = latch.wait();
+        assert_eq!(before.is_resolved(), false);
+
+        latch.open();
+        assert_eq!(before.is_resolved(), true);
+        assert_eq!(latch.wait().is_resolved(), true);
+    }
+
+    #[test]
+    fn dropped_waiters_are_pruned() {
+        let latch = Latch::new();
+        for _ in 0..100 {
+            drop(latch.wait());
+        }
+        let waiting = latch.wait();
+        assert_eq!(latch.inner.lock().unwrap().waiters.len(), 1);
+        latch.open();
+        assert_eq!(waiting.is_resolved(), true);
+    }
+}
@@ -0,0 +1,55 @@
+//! Synchronization primitives whose waiting operations produce `Future`s instead of blocking.
+
+mod latch;
+mod watch;
+
+pub use self::latch::*;
+pub use self::watch::*;
+
+use super::{new, Future, FutureSetter, Never};
+use super::config::DropPolicy;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// The setters of the `Future`s waiting on a primitive. Waiters whose `Future` has been dropped
+/// are pruned as new ones are added, so polling a primitive that rarely changes doesn't grow its
+/// list of waiters without bound.
+struct Waiters<A: 'static> {
+    waiters: Vec<(Arc<AtomicBool>, FutureSetter<A, Never>)>
+}
+
+impl<A: 'static> Waiters<A> {
+    fn new() -> Waiters<A> {
+        Waiters { waiters: vec![] }
+    }
+
+    /// Adds a waiter, returning its `Future`.
+    fn wait(&mut self) -> Future<A, Never> {
+        self.prune();
+        let (future, setter) = new();
+        let abandoned = Arc::new(AtomicBool::new(false));
+        let flag = abandoned.clone();
+        future.on_abandoned(move || flag.store(true, Ordering::SeqCst));
+        self.waiters.push((abandoned, setter));
+        future
+    }
+
+    /// Removes every waiter, for resolving outside the primitive's lock.
+    fn take(&mut self) -> Vec<FutureSetter<A, Never>> {
+        self.waiters.drain(..).map(|(_, setter)| setter).collect()
+    }
+
+    fn len(&self) -> usize {
+        self.waiters.len()
+    }
+
+    fn prune(&mut self) {
+        let (abandoned, waiting): (Vec<_>, Vec<_>) = self.waiters.drain(..)
+            .partition(|&(ref abandoned, _)| abandoned.load(Ordering::SeqCst));
+        self.waiters = waiting;
+        for (_, setter) in abandoned {
+            // Nothing is waiting on the result, so there's nothing to report.
+            setter.set_drop_policy(DropPolicy::Ignore);
+        }
+    }
+}
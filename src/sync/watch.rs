@@ -0,0 +1,121 @@
+use super::Waiters;
+use super::super::{value, Future, Never};
+use std::sync::{Arc, Mutex};
+
+/// A shared, observable value. `get` reads the current value, and `changed` returns a `Future`
+/// that resolves with the next value `set` on the cell. Clones of a `WatchCell` refer to the same
+/// value.
+///
+/// # Examples
+///
+/// ```
+/// use future;
+/// use future::sync::WatchCell;
+///
+/// let config = WatchCell::new(String::from("v1"));
+/// let next = config.changed();
+///
+/// config.set(String::from("v2"));
+/// assert_eq!("v2", future::await(next).unwrap());
+/// assert_eq!("v2", config.get());
+/// ```
+pub struct WatchCell<T>
+    where T: Clone + 'static
+{
+    inner: Arc<Mutex<WatchInner<T>>>
+}
+
+struct WatchInner<T>
+    where T: Clone + 'static
+{
+    value: T,
+    version: u64,
+    waiters: Waiters<T>
+}
+
+impl<T: Clone + 'static> WatchCell<T> {
+    pub fn new(value: T) -> WatchCell<T> {
+        WatchCell {
+            inner: Arc::new(Mutex::new(WatchInner { value: value, version: 0, waiters: Waiters::new() }))
+        }
+    }
+
+    /// Returns a clone of the current value.
+    pub fn get(&self) -> T {
+        self.inner.lock().unwrap().value.clone()
+    }
+
+    /// The number of times the value has been `set`.
+    pub fn version(&self) -> u64 {
+        self.inner.lock().unwrap().version
+    }
+
+    /// Replaces the current value, resolving every `Future` returned by `changed` since the last
+    /// update.
+    pub fn set(&self, value: T) {
+        let waiters = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.value = value.clone();
+            inner.version += 1;
+            inner.waiters.take()
+        };
+        for waiter in waiters {
+            waiter.set_result(Ok(value.clone()): Result<T, Never>);
+        }
+    }
+
+    /// Returns a `Future` of the next value `set` on this cell.
+    pub fn changed(&self) -> Future<T, Never> {
+        self.inner.lock().unwrap().waiters.wait()
+    }
+
+    /// Returns a `Future` of the current value if it has been `set` since `version`, or the next
+    /// value `set` otherwise. Pairing this with `version` lets an observer avoid missing an update
+    /// made between reading the value and waiting for the next one.
+    pub fn changed_since(&self, version: u64) -> Future<T, Never> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.version > version {
+            value(inner.value.clone())
+        } else {
+            inner.waiters.wait()
+        }
+    }
+}
+
+impl<T: Clone + 'static> Clone for WatchCell<T> {
+    fn clone(&self) -> WatchCell<T> {
+        WatchCell { inner: self.inner.clone() }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::super::await;
+
+    #[test]
+    fn changed_since_resolves_immediately_for_missed_updates() {
+        let cell = WatchCell::new(1);
+        let version = cell.version();
+        cell.set(2);
+
+        let next = cell.changed_since(version);
+        assert_eq!(await(next), Ok(2));
+
+        let next = cell.changed_since(cell.version());
+        assert_eq!(next.is_resolved(), false);
+        cell.clone().set(3);
+        assert_eq!(await(next), Ok(3));
+    }
+
+    #[test]
+    fn dropped_waiters_are_pruned() {
+        let cell = WatchCell::new(1);
+        for _ in 0..100 {
+            drop(cell.changed());
+        }
+        let next = cell.changed();
+        assert_eq!(cell.inner.lock().unwrap().waiters.len(), 1);
+        cell.set(2);
+        assert_eq!(await(next), Ok(2));
+    }
+}
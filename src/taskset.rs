@@ -0,0 +1,182 @@
+use super::{new, run, value, Future, FutureSetter, Never};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Owns detached `Future`s, so that background work can't outlive its owner. Dropping a `TaskSet`
+/// blocks until every `Future` added to it has resolved, or until its shutdown timeout (if any)
+/// has passed. The results of the `Future`s are discarded.
+///
+/// # Examples
+///
+/// ```
+/// use future;
+/// use future::TaskSet;
+/// use std::sync::{Arc, Mutex};
+/// use std::time::Duration;
+///
+/// let written = Arc::new(Mutex::new(vec![]));
+/// {
+///     let tasks = TaskSet::new().shutdown_timeout(Duration::from_secs(5));
+///     for i in 0..3 {
+///         let written = written.clone();
+///         tasks.spawn(move || Ok(written.lock().unwrap().push(i)): Result<(), ()>);
+///     }
+/// } // blocks until the spawned work is done
+/// assert_eq!(3, written.lock().unwrap().len());
+/// ```
+pub struct TaskSet {
+    shutdown_timeout: Option<Duration>,
+    shared: Arc<TaskSetShared>
+}
+
+struct TaskSetShared {
+    state: Mutex<TaskSetState>,
+    condvar: Condvar
+}
+
+struct TaskSetState {
+    pending: usize,
+    idle: Vec<FutureSetter<(), Never>>
+}
+
+impl TaskSet {
+    /// Creates an empty `TaskSet` that waits indefinitely when dropped.
+    pub fn new() -> TaskSet {
+        TaskSet {
+            shutdown_timeout: None,
+            shared: Arc::new(TaskSetShared {
+                state: Mutex::new(TaskSetState { pending: 0, idle: vec![] }),
+                condvar: Condvar::new()
+            })
+        }
+    }
+
+    /// Sets the longest time dropping this `TaskSet` will wait for its `Future`s to resolve.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> TaskSet {
+        self.shutdown_timeout = Some(timeout);
+        self
+    }
+
+    /// Adds `future` to the set, consuming it. It counts as resolved if its `FutureSetter` is
+    /// dropped without setting a result, e.g. by a panic in work spawned with `spawn`.
+    pub fn add<A: 'static, E: 'static>(&self, future: Future<A, E>) {
+        self.shared.state.lock().unwrap().pending += 1;
+        // Dropped with the callback, whether or not it runs.
+        let member = Member(self.shared.clone());
+        future.register(move |_| drop(member));
+    }
+
+    /// Executes `f` in a new thread like `future::run`, adding the resulting `Future` to the set.
+    pub fn spawn<F, A, E>(&self, f: F)
+        where F: FnOnce() -> Result<A, E> + Send + 'static,
+              A: 'static,
+              E: 'static
+    {
+        self.add(run(f));
+    }
+
+    /// The number of `Future`s in the set that haven't resolved yet.
+    pub fn len(&self) -> usize {
+        self.shared.state.lock().unwrap().pending
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a `Future` that resolves once every `Future` currently in the set has resolved.
+    pub fn idle(&self) -> Future<(), Never> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.pending == 0 {
+            return value(());
+        }
+        let (future, setter) = new();
+        state.idle.push(setter);
+        future
+    }
+
+    /// Blocks until every `Future` in the set has resolved, or until `timeout` has passed if one
+    /// is given. Returns whether the set is empty.
+    pub fn wait(&self, timeout: Option<Duration>) -> bool {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut state = self.shared.state.lock().unwrap();
+        while state.pending > 0 {
+            state = match deadline {
+                None => self.shared.condvar.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    self.shared.condvar.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+        true
+    }
+}
+
+impl Drop for TaskSet {
+    fn drop(&mut self) {
+        self.wait(self.shutdown_timeout);
+    }
+}
+
+/// Counts a `Future` as pending in its `TaskSet` until dropped.
+struct Member(Arc<TaskSetShared>);
+
+impl Drop for Member {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+impl TaskSetShared {
+    fn finish(&self) {
+        let idle = {
+            let mut state = match self.state.lock() {
+                Ok(state) => state,
+                Err(_) => return
+            };
+            state.pending -= 1;
+            if state.pending > 0 {
+                return;
+            }
+            state.idle.drain(..).collect::<Vec<_>>()
+        };
+        self.condvar.notify_all();
+        for setter in idle {
+            setter.set_result(Ok(()): Result<(), Never>);
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::await;
+
+    #[test]
+    fn drop_stops_waiting_after_the_shutdown_timeout() {
+        let (f, setter) = new::<(), ()>();
+        let tasks = TaskSet::new().shutdown_timeout(Duration::from_millis(10));
+        tasks.add(f);
+        let idle = tasks.idle();
+        assert_eq!(tasks.len(), 1);
+
+        let start = Instant::now();
+        drop(tasks);
+        assert!(start.elapsed() >= Duration::from_millis(10));
+
+        setter.set_result(Ok(()): Result<(), ()>);
+        assert_eq!(await(idle), Ok(()));
+    }
+
+    #[test]
+    fn panicking_tasks_count_as_finished() {
+        let tasks = TaskSet::new();
+        tasks.spawn(|| -> Result<(), ()> { panic!("background work failed") });
+        let idle = tasks.idle();
+        drop(tasks);
+        assert_eq!(await(idle), Ok(()));
+    }
+}
@@ -0,0 +1,127 @@
+use super::Future;
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Mutex, Once, ONCE_INIT};
+use std::time::{Duration, Instant};
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Like `on_err`, except `f` runs at most once per `window` across every `Future` with the same
+    /// label, so that a hot failing call can't flood a log. Unlabelled `Future`s are throttled per
+    /// call site of `on_err_throttled` instead. Errors skipped in the meantime are counted, and the
+    /// count passed to the next call of `f`.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use std::time::Duration;
+    ///
+    /// for _ in 0..100 {
+    ///     future::err::<(), String>(String::from("connection refused"))
+    ///         .with_label("fetch_user")
+    ///         .on_err_throttled(Duration::from_secs(10), |e, suppressed| {
+    ///             println!("{} (and {} more)", e, suppressed)
+    ///         })
+    ///         .resolve(|_| {});
+    /// }
+    /// ```
+    pub fn on_err_throttled<F>(self, window: Duration, f: F) -> Future<A, E>
+        where F: FnOnce(&E, usize) -> (), F: 'static
+    {
+        let key = match self.lock.lock().unwrap().label {
+            Some(ref label) => ThrottleKey::Label((**label).clone()),
+            None => ThrottleKey::Site(TypeId::of::<F>())
+        };
+        self.on_err(move |e| if let Some(suppressed) = admit(key, window) {
+            f(e, suppressed)
+        })
+    }
+}
+
+/// What errors are throttled together by.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ThrottleKey {
+    Label(String),
+    /// The type of the callback given to `on_err_throttled`, which is unique to its call site
+    Site(TypeId)
+}
+
+struct Throttle {
+    last: Instant,
+    window: Duration,
+    /// Errors skipped since `last`
+    suppressed: usize
+}
+
+static THROTTLES_INIT: Once = ONCE_INIT;
+static mut THROTTLES: *const Mutex<HashMap<ThrottleKey, Throttle>> = 0 as *const _;
+
+/// Returns the number of errors suppressed for `key` since the last one admitted, if an error may
+/// be reported now.
+fn admit(key: ThrottleKey, window: Duration) -> Option<usize> {
+    let mut throttles = throttles().lock().unwrap();
+    let now = Instant::now();
+    match throttles.get_mut(&key) {
+        Some(ref mut throttle) if now.duration_since(throttle.last) < window => {
+            throttle.suppressed += 1;
+            return None;
+        },
+        Some(ref mut throttle) => {
+            throttle.last = now;
+            throttle.window = window;
+            return Some(mem::replace(&mut throttle.suppressed, 0));
+        },
+        None => {}
+    }
+    // Throttles whose window has passed with nothing suppressed would admit the next error
+    // anyway, so forgetting them loses nothing.
+    throttles.retain(|_, throttle| throttle.suppressed > 0 || now.duration_since(throttle.last) < throttle.window);
+    throttles.insert(key, Throttle { last: now, window: window, suppressed: 0 });
+    Some(0)
+}
+
+fn throttles() -> &'static Mutex<HashMap<ThrottleKey, Throttle>> {
+    unsafe {
+        THROTTLES_INIT.call_once(|| {
+            THROTTLES = Box::into_raw(box Mutex::new(HashMap::new()));
+        });
+        &*THROTTLES
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, err};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn throttled_errors_are_counted() {
+        let reported = Arc::new(Mutex::new(vec![]));
+        let fail = |reported: &Arc<Mutex<Vec<usize>>>| {
+            let reported = reported.clone();
+            let f = err::<(), i64>(1).with_label("throttle_test")
+                .on_err_throttled(Duration::from_millis(50), move |_, suppressed| reported.lock().unwrap().push(suppressed));
+            await(f).unwrap_err();
+        };
+
+        for _ in 0..3 {
+            fail(&reported);
+        }
+        thread::sleep(Duration::from_millis(60));
+        fail(&reported);
+        assert_eq!(*reported.lock().unwrap(), vec![0, 2]);
+    }
+
+    #[test]
+    fn unlabelled_errors_are_throttled_per_call_site() {
+        let reported = Arc::new(Mutex::new(vec![]));
+        for _ in 0..2 {
+            let (a, b) = (reported.clone(), reported.clone());
+            let f = err::<(), i64>(1).on_err_throttled(Duration::from_secs(10), move |_, _| a.lock().unwrap().push("a"));
+            await(f).unwrap_err();
+            let f = err::<(), i64>(2).on_err_throttled(Duration::from_secs(10), move |_, _| b.lock().unwrap().push("b"));
+            await(f).unwrap_err();
+        }
+        assert_eq!(*reported.lock().unwrap(), vec!["a", "b"]);
+    }
+}
@@ -0,0 +1,62 @@
+//! Lightweight timing of `Future`s, for threading latency into results without setting up
+//! metrics.
+
+use super::Future;
+use std::time::{Duration, Instant};
+
+/// Measures the time from when it's started until a `Future` resolves.
+/// # Examples
+/// ```
+/// use future;
+/// use future::time::Stopwatch;
+/// use future::timer;
+/// use std::time::Duration;
+///
+/// let stopwatch = Stopwatch::start();
+/// let f = timer::sleep(Duration::from_millis(10)).map(|_| "done");
+/// let (done, elapsed) = future::await(stopwatch.measure(f)).unwrap();
+/// assert_eq!("done", done);
+/// assert!(elapsed >= Duration::from_millis(10));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Stopwatch {
+    started: Instant
+}
+
+impl Stopwatch {
+    pub fn start() -> Stopwatch {
+        Stopwatch { started: Instant::now() }
+    }
+
+    /// The time since this was started.
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Pairs the value of `future` with the time from when this was started until it resolved.
+    /// Errors are passed through unchanged.
+    pub fn measure<A, E>(self, future: Future<A, E>) -> Future<(A, Duration), E>
+        where A: 'static, E: 'static
+    {
+        future.map(move |a| (a, self.elapsed()))
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, new};
+    use std::thread;
+
+    #[test]
+    fn measures_until_resolution_not_until_await() {
+        let (f, setter) = new::<i64, ()>();
+        let f = Stopwatch::start().measure(f);
+        thread::sleep(Duration::from_millis(10));
+        setter.set_result(Ok(1): Result<i64, ()>);
+        thread::sleep(Duration::from_millis(50));
+
+        let (i, elapsed) = await(f).unwrap();
+        assert_eq!(i, 1);
+        assert!(elapsed >= Duration::from_millis(10) && elapsed < Duration::from_millis(50), "{:?}", elapsed);
+    }
+}
@@ -0,0 +1,338 @@
+use super::{config, new, timer, AlreadySet, Future, FutureSetter, SharedSetter};
+use super::log::{self, Level};
+use super::trace::Span;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Fails with a `TimeoutError` if this `Future` hasn't resolved within `timeout`; otherwise the
+    /// result is passed through unchanged.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::TimeoutError;
+    /// use std::time::Duration;
+    ///
+    /// let (f, _setter) = future::new::<i64, TimeoutError>();
+    /// let f = f.within(Duration::from_millis(10));
+    /// assert!(future::await(f).is_err());
+    /// ```
+    pub fn within(self, timeout: Duration) -> Future<A, E>
+        where E: From<TimeoutError>
+    {
+        let label = self.lock.lock().unwrap().stage();
+        let span = Span::start("within", label.as_ref().map(|label| &label[..]));
+        let (future, setter) = self.derive();
+        let setter = setter.shared();
+
+        // The timer only holds a weak handle, so a dropped setter fails the result at once rather
+        // than at the timeout.
+        let timeout_setter = setter.downgrade();
+        let timeout_span = span.clone();
+        timer::sleep(timeout).register(move |_| {
+            let timeout = TimeoutError { after: timeout, label: label };
+            if let Some(ref span) = timeout_span {
+                span.event(&timeout.to_string());
+            }
+            timeout_setter.set_if_unset(Err(timeout): Result<A, TimeoutError>);
+        });
+
+        self.register(move |result| {
+            setter.set_if_unset(result);
+        });
+        future.traced(span)
+    }
+
+    /// Holds back the result, success or error, until at least `delay` after this call, e.g. to
+    /// avoid a flickering spinner or to keep the response time of a security-sensitive endpoint
+    /// independent of the outcome.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let start = Instant::now();
+    /// let f = future::err::<(), &str>("invalid password").min_delay(Duration::from_millis(20));
+    /// assert_eq!(Err("invalid password"), future::await(f));
+    /// assert!(start.elapsed() >= Duration::from_millis(20));
+    /// ```
+    pub fn min_delay(self, delay: Duration) -> Future<A, E> {
+        let earliest = timer::sleep(delay);
+        self.transformf(move |result| earliest.transform(move |_| result))
+    }
+
+    /// Like `within`, except a timeout carries a `LateFuture` of this `Future`'s eventual result,
+    /// so that a late result can still be used (e.g. to populate a cache) rather than lost.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::WithinError;
+    /// use std::time::Duration;
+    ///
+    /// let (f, setter) = future::new::<i64, String>();
+    /// let late = match future::await(f.within_late(Duration::from_millis(10))) {
+    ///     Err(WithinError::TimedOut(late)) => late,
+    ///     other => panic!("Expected a timeout, got {:?}", other)
+    /// };
+    ///
+    /// setter.set_result(Ok(5): Result<i64, String>);
+    /// assert_eq!(Ok(5), future::await(late.into_future()));
+    /// ```
+    pub fn within_late(self, timeout: Duration) -> Future<A, WithinError<A, E>> {
+        let (future, setter) = self.derive();
+        let setter = setter.shared();
+        let (late, mut late_setter) = new();
+        // Dropped unset whenever the result arrives in time, which isn't worth reporting.
+        late_setter.drop_guard.derived = true;
+        let late = Arc::new(Mutex::new(Some(late)));
+
+        // The timer only holds a weak handle, so a dropped setter fails the result at once rather
+        // than at the timeout.
+        let timeout_setter = setter.downgrade();
+        timer::sleep(timeout).register(move |_| {
+            let late = late.lock().unwrap().take().unwrap();
+            let late = LateFuture { after: timeout, future: late };
+            timeout_setter.set_if_unset(Err(WithinError::TimedOut(late)): Result<A, WithinError<A, E>>);
+        });
+
+        self.register(move |result| {
+            if let Err(AlreadySet(result)) = setter.try_set_result(result.map_err(WithinError::Failed)) {
+                late_setter.set_result(result.map_err(|e| match e {
+                    WithinError::Failed(e) => e,
+                    WithinError::TimedOut(_) => unreachable!()
+                }));
+            }
+        });
+        future
+    }
+}
+
+impl<A: 'static, E: 'static> FutureSetter<A, E> {
+    /// Schedules `result` to be set after `delay`, unless a result is set first through the
+    /// returned `SharedSetter`, in which case the scheduled one is discarded. Useful for fallback
+    /// responses, and for adding synthetic latency in tests.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use std::time::Duration;
+    ///
+    /// let (f, setter) = future::new::<&str, ()>();
+    /// let setter = setter.set_after(Duration::from_millis(10), Ok("fallback"): Result<&str, ()>);
+    /// setter.set_if_unset(Ok("fresh"): Result<&str, ()>);
+    /// assert_eq!(Ok("fresh"), future::await(f));
+    ///
+    /// let (f, setter) = future::new::<&str, ()>();
+    /// let _setter = setter.set_after(Duration::from_millis(10), Ok("fallback"): Result<&str, ()>);
+    /// assert_eq!(Ok("fallback"), future::await(f));
+    /// ```
+    pub fn set_after<E2>(self, delay: Duration, result: Result<A, E2>) -> SharedSetter<A, E>
+        where E2: Into<E> + 'static
+    {
+        let setter = self.shared();
+        let scheduled = setter.clone();
+        timer::sleep(delay).register(move |_| {
+            scheduled.set_if_unset(result);
+        });
+        setter
+    }
+}
+
+/// Applies `config::set_default_timeout`, if set, to `future`, unless its error type can't
+/// represent a timeout.
+pub(crate) fn with_default_timeout<A: 'static, E: 'static>(future: Future<A, E>) -> Future<A, E> {
+    match config::default_timeout() {
+        Some(timeout) => apply_default_timeout(future, timeout),
+        None => future
+    }
+}
+
+/// Fails `future` with the registered conversion of a `TimeoutError` if it hasn't resolved within
+/// `timeout`, unless its error type can't represent a timeout.
+fn apply_default_timeout<A: 'static, E: 'static>(future: Future<A, E>, timeout: Duration) -> Future<A, E> {
+    let convert = match config::timeout_error::<E>() {
+        Some(convert) => convert,
+        None => return future
+    };
+    let label = future.lock.lock().unwrap().stage();
+    let (derived, setter) = future.derive();
+    let setter = setter.shared();
+
+    // The timer only holds a weak handle, so a dropped setter fails the result at once rather
+    // than at the timeout.
+    let expired = setter.downgrade();
+    timer::sleep(timeout).register(move |_| {
+        let timeout = TimeoutError { after: timeout, label: label };
+        let message = format!("{} (the default timeout)", timeout);
+        if expired.set_if_unset(Err(convert(timeout)): Result<A, E>) {
+            log::log(Level::Warn, "future", &message);
+        }
+    });
+
+    future.register(move |result| {
+        setter.set_if_unset(result);
+    });
+    derived
+}
+
+/// The error of a `Future` returned by `within_late`.
+pub enum WithinError<A, E>
+    where A: 'static, E: 'static
+{
+    /// The original `Future` failed in time
+    Failed(E),
+    /// The original `Future` didn't resolve in time; its result will be delivered to the
+    /// contained `LateFuture`
+    TimedOut(LateFuture<A, E>)
+}
+
+/// The still-pending result of a `Future` that timed out in `within_late`.
+pub struct LateFuture<A, E>
+    where A: 'static, E: 'static
+{
+    after: Duration,
+    future: Future<A, E>
+}
+
+impl<A: 'static, E: 'static> LateFuture<A, E> {
+    /// The timeout that was exceeded.
+    pub fn after(&self) -> Duration {
+        self.after
+    }
+
+    /// The eventual result of the `Future` that timed out.
+    pub fn into_future(self) -> Future<A, E> {
+        self.future
+    }
+}
+
+impl<A: 'static, E: 'static> fmt::Debug for LateFuture<A, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LateFuture {{ after: {:?} }}", self.after)
+    }
+}
+
+impl<A: 'static, E: fmt::Debug + 'static> fmt::Debug for WithinError<A, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WithinError::Failed(ref e) => write!(f, "Failed({:?})", e),
+            WithinError::TimedOut(ref late) => write!(f, "TimedOut({:?})", late)
+        }
+    }
+}
+
+impl<A: 'static, E: fmt::Display + 'static> fmt::Display for WithinError<A, E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WithinError::Failed(ref e) => e.fmt(f),
+            WithinError::TimedOut(ref late) => write!(f, "Timed out after {:?}", late.after)
+        }
+    }
+}
+
+impl<A: 'static, E: Error + 'static> Error for WithinError<A, E> {
+    fn description(&self) -> &str {
+        match *self {
+            WithinError::Failed(ref e) => e.description(),
+            WithinError::TimedOut(_) => "The Future did not resolve within the allowed time"
+        }
+    }
+}
+
+/// An Error indicating that a `Future` did not resolve within the time allowed by `within`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeoutError {
+    /// The timeout that was exceeded
+    pub after: Duration,
+    /// The stage of the labelled chain that timed out, as described by `Future::with_label`
+    pub label: Option<String>
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.label {
+            Some(ref label) => write!(f, "Future '{}' timed out after {:?}", label, self.after),
+            None => write!(f, "Timed out after {:?}", self.after)
+        }
+    }
+}
+
+impl Error for TimeoutError {
+    fn description(&self) -> &str {
+        "The Future did not resolve within the allowed time"
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, await_safe, value};
+
+    #[test]
+    fn within_late_passes_through_timely_results() {
+        let f = value::<i64, ()>(1).within_late(Duration::from_millis(10));
+        match await(f) {
+            Ok(1) => {},
+            other => panic!("Unexpected result: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn within_late_doesnt_report_the_unused_late_setter() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let events2 = events.clone();
+        let (f, setter) = new::<i64, ()>();
+        let f = f.with_drop_policy(config::DropPolicy::Handler(Arc::new(move |event: &config::UnresolvedDrop| {
+            events2.lock().unwrap().push(*event)
+        })));
+        let f = f.within_late(Duration::from_secs(10));
+
+        setter.set_result(Ok(1): Result<i64, ()>);
+        match await(f) {
+            Ok(1) => {},
+            other => panic!("Unexpected result: {:?}", other)
+        }
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn default_timeout_fails_stuck_futures() {
+        let (f, setter) = new::<i64, TimeoutError>();
+        let (untyped, _untyped_setter) = new::<i64, ()>();
+        let timeout = Duration::from_millis(10);
+        let f = apply_default_timeout(f, timeout);
+        let ok = apply_default_timeout(value::<i64, TimeoutError>(1), timeout);
+        let untyped = apply_default_timeout(untyped, timeout);
+
+        assert_eq!(await(ok), Ok(1));
+        assert_eq!(await(f).unwrap_err().after, Duration::from_millis(10));
+        assert!(!untyped.is_resolved());
+        drop(setter);
+    }
+
+    #[test]
+    fn the_timer_doesnt_hold_the_setter() {
+        let (f, setter) = new::<i64, TimeoutError>();
+        let f = f.within(Duration::from_secs(60));
+        drop(setter);
+        assert!(await_safe(f).is_err());
+
+        let (f, setter) = new::<i64, ()>();
+        let f = f.within_late(Duration::from_secs(60));
+        drop(setter);
+        assert!(await_safe(f).is_err());
+
+        let (f, setter) = new::<i64, TimeoutError>();
+        let f = apply_default_timeout(f, Duration::from_secs(60));
+        drop(setter);
+        assert!(await_safe(f).is_err());
+    }
+
+    #[test]
+    fn scheduled_results_lose_to_earlier_ones() {
+        let (f, setter) = new::<i64, ()>();
+        let setter = setter.set_after(Duration::from_millis(10), Ok(1): Result<i64, ()>);
+        assert!(setter.set_if_unset(Ok(2): Result<i64, ()>));
+        assert_eq!(await(f), Ok(2));
+    }
+}
@@ -0,0 +1,147 @@
+//! `Future`s that resolve after a delay, all driven by a single shared timer thread.
+//!
+//! Callbacks added to these `Future`s run on the timer thread, so long-running work should be
+//! moved elsewhere (e.g. with `future::run`) to avoid delaying other timers.
+
+use super::{new, Future, FutureSetter, Never};
+#[cfg(feature = "wasm")]
+use super::wasm;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Condvar, Mutex, Once, ONCE_INIT};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Returns a `Future` that resolves once `duration` has elapsed.
+/// # Examples
+/// ```
+/// use future;
+/// use future::timer;
+/// use std::time::{Duration, Instant};
+///
+/// let start = Instant::now();
+/// future::await(timer::sleep(Duration::from_millis(10))).unwrap();
+/// assert!(start.elapsed() >= Duration::from_millis(10));
+/// ```
+pub fn sleep(duration: Duration) -> Future<(), Never> {
+    at(Instant::now() + duration)
+}
+
+/// Returns a `Future` that resolves once `deadline` has passed.
+pub fn at(deadline: Instant) -> Future<(), Never> {
+    let (future, setter) = new();
+    #[cfg(feature = "wasm")]
+    {
+        if let Some(timer) = wasm::timer() {
+            let now = Instant::now();
+            let delay = if deadline > now { deadline - now } else { Duration::from_millis(0) };
+            let fire = move || setter.set_result(Ok(()): Result<(), Never>);
+            timer.set_timeout(delay, box fire);
+            return future;
+        }
+    }
+    let timer = timer();
+    {
+        let mut state = timer.state.lock().unwrap();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.queue.push(Entry { deadline: deadline, seq: seq, setter: setter });
+    }
+    timer.condvar.notify_one();
+    future
+}
+
+struct Timer {
+    state: Mutex<TimerState>,
+    condvar: Condvar
+}
+
+struct TimerState {
+    queue: BinaryHeap<Entry>,
+    next_seq: u64
+}
+
+struct Entry {
+    deadline: Instant,
+    seq: u64,
+    setter: FutureSetter<(), Never>
+}
+
+impl Ord for Entry {
+    // Reversed so that the `BinaryHeap` pops the earliest deadline first, breaking ties by the
+    // order timers were created in.
+    fn cmp(&self, other: &Entry) -> Ordering {
+        other.deadline.cmp(&self.deadline).then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Entry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Entry) -> bool {
+        self.deadline == other.deadline && self.seq == other.seq
+    }
+}
+
+impl Eq for Entry {}
+
+static TIMER_INIT: Once = ONCE_INIT;
+static mut TIMER: *const Timer = 0 as *const _;
+
+fn timer() -> &'static Timer {
+    unsafe {
+        TIMER_INIT.call_once(|| {
+            TIMER = Box::into_raw(Box::new(Timer {
+                state: Mutex::new(TimerState { queue: BinaryHeap::new(), next_seq: 0 }),
+                condvar: Condvar::new()
+            }));
+            thread::Builder::new()
+                .name(String::from("future-timer"))
+                .spawn(|| run_timer(&*TIMER))
+                .unwrap();
+        });
+        &*TIMER
+    }
+}
+
+fn run_timer(timer: &'static Timer) {
+    let mut state = timer.state.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        let next_deadline = state.queue.peek().map(|entry| entry.deadline);
+        match next_deadline {
+            None => state = timer.condvar.wait(state).unwrap(),
+            Some(deadline) if deadline > now => {
+                state = timer.condvar.wait_timeout(state, deadline - now).unwrap().0;
+            },
+            Some(_) => {
+                let entry = state.queue.pop().unwrap();
+                drop(state);
+                entry.setter.set_result(Ok(()): Result<(), Never>);
+                state = timer.state.lock().unwrap();
+            }
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::await;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn timers_fire_in_deadline_order() {
+        let fired = Arc::new(Mutex::new(vec![]));
+        let (f1, f2) = (fired.clone(), fired.clone());
+        let late = sleep(Duration::from_millis(40)).on_success(move |_| f1.lock().unwrap().push(2));
+        let early = sleep(Duration::from_millis(10)).on_success(move |_| f2.lock().unwrap().push(1));
+
+        await(late).unwrap();
+        await(early).unwrap();
+        assert_eq!(*fired.lock().unwrap(), vec![1, 2]);
+    }
+}
@@ -0,0 +1,209 @@
+//! A pluggable tracing hook, through which `Future::trace_span`, and the timeout, retry and
+//! `Service` layers of this crate, report spans and their outcomes.
+//!
+//! Nothing is traced until a `Tracer` is installed with `set_tracer`, which lets an
+//! OpenTracing-style backend be plugged in without this crate depending on it.
+
+use super::Future;
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Receives the spans reported by this crate.
+pub trait Tracer: Send + Sync {
+    /// Starts a span for the operation `name`, on the `Future` with the given label, if any.
+    /// Returns the id by which the span's events and end are reported.
+    fn start_span(&self, name: &str, label: Option<&str>) -> u64;
+
+    /// Ends the span `span`. Called exactly once per span.
+    fn end_span(&self, span: u64, outcome: Outcome);
+
+    /// Records `event`, such as a timeout or a retry, within the span `span`.
+    fn add_event(&self, span: u64, event: &str);
+}
+
+/// How a span ended.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure,
+    /// The `Future` was dropped without ever resolving
+    Abandoned
+}
+
+static TRACER_INIT: Once = ONCE_INIT;
+static mut TRACER: *const Mutex<Option<Arc<Tracer>>> = 0 as *const _;
+
+/// Installs the `Tracer` to which spans are reported. Spans already started are still reported to
+/// the `Tracer` that started them.
+/// # Examples
+/// ```
+/// use future;
+/// use future::trace::{self, Outcome, Tracer};
+/// use std::sync::Mutex;
+///
+/// struct Printer {
+///     next: Mutex<u64>
+/// }
+///
+/// impl Tracer for Printer {
+///     fn start_span(&self, name: &str, label: Option<&str>) -> u64 {
+///         let mut next = self.next.lock().unwrap();
+///         *next += 1;
+///         println!("start {} {} {:?}", *next, name, label);
+///         *next
+///     }
+///
+///     fn end_span(&self, span: u64, outcome: Outcome) {
+///         println!("end {} {:?}", span, outcome);
+///     }
+///
+///     fn add_event(&self, span: u64, event: &str) {
+///         println!("event {} {}", span, event);
+///     }
+/// }
+///
+/// trace::set_tracer(Printer { next: Mutex::new(0) });
+/// let f = future::value::<i64, ()>(1).with_label("lookup").trace_span("cache");
+/// assert_eq!(Ok(1), future::await(f));
+/// # trace::clear_tracer();
+/// ```
+pub fn set_tracer<T: Tracer + 'static>(tracer: T) {
+    *tracer_cell().lock().unwrap() = Some(Arc::new(tracer));
+}
+
+/// Stops tracing.
+pub fn clear_tracer() {
+    *tracer_cell().lock().unwrap() = None;
+}
+
+fn tracer_cell() -> &'static Mutex<Option<Arc<Tracer>>> {
+    unsafe {
+        TRACER_INIT.call_once(|| {
+            TRACER = Box::into_raw(box Mutex::new(None));
+        });
+        &*TRACER
+    }
+}
+
+/// A span started with the current `Tracer`, ended as `Abandoned` if dropped before `end`.
+pub(crate) struct Span {
+    tracer: Arc<Tracer>,
+    id: u64,
+    ended: AtomicBool
+}
+
+impl Span {
+    /// Starts a span, unless no `Tracer` is installed.
+    pub(crate) fn start(name: &str, label: Option<&str>) -> Option<Arc<Span>> {
+        let tracer = match *tracer_cell().lock().unwrap() {
+            Some(ref tracer) => tracer.clone(),
+            None => return None
+        };
+        let id = tracer.start_span(name, label);
+        Some(Arc::new(Span { tracer: tracer, id: id, ended: AtomicBool::new(false) }))
+    }
+
+    pub(crate) fn event(&self, event: &str) {
+        self.tracer.add_event(self.id, event);
+    }
+
+    /// Ends the span, unless it has already ended.
+    pub(crate) fn end(&self, outcome: Outcome) {
+        if !self.ended.swap(true, Ordering::SeqCst) {
+            self.tracer.end_span(self.id, outcome);
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        self.end(Outcome::Abandoned);
+    }
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Reports a span named `name` to the installed `Tracer`, if any, from now until this `Future`
+    /// resolves. The result is passed through unchanged.
+    pub fn trace_span(self, name: &str) -> Future<A, E> {
+        let label = self.lock.lock().unwrap().stage();
+        let span = Span::start(name, label.as_ref().map(|label| &label[..]));
+        self.traced(span)
+    }
+
+    /// Ends `span`, if any, when this `Future` resolves.
+    pub(crate) fn traced(self, span: Option<Arc<Span>>) -> Future<A, E> {
+        let span = match span {
+            Some(span) => span,
+            None => return self
+        };
+        let (future, setter) = self.derive();
+        self.register(move |result| {
+            span.end(if result.is_ok() { Outcome::Success } else { Outcome::Failure });
+            setter.set_result(result);
+        });
+        future
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, new};
+
+    struct Recorder {
+        spans: Mutex<Vec<String>>,
+        log: Arc<Mutex<Vec<String>>>
+    }
+
+    impl Recorder {
+        /// Only spans named "traced..." are recorded, as other tests run alongside this one.
+        fn is_traced(&self, span: u64) -> bool {
+            self.spans.lock().unwrap()[span as usize].starts_with("traced")
+        }
+    }
+
+    impl Tracer for Recorder {
+        fn start_span(&self, name: &str, label: Option<&str>) -> u64 {
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(format!("{} {:?}", name, label));
+            if name.starts_with("traced") {
+                self.log.lock().unwrap().push(format!("start {} {:?}", name, label));
+            }
+            spans.len() as u64 - 1
+        }
+
+        fn end_span(&self, span: u64, outcome: Outcome) {
+            if self.is_traced(span) {
+                self.log.lock().unwrap().push(format!("end {:?}", outcome));
+            }
+        }
+
+        fn add_event(&self, span: u64, event: &str) {
+            if self.is_traced(span) {
+                self.log.lock().unwrap().push(format!("event {}", event));
+            }
+        }
+    }
+
+    #[test]
+    fn spans_end_with_the_outcome() {
+        let log = Arc::new(Mutex::new(vec![]));
+        set_tracer(Recorder { spans: Mutex::new(vec![]), log: log.clone() });
+
+        let (f, setter) = new::<i64, ()>();
+        let f = f.with_label("lookup").trace_span("traced");
+        setter.set_result(Err(()): Result<i64, ()>);
+        assert_eq!(await(f), Err(()));
+
+        let (f, setter) = new::<i64, ()>();
+        drop(f.trace_span("traced-dropped"));
+        drop(setter);
+        clear_tracer();
+
+        assert_eq!(*log.lock().unwrap(), vec![
+            String::from("start traced Some(\"lookup\")"),
+            String::from("end Failure"),
+            String::from("start traced-dropped None"),
+            String::from("end Abandoned")
+        ]);
+    }
+}
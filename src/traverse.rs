@@ -0,0 +1,361 @@
+use super::{new, stream, Future, FutureSetter, FutureStream, FutureStreamSetter};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::usize;
+
+/// Run `f` on every item, collecting the results in input order. Every `Future` is started
+/// immediately; the result fails as soon as any of them fails.
+/// # Examples
+/// ```
+/// use future;
+///
+/// let lengths = future::traverse(vec!["a", "bb", "ccc"], |s| future::value::<usize, ()>(s.len()));
+/// assert_eq!(vec![1, 2, 3], future::await(lengths).unwrap());
+/// ```
+pub fn traverse<I, F, A, E>(items: I, f: F) -> Future<Vec<A>, E>
+    where I: IntoIterator, I::IntoIter: 'static,
+          F: FnMut(I::Item) -> Future<A, E>, F: 'static,
+          A: 'static,
+          E: 'static
+{
+    traverse_limited(items, usize::MAX, f)
+}
+
+/// Like `traverse`, except at most `limit` of the `Future`s returned by `f` are in flight at once;
+/// `f` is not called for the next item until an earlier `Future` resolves.
+/// # Panics
+/// This will panic if `limit` is 0.
+pub fn traverse_limited<I, F, A, E>(items: I, limit: usize, f: F) -> Future<Vec<A>, E>
+    where I: IntoIterator, I::IntoIter: 'static,
+          F: FnMut(I::Item) -> Future<A, E>, F: 'static,
+          A: 'static,
+          E: 'static
+{
+    let (future, setter) = new();
+    Traversal::start(items.into_iter(), limit, f, TraversalOutput::Ordered(vec![], setter));
+    future
+}
+
+/// Like `traverse_limited`, except results are delivered as they complete rather than in input
+/// order. Each value is paired with the index of the item it was produced from, and the stream
+/// fails as soon as any of the `Future`s returned by `f` fails.
+/// # Examples
+/// ```
+/// use future;
+/// use std::usize;
+///
+/// let (slow, slow_setter) = future::new::<&str, ()>();
+/// let mut inputs = vec![slow, future::value("fast")].into_iter();
+/// let completions = future::traverse_unordered(0..2, usize::MAX, move |_| inputs.next().unwrap());
+///
+/// slow_setter.set_result(Ok("slow"): Result<&str, ()>);
+/// assert_eq!(vec![(1, "fast"), (0, "slow")], future::await(completions.collect()).unwrap());
+/// ```
+pub fn traverse_unordered<I, F, A, E>(items: I, limit: usize, f: F) -> FutureStream<(usize, A), E>
+    where I: IntoIterator, I::IntoIter: 'static,
+          F: FnMut(I::Item) -> Future<A, E>, F: 'static,
+          A: 'static,
+          E: 'static
+{
+    let (stream, setter) = stream();
+    Traversal::start(items.into_iter(), limit, f, TraversalOutput::Unordered(setter));
+    stream
+}
+
+/// What `collect_map_values` does when the lookup for a key fails.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KeyErrors {
+    /// Fail with the first error, as `traverse` does
+    FailFast,
+    /// Leave the key out of the map
+    Skip
+}
+
+/// Looks up every key with `f`, at most `limit` at once, collecting the values by key. A batch get
+/// built on `traverse_limited`; if a key is given more than once, it is looked up more than once.
+/// # Examples
+/// ```
+/// use future;
+/// use future::KeyErrors;
+///
+/// let lookup = |id: &u64| if *id == 0 { future::err(()) } else { future::value(format!("user {}", id)) };
+/// let users = future::await(future::collect_map_values(vec![0, 1, 2], 2, KeyErrors::Skip, lookup)).unwrap();
+/// assert_eq!(2, users.len());
+/// assert_eq!("user 1", users[&1]);
+/// ```
+/// # Panics
+/// This will panic if `limit` is 0.
+pub fn collect_map_values<I, F, K, V, E>(keys: I, limit: usize, errors: KeyErrors, f: F) -> Future<HashMap<K, V>, E>
+    where I: IntoIterator<Item = K>, I::IntoIter: 'static,
+          F: Fn(&K) -> Future<V, E>, F: 'static,
+          K: Hash + Eq + 'static,
+          V: 'static,
+          E: 'static
+{
+    traverse_limited(keys, limit, move |key| {
+        let lookup = f(&key);
+        match errors {
+            KeyErrors::FailFast => lookup.map(move |v| Some((key, v))),
+            KeyErrors::Skip => lookup.transform::<_, _, E>(move |result| Ok(result.ok().map(|v| (key, v))))
+        }
+    }).map(|entries| entries.into_iter().filter_map(|entry| entry).collect())
+}
+
+impl<T: 'static, E: 'static> Future<Vec<T>, E> {
+    /// Run `f` on every item of the successful `Vec`, collecting the results in order. This fuses
+    /// the common pattern of fetching a list and then fetching something for each of its items.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::Future;
+    ///
+    /// let ids: Future<Vec<i64>, ()> = future::value(vec![1, 2, 3]);
+    /// let names = ids.and_then_iter(|id| future::value(format!("user{}", id)));
+    /// assert_eq!(vec!["user1", "user2", "user3"], future::await(names).unwrap());
+    /// ```
+    pub fn and_then_iter<F, A>(self, f: F) -> Future<Vec<A>, E>
+        where F: FnMut(T) -> Future<A, E>, F: 'static,
+              A: 'static
+    {
+        self.and_thenf(move |items| traverse(items, f))
+    }
+
+    /// Like `and_then_iter`, except at most `limit` of the `Future`s returned by `f` are in flight
+    /// at once.
+    pub fn and_then_iter_limited<F, A>(self, limit: usize, f: F) -> Future<Vec<A>, E>
+        where F: FnMut(T) -> Future<A, E>, F: 'static,
+              A: 'static
+    {
+        self.and_thenf(move |items| traverse_limited(items, limit, f))
+    }
+
+    /// Like `and_then_iter_limited`, except results are streamed in completion order, paired with
+    /// the index of the item they were produced from. See `traverse_unordered`.
+    pub fn and_then_iter_unordered<F, A>(self, limit: usize, f: F) -> FutureStream<(usize, A), E>
+        where F: FnMut(T) -> Future<A, E>, F: 'static,
+              A: Send + 'static,
+              E: Send
+    {
+        self.map(move |items| traverse_unordered(items, limit, f)).flatten_stream()
+    }
+}
+
+struct Traversal<I, F, A, E>
+    where I: Iterator, A: 'static, E: 'static
+{
+    f: Mutex<F>,
+    state: Mutex<TraversalState<I, A, E>>
+}
+
+struct TraversalState<I, A, E>
+    where A: 'static, E: 'static
+{
+    items: I,
+    exhausted: bool,
+    pumping: bool,
+    in_flight: usize,
+    limit: usize,
+    started: usize,
+    output: Option<TraversalOutput<A, E>>
+}
+
+enum TraversalOutput<A, E>
+    where A: 'static, E: 'static
+{
+    Ordered(Vec<Option<A>>, FutureSetter<Vec<A>, E>),
+    Unordered(FutureStreamSetter<(usize, A), E>)
+}
+
+impl<I, F, A, E> Traversal<I, F, A, E>
+    where I: Iterator + 'static,
+          F: FnMut(I::Item) -> Future<A, E> + 'static,
+          A: 'static,
+          E: 'static
+{
+    fn start(items: I, limit: usize, f: F, output: TraversalOutput<A, E>) {
+        assert!(limit > 0, "traversals require a limit of at least 1");
+        let traversal = Arc::new(Traversal {
+            f: Mutex::new(f),
+            state: Mutex::new(TraversalState {
+                items: items,
+                exhausted: false,
+                pumping: false,
+                in_flight: 0,
+                limit: limit,
+                started: 0,
+                output: Some(output)
+            })
+        });
+        Traversal::pump(&traversal);
+    }
+
+    /// Starts `Future`s until the limit is reached or the items run out. Completions that happen
+    /// while a pump is already running leave the starting of further items to that pump, so
+    /// already-resolved `Future`s don't recurse once per item.
+    fn pump(traversal: &Arc<Self>) {
+        loop {
+            let (index, item) = {
+                let mut state = traversal.state.lock().unwrap();
+                if state.output.is_none() || state.exhausted || state.in_flight >= state.limit {
+                    state.pumping = false;
+                    return;
+                }
+                state.pumping = true;
+                match state.items.next() {
+                    Some(item) => {
+                        state.in_flight += 1;
+                        state.started += 1;
+                        if let Some(TraversalOutput::Ordered(ref mut results, _)) = state.output {
+                            results.push(None);
+                        }
+                        (state.started - 1, item)
+                    },
+                    None => {
+                        state.exhausted = true;
+                        state.pumping = false;
+                        let finished = state.take_finished();
+                        drop(state);
+                        if let Some(output) = finished {
+                            output.succeed();
+                        }
+                        return;
+                    }
+                }
+            };
+
+            let future = (&mut *traversal.f.lock().unwrap())(item);
+            let traversal = traversal.clone();
+            future.register(move |result| Traversal::complete(&traversal, index, result));
+        }
+    }
+
+    fn complete(traversal: &Arc<Self>, index: usize, result: Result<A, E>) {
+        let mut state = traversal.state.lock().unwrap();
+        state.in_flight -= 1;
+        match result {
+            Ok(a) => {
+                match state.output {
+                    Some(TraversalOutput::Ordered(ref mut results, _)) => results[index] = Some(a),
+                    Some(TraversalOutput::Unordered(ref setter)) => setter.send((index, a)),
+                    None => {}
+                }
+                let finished = state.take_finished();
+                let pumping = state.pumping;
+                drop(state);
+                if let Some(output) = finished {
+                    output.succeed();
+                } else if !pumping {
+                    Traversal::pump(traversal);
+                }
+            },
+            Err(e) => {
+                let output = state.output.take();
+                drop(state);
+                if let Some(output) = output {
+                    output.fail(e);
+                }
+            }
+        }
+    }
+}
+
+impl<I, A: 'static, E: 'static> TraversalState<I, A, E> {
+    fn take_finished(&mut self) -> Option<TraversalOutput<A, E>> {
+        if !self.exhausted || self.in_flight > 0 {
+            return None;
+        }
+        self.output.take()
+    }
+}
+
+impl<A: 'static, E: 'static> TraversalOutput<A, E> {
+    fn succeed(self) {
+        match self {
+            TraversalOutput::Ordered(results, setter) => {
+                let results = results.into_iter().map(|a| a.unwrap()).collect();
+                setter.set_result(Ok(results): Result<Vec<A>, E>);
+            },
+            TraversalOutput::Unordered(setter) => setter.close()
+        }
+    }
+
+    fn fail(self, e: E) {
+        match self {
+            TraversalOutput::Ordered(_, setter) => setter.set_result(Err(e)),
+            TraversalOutput::Unordered(setter) => setter.fail(e)
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::{await, err, value};
+    use std::sync::{Arc, Mutex};
+    use std::usize;
+
+    #[test]
+    fn traverse_limited_respects_limit_and_order() {
+        let setters = Arc::new(Mutex::new(vec![]));
+        let setters2 = setters.clone();
+        let f = traverse_limited(0..4, 2, move |i| {
+            let (future, setter) = new::<i64, ()>();
+            setters2.lock().unwrap().push((i, setter));
+            future
+        });
+
+        assert_eq!(setters.lock().unwrap().len(), 2);
+        let (i, setter) = setters.lock().unwrap().remove(1);
+        setter.set_result(Ok(i * 10): Result<i64, ()>);
+        assert_eq!(setters.lock().unwrap().len(), 2);
+
+        loop {
+            let next = setters.lock().unwrap().pop();
+            match next {
+                Some((i, setter)) => setter.set_result(Ok(i * 10): Result<i64, ()>),
+                None => break
+            }
+        }
+        assert_eq!(await(f), Ok(vec![0, 10, 20, 30]));
+    }
+
+    #[test]
+    fn traverse_fails_fast() {
+        let f = traverse(vec![1, 2, 3], |i| if i == 2 { err(i) } else { value(i) });
+        assert_eq!(await(f), Err(2));
+    }
+
+    #[test]
+    fn traverse_unordered_streams_in_completion_order() {
+        let setters = Arc::new(Mutex::new(vec![]));
+        let setters2 = setters.clone();
+        let completions = value::<Vec<i64>, ()>(vec![1, 2, 3])
+            .and_then_iter_unordered(usize::MAX, move |i| {
+                let (future, setter) = new::<i64, ()>();
+                setters2.lock().unwrap().push((i, setter));
+                future
+            });
+
+        let mut setters = setters.lock().unwrap();
+        while let Some((i, setter)) = setters.pop() {
+            setter.set_result(Ok(i * 10): Result<i64, ()>);
+        }
+        assert_eq!(await(completions.collect()), Ok(vec![(2, 30), (1, 20), (0, 10)]));
+    }
+
+    #[test]
+    fn collect_map_values_fails_fast_or_skips_keys() {
+        let lookup = |id: &i64| if *id % 2 == 0 { err(*id) } else { value(*id * 10) };
+        assert_eq!(await(collect_map_values(vec![1, 2, 3], 1, KeyErrors::FailFast, lookup)), Err(2));
+
+        let values = await(collect_map_values(vec![1, 2, 3], 1, KeyErrors::Skip, lookup)).unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!((values[&1], values[&3]), (10, 30));
+    }
+
+    #[test]
+    fn traverse_handles_empty_input() {
+        let f = traverse(Vec::<i64>::new(), |i| value::<i64, ()>(i));
+        assert_eq!(await(f), Ok(vec![]));
+    }
+}
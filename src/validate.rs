@@ -0,0 +1,76 @@
+use super::{new_sequence, Future, Never};
+use std::iter::FromIterator;
+
+/// Collects an iterator of `Future`s without failing fast, for validation-style use where every
+/// error should be reported. Complements collecting into a `Future<C, E>`, which fails with the
+/// first error.
+pub trait CollectValidated<A, E>: Iterator<Item = Future<A, E>> + Sized
+    where A: 'static, E: 'static
+{
+    /// Resolves with every success if all of the `Future`s succeed, and with every error, in
+    /// input order, otherwise.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use future::CollectValidated;
+    ///
+    /// let fields = vec![
+    ///     future::value::<i64, String>(1),
+    ///     future::err(String::from("name is required")),
+    ///     future::err(String::from("age must be positive"))
+    /// ];
+    /// let validated = fields.into_iter().collect_validated::<Vec<_>>();
+    /// assert_eq!(
+    ///     Err(vec![String::from("name is required"), String::from("age must be positive")]),
+    ///     future::await(validated).unwrap()
+    /// );
+    /// ```
+    fn collect_validated<C>(self) -> Future<Result<C, Vec<E>>, Never>
+        where C: FromIterator<A> + 'static
+    {
+        // Each result goes into its own slot, so resolving inputs in any order never runs a chain
+        // of continuations, one per input.
+        let futures = self.collect::<Vec<_>>();
+        let (results, setter) = new_sequence::<Result<A, E>, Never>(futures.len());
+        for (i, future) in futures.into_iter().enumerate() {
+            let setter = setter.clone();
+            future.register(move |result| {
+                setter.set_slot(i, result);
+            });
+        }
+        results.map(|results| {
+            let mut successes = vec![];
+            let mut errors = vec![];
+            for result in results {
+                match result {
+                    Ok(a) => successes.push(a),
+                    Err(e) => errors.push(e)
+                }
+            }
+            if errors.is_empty() {
+                Ok(successes.into_iter().collect::<C>())
+            } else {
+                Err(errors)
+            }
+        })
+    }
+}
+
+impl<I, A, E> CollectValidated<A, E> for I
+    where I: Iterator<Item = Future<A, E>>,
+          A: 'static, E: 'static
+{}
+
+mod test {
+    use super::*;
+    use super::super::{await, new, value};
+
+    #[test]
+    fn large_inputs_resolved_out_of_order_dont_recurse() {
+        let (first, setter) = new::<u32, ()>();
+        let rest = (1..100000).map(|i| value(i));
+        let validated = Some(first).into_iter().chain(rest).collect_validated::<Vec<_>>();
+        setter.set_result(Ok(0): Result<u32, ()>);
+        assert_eq!(await(validated).map(|result| result.map(|values| values.len())), Ok(Ok(100000)));
+    }
+}
@@ -0,0 +1,164 @@
+use super::{DroppedSetterError, Future};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// A cloneable handle any number of threads can block on until a `Future` resolves, sharing its
+/// result rather than consuming it. Created with `Future::wait_handle`.
+/// # Examples
+/// ```
+/// use future;
+/// use std::thread;
+///
+/// let (f, setter) = future::new::<String, ()>();
+/// let handle = f.wait_handle();
+/// let workers = (0..3).map(|_| {
+///     let handle = handle.clone();
+///     thread::spawn(move || match *handle.wait().unwrap() {
+///         Ok(ref config) => config.len(),
+///         Err(()) => 0
+///     })
+/// }).collect::<Vec<_>>();
+///
+/// setter.set_result(Ok(String::from("config")): Result<String, ()>);
+/// for worker in workers {
+///     assert_eq!(6, worker.join().unwrap());
+/// }
+/// ```
+pub struct WaitHandle<A, E> {
+    inner: Arc<(Mutex<WaitState<A, E>>, Condvar)>
+}
+
+enum WaitState<A, E> {
+    Pending,
+    Done(Arc<Result<A, E>>),
+    Dropped
+}
+
+/// Wakes waiters when the result is set, or when the callback is dropped because the
+/// `FutureSetter` was dropped without setting it.
+struct Notifier<A, E> {
+    inner: Arc<(Mutex<WaitState<A, E>>, Condvar)>
+}
+
+impl<A: 'static, E: 'static> Future<A, E> {
+    /// Converts this `Future` into a `WaitHandle`.
+    pub fn wait_handle(self) -> WaitHandle<A, E> {
+        let inner = Arc::new((Mutex::new(WaitState::Pending), Condvar::new()));
+        let notifier = Notifier { inner: inner.clone() };
+        self.register(move |result| notifier.notify(WaitState::Done(Arc::new(result))));
+        WaitHandle { inner: inner }
+    }
+
+    /// Stores the result in `pair` and notifies every thread waiting on its `Condvar` once this
+    /// `Future` resolves, for legacy code that already blocks on a mutex and condition variable.
+    /// If the `FutureSetter` is dropped without setting the result, nothing is stored or notified.
+    /// # Examples
+    /// ```
+    /// use future;
+    /// use std::sync::{Arc, Condvar, Mutex};
+    /// use std::thread;
+    ///
+    /// let pair = Arc::new((Mutex::new(None), Condvar::new()));
+    /// let (f, setter) = future::new::<u64, String>();
+    /// f.notify_condvar(pair.clone());
+    ///
+    /// thread::spawn(move || setter.set_result(Ok(42): Result<u64, String>));
+    /// let &(ref slot, ref condvar) = &*pair;
+    /// let mut slot = slot.lock().unwrap();
+    /// while slot.is_none() {
+    ///     slot = condvar.wait(slot).unwrap();
+    /// }
+    /// assert_eq!(Some(Ok(42)), *slot);
+    /// ```
+    pub fn notify_condvar(self, pair: Arc<(Mutex<Option<Result<A, E>>>, Condvar)>) {
+        self.register(move |result| {
+            let &(ref slot, ref condvar) = &*pair;
+            *slot.lock().unwrap() = Some(result);
+            condvar.notify_all();
+        });
+    }
+}
+
+impl<A, E> WaitHandle<A, E> {
+    /// Blocks until the `Future` resolves, returning its shared result.
+    /// # Failures
+    /// Returns Err(DroppedSetterError) if the FutureSetter goes out of scope without setting the
+    /// result.
+    pub fn wait(&self) -> Result<Arc<Result<A, E>>, DroppedSetterError> {
+        let &(ref state, ref condvar) = &*self.inner;
+        let mut state = state.lock().unwrap();
+        loop {
+            match *state {
+                WaitState::Pending => state = condvar.wait(state).unwrap(),
+                WaitState::Done(ref result) => return Ok(result.clone()),
+                WaitState::Dropped => return Err(DroppedSetterError)
+            }
+        }
+    }
+
+    /// Like `wait`, but gives up after `timeout`, returning `None`.
+    pub fn wait_timeout(&self, timeout: Duration) -> Option<Result<Arc<Result<A, E>>, DroppedSetterError>> {
+        let deadline = Instant::now() + timeout;
+        let &(ref state, ref condvar) = &*self.inner;
+        let mut state = state.lock().unwrap();
+        loop {
+            match *state {
+                WaitState::Done(ref result) => return Some(Ok(result.clone())),
+                WaitState::Dropped => return Some(Err(DroppedSetterError)),
+                WaitState::Pending => {}
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            state = condvar.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Whether the `Future` has resolved, or its `FutureSetter` has been dropped.
+    pub fn is_done(&self) -> bool {
+        match *self.inner.0.lock().unwrap() {
+            WaitState::Pending => false,
+            _ => true
+        }
+    }
+}
+
+impl<A, E> Clone for WaitHandle<A, E> {
+    fn clone(&self) -> WaitHandle<A, E> {
+        WaitHandle { inner: self.inner.clone() }
+    }
+}
+
+impl<A, E> Notifier<A, E> {
+    fn notify(&self, done: WaitState<A, E>) {
+        let &(ref state, ref condvar) = &*self.inner;
+        let mut state = state.lock().unwrap();
+        if let WaitState::Pending = *state {
+            *state = done;
+            condvar.notify_all();
+        }
+    }
+}
+
+impl<A, E> Drop for Notifier<A, E> {
+    fn drop(&mut self) {
+        self.notify(WaitState::Dropped);
+    }
+}
+
+mod test {
+    use super::*;
+    use super::super::new;
+
+    #[test]
+    fn wait_reports_a_dropped_setter() {
+        let (f, setter) = new::<(), ()>();
+        let handle = f.wait_handle();
+        assert!(handle.wait_timeout(Duration::from_millis(5)).is_none());
+
+        drop(setter);
+        assert!(handle.is_done());
+        assert!(handle.wait().is_err());
+    }
+}
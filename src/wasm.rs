@@ -0,0 +1,87 @@
+//! Hooks for running in environments without threads, such as the browser. Enabled with the
+//! `wasm` feature.
+//!
+//! With a `Spawner` installed, `future::run` hands its work to the spawner instead of starting a
+//! thread; with a `Timer` installed, the `timer` module schedules through it (e.g. with JavaScript's
+//! `setTimeout`) instead of its own thread. Until one is installed, the usual thread is used, which
+//! panics on `wasm32` targets. On `wasm32` targets, the functions that block the calling thread,
+//! such as `future::await`, are left out entirely, so results must be consumed with callbacks
+//! like `Future::resolve`.
+
+use std::boxed::FnBox;
+use std::sync::{Arc, Mutex, Once, ONCE_INIT};
+use std::time::Duration;
+
+/// Runs the work started by `future::run`.
+pub trait Spawner: Send + Sync {
+    fn spawn(&self, task: Box<FnBox() -> () + Send>);
+}
+
+/// Calls back after a delay, for the `timer` module.
+pub trait Timer: Send + Sync {
+    fn set_timeout(&self, delay: Duration, callback: Box<FnBox() -> () + Send>);
+}
+
+struct Hooks {
+    spawner: Option<Arc<Spawner>>,
+    timer: Option<Arc<Timer>>
+}
+
+static HOOKS_INIT: Once = ONCE_INIT;
+static mut HOOKS: *const Mutex<Hooks> = 0 as *const _;
+
+/// Installs the `Spawner` used by `future::run`.
+/// # Examples
+/// ```
+/// use future;
+/// use future::wasm::{self, Spawner};
+/// use std::boxed::FnBox;
+///
+/// struct Inline;
+///
+/// impl Spawner for Inline {
+///     fn spawn(&self, task: Box<FnBox() -> () + Send>) {
+///         task()
+///     }
+/// }
+///
+/// wasm::set_spawner(Inline);
+/// let f = future::run(|| Ok(1): Result<i64, ()>);
+/// assert!(f.is_resolved());
+/// # wasm::clear_spawner();
+/// ```
+pub fn set_spawner<S: Spawner + 'static>(spawner: S) {
+    hooks().lock().unwrap().spawner = Some(Arc::new(spawner));
+}
+
+/// Goes back to starting a thread for each `future::run`.
+pub fn clear_spawner() {
+    hooks().lock().unwrap().spawner = None;
+}
+
+/// Installs the `Timer` used by the `timer` module. Timers already scheduled are unaffected.
+pub fn set_timer<T: Timer + 'static>(timer: T) {
+    hooks().lock().unwrap().timer = Some(Arc::new(timer));
+}
+
+/// Goes back to the timer thread.
+pub fn clear_timer() {
+    hooks().lock().unwrap().timer = None;
+}
+
+pub(crate) fn spawner() -> Option<Arc<Spawner>> {
+    hooks().lock().unwrap().spawner.clone()
+}
+
+pub(crate) fn timer() -> Option<Arc<Timer>> {
+    hooks().lock().unwrap().timer.clone()
+}
+
+fn hooks() -> &'static Mutex<Hooks> {
+    unsafe {
+        HOOKS_INIT.call_once(|| {
+            HOOKS = Box::into_raw(box Mutex::new(Hooks { spawner: None, timer: None }));
+        });
+        &*HOOKS
+    }
+}